@@ -0,0 +1,131 @@
+//! Compares the bulk pixel operations of the `Bitmap` trait going through the
+//! rows of the image (as `RgbaBitmap` and tarsila's `WrappedImage` do) with the
+//! default fallback that sets one pixel at a time, on a 2048x2048 canvas.
+//!
+//! Run with `cargo bench -p lapix --bench bulk_ops`.
+
+use lapix::color::BLACK;
+use lapix::{Bitmap, Color, Event, Point, Rect, RgbaBitmap, Size, State, Transform};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+const SIDE: i32 = 2048;
+
+/// An `RgbaBitmap` that only implements the required methods of `Bitmap`, so
+/// the bulk operations fall back to one pixel at a time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+struct PerPixel(RgbaBitmap);
+
+impl Bitmap for PerPixel {
+    fn new(size: Size<i32>, color: Color) -> Self {
+        Self(RgbaBitmap::new(size, color))
+    }
+    fn size(&self) -> Size<i32> {
+        self.0.size()
+    }
+    fn width(&self) -> i32 {
+        self.0.width()
+    }
+    fn height(&self) -> i32 {
+        self.0.height()
+    }
+    fn pixel(&self, point: Point<i32>) -> Color {
+        self.0.pixel(point)
+    }
+    fn set_pixel(&mut self, point: Point<i32>, color: Color) {
+        self.0.set_pixel(point, color);
+    }
+    fn bytes(&self) -> &[u8] {
+        self.0.bytes()
+    }
+    fn from_parts(size: Size<i32>, bytes: &[u8]) -> Self {
+        Self(RgbaBitmap::from_parts(size, bytes))
+    }
+    fn set_from(&mut self, other: &Self) {
+        self.0.set_from(&other.0);
+    }
+}
+
+/// An operation to time, with the per-pixel and the row versions
+type Op = (&'static str, fn(&mut PerPixel), fn(&mut RgbaBitmap));
+
+/// Time an operation on a fresh image
+fn time<IMG: Bitmap>(op: fn(&mut IMG)) -> Duration {
+    let mut img = IMG::new(Size::new(SIDE, SIDE), Color::new(10, 20, 30, 128));
+    let t0 = Instant::now();
+    op(&mut img);
+
+    t0.elapsed()
+}
+
+fn report(name: &str, slow: Duration, fast: Duration) {
+    println!("{name}:");
+    println!("  per pixel: {slow:?}, by rows: {fast:?}");
+    println!("  speedup: {:.1}x", slow.as_secs_f64() / fast.as_secs_f64());
+}
+
+fn fill<IMG: Bitmap>(img: &mut IMG) {
+    img.fill_rect(Rect::new(0, 0, SIDE, SIDE), BLACK);
+}
+
+fn copy<IMG: Bitmap>(img: &mut IMG) {
+    let src = img.clone();
+    img.copy_rect(
+        &src,
+        Rect::new(SIDE / 4, SIDE / 4, SIDE / 2, SIDE / 2),
+        Point::ZERO,
+    );
+}
+
+fn blit<IMG: Bitmap>(img: &mut IMG) {
+    let src = img.clone();
+    img.blit(&src, Rect::new(0, 0, SIDE, SIDE), Point::new(1, 1), 200);
+}
+
+fn silhouette<IMG: Bitmap>(img: &mut IMG) {
+    Transform::Silhouete.apply(img, Vec::new());
+}
+
+/// Time blending a project with a few half transparent layers
+fn blend_layers<IMG>() -> Duration
+where
+    IMG: Bitmap + Serialize + for<'de> Deserialize<'de>,
+{
+    let mut state = State::<IMG>::new(Size::new(SIDE, SIDE));
+    for i in 0..3 {
+        let layer = i as usize + 1;
+        let color = Color::new(i * 80, 0, 0, 200);
+        state.execute(Event::NewLayerAbove).unwrap();
+        state.execute(Event::SwitchLayer(layer)).unwrap();
+        state.execute(Event::SetMainColor(color)).unwrap();
+        state.execute(Event::Bucket(Point::ZERO)).unwrap();
+        state
+            .execute(Event::ChangeLayerOpacity(layer, 100))
+            .unwrap();
+    }
+
+    let t0 = Instant::now();
+    state.layers().blended();
+
+    t0.elapsed()
+}
+
+fn main() {
+    let ops: [Op; 4] = [
+        ("fill_rect 2048x2048", fill, fill),
+        ("copy_rect 1024x1024", copy, copy),
+        ("blit 2048x2048", blit, blit),
+        ("silhouette transform 2048x2048", silhouette, silhouette),
+    ];
+
+    for (name, slow, fast) in ops {
+        report(name, time(slow), time(fast));
+    }
+
+    report(
+        "blend 4 layers 2048x2048",
+        blend_layers::<PerPixel>(),
+        blend_layers::<RgbaBitmap>(),
+    );
+}
@@ -0,0 +1,63 @@
+//! Measures how much memory the undo history takes for large edits, compared
+//! to storing one entry per changed pixel (the lower bound of what the old
+//! per-pixel `SetPixel` reversals took).
+//!
+//! Run with `cargo bench -p lapix --bench undo_storage`.
+
+use lapix::color::BLACK;
+use lapix::{Color, Event, Point, RgbaBitmap, Size, State};
+use std::mem::size_of;
+use std::time::Instant;
+
+const SIDE: i32 = 1024;
+
+/// Run some events on a fresh canvas and report the size of the history
+fn bench(name: &str, changed_pixels: usize, events: Vec<Event>) {
+    let mut state = State::<RgbaBitmap>::new(Size::new(SIDE, SIDE));
+    let t0 = Instant::now();
+
+    for event in events {
+        state.execute(event).unwrap();
+    }
+
+    let elapsed = t0.elapsed();
+    let t0 = Instant::now();
+    state.execute(Event::Undo).unwrap();
+    state.execute(Event::Redo).unwrap();
+    let undo_redo = t0.elapsed();
+
+    let per_pixel = changed_pixels * size_of::<(usize, Point<i32>, Color)>();
+    let runs = state.history().memory_size();
+
+    println!("{name}:");
+    println!("  edit: {elapsed:?}, undo + redo: {undo_redo:?}");
+    println!("  per-pixel reversals: >= {:>10} bytes", per_pixel);
+    println!("  run-length history:     {:>10} bytes", runs);
+    println!("  saving: {:.1}x", per_pixel as f64 / runs as f64);
+}
+
+fn main() {
+    let area = (SIDE * SIDE) as usize;
+
+    bench(
+        "bucket fill 1024x1024",
+        area,
+        vec![Event::Bucket(Point::new(0, 0))],
+    );
+
+    let events = vec![
+        Event::SetMainColor(BLACK),
+        Event::Bucket(Point::new(0, 0)),
+        Event::StartSelection(Point::new(0, 0)),
+        Event::EndSelection(Point::new(SIDE - 1, SIDE - 1)),
+        Event::DeleteSelection,
+    ];
+    bench("fill + delete selection 1024x1024", area * 2, events);
+
+    let mut events = vec![Event::BrushStart];
+    for y in 0..SIDE {
+        events.push(Event::BrushStroke(Point::new(y % 64, y)));
+    }
+    events.push(Event::BrushEnd);
+    bench("brush stroke across the canvas", 2 * SIDE as usize, events);
+}
@@ -1,21 +1,24 @@
-use crate::{Bitmap, CanvasEffect, Color, Layer, Layers, Point};
+use crate::{
+    Bitmap, Canvas, CanvasEffect, Color, FreeImage, Layer, Palette, Point, Rect, Selection, Size,
+    State,
+};
 use std::fmt::Debug;
+use std::mem::{size_of, size_of_val};
 
 pub type LayerIndex = usize;
 
-pub struct Action<IMG>(Vec<AtomicAction<IMG>>);
-
-impl<IMG> Default for Action<IMG> {
-    fn default() -> Self {
-        Self(Default::default())
-    }
+/// A set of [`AtomicAction`]s that are undone (or redone) together, with a
+/// human-readable label describing it (e.g. "Brush stroke")
+pub struct Action<IMG> {
+    label: String,
+    actions: Vec<AtomicAction<IMG>>,
 }
 
 impl<IMG> Debug for Action<IMG> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        f.write_str("Action([")?;
+        f.write_fmt(format_args!("Action({:?}, [", self.label))?;
 
-        for action in self.0.iter() {
+        for action in self.actions.iter() {
             f.write_fmt(format_args!("{:?}, ", action))?;
         }
 
@@ -23,15 +26,20 @@ impl<IMG> Debug for Action<IMG> {
     }
 }
 
-impl<IMG> From<Vec<AtomicAction<IMG>>> for Action<IMG> {
-    fn from(actions: Vec<AtomicAction<IMG>>) -> Self {
-        Self(actions)
+impl<IMG: Bitmap> Action<IMG> {
+    pub fn new<S: Into<String>>(label: S, actions: Vec<AtomicAction<IMG>>) -> Self {
+        Self {
+            label: label.into(),
+            actions,
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
     }
-}
 
-impl<IMG: Bitmap> Action<IMG> {
     pub fn push(&mut self, action: AtomicAction<IMG>) {
-        self.0.push(action);
+        self.actions.push(action);
     }
 
     pub fn append(&mut self, actions: Vec<AtomicAction<IMG>>) {
@@ -40,63 +48,398 @@ impl<IMG: Bitmap> Action<IMG> {
         }
     }
 
-    pub fn apply(mut self, layers: &mut Layers<IMG>) -> CanvasEffect {
+    /// Add all the atomic actions of another action after the ones of this
+    /// one, so that they are undone first
+    pub fn merge(&mut self, other: Self) {
+        self.actions.extend(other.actions);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    /// Approximate amount of memory used by this action, in bytes
+    pub fn memory_size(&self) -> usize {
+        size_of::<Self>()
+            + self.label.len()
+            + self
+                .actions
+                .iter()
+                .map(|action| action.memory_size())
+                .sum::<usize>()
+    }
+
+    /// Merge all the pixel changes of each layer into a single
+    /// [`PixelDiff`], if this action contains nothing but pixel changes. This
+    /// is the case for e.g. brush strokes, which are recorded one segment at a
+    /// time
+    pub fn compact(&mut self) {
+        let only_pixels = self
+            .actions
+            .iter()
+            .all(|action| matches!(action, AtomicAction::SetPixels(..)));
+
+        if !only_pixels || self.actions.len() < 2 {
+            return;
+        }
+
+        let mut layers: Vec<(LayerIndex, Vec<_>)> = Vec::new();
+
+        for action in std::mem::take(&mut self.actions) {
+            if let AtomicAction::SetPixels(i, diff) = action {
+                match layers.iter_mut().find(|(layer, _)| *layer == i) {
+                    Some((_, pixels)) => pixels.extend(diff.pixels()),
+                    None => layers.push((i, diff.pixels().collect())),
+                }
+            }
+        }
+
+        for (i, pixels) in layers {
+            self.push(AtomicAction::SetPixels(i, PixelDiff::new(pixels)));
+        }
+    }
+
+    /// Apply all the atomic actions in reverse order. Returns the
+    /// [`CanvasEffect`] caused, along with the inverse action, that is, the one
+    /// that would revert what was just applied (used to redo an undone action
+    /// and vice-versa)
+    pub fn apply(mut self, state: &mut State<IMG>) -> (CanvasEffect, Self) {
         let mut effect = CanvasEffect::None;
+        let mut inverse = Self::new(std::mem::take(&mut self.label), Vec::new());
+
+        while let Some(action) = self.actions.pop() {
+            let (fx, inv) = action.apply(state);
+            effect = effect.merge(fx);
+            inverse.push(inv);
+        }
+
+        (effect, inverse)
+    }
+}
+
+/// A horizontal run of pixels of the same color
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Run {
+    x: i32,
+    y: i32,
+    len: u32,
+    color: Color,
+}
+
+/// A set of pixels of a layer and the colors they should be set to, stored as
+/// run-length encoded rows, so that large areas (e.g. a bucket fill) take
+/// little memory
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PixelDiff {
+    runs: Vec<Run>,
+}
+
+impl PixelDiff {
+    /// Create a diff from a list of points and their colors. If a point appears
+    /// more than once, only its first color is kept (as it is the original
+    /// one, when the list is built from reversals)
+    pub fn new(mut pixels: Vec<(Point<i32>, Color)>) -> Self {
+        // The sort is stable, so the first occurrence of a point comes first
+        pixels.sort_by_key(|(p, _)| (p.y, p.x));
+
+        let mut diff = Self::default();
+        let mut last = None;
+
+        for (p, color) in pixels {
+            if last == Some(p) {
+                continue;
+            }
+            last = Some(p);
+            diff.push(p, color);
+        }
+        diff.runs.shrink_to_fit();
+
+        diff
+    }
+
+    /// Add a pixel to the diff. Must be called in row-major order
+    fn push(&mut self, p: Point<i32>, color: Color) {
+        match self.runs.last_mut() {
+            Some(run) if run.y == p.y && run.x + run.len as i32 == p.x && run.color == color => {
+                run.len += 1;
+            }
+            _ => self.runs.push(Run {
+                x: p.x,
+                y: p.y,
+                len: 1,
+                color,
+            }),
+        }
+    }
+
+    /// Iterate over all the pixels in the diff and their colors
+    pub fn pixels(&self) -> impl Iterator<Item = (Point<i32>, Color)> + '_ {
+        self.runs.iter().flat_map(|run| {
+            (run.x..run.x + run.len as i32).map(move |x| (Point::new(x, run.y), run.color))
+        })
+    }
+
+    /// The smallest rectangle containing all the pixels of the diff, if any
+    pub fn bounds(&self) -> Option<Rect<i32>> {
+        self.runs
+            .iter()
+            .map(|run| Rect::new(run.x, run.y, run.len as i32, 1))
+            .reduce(Rect::union)
+    }
+
+    /// Number of runs used to store the diff
+    pub fn run_count(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// Approximate amount of memory used by this diff, in bytes
+    pub fn memory_size(&self) -> usize {
+        size_of::<Self>() + self.runs.capacity() * size_of::<Run>()
+    }
+
+    /// Set the pixels of the diff in a canvas, returning the diff that
+    /// reverts it
+    pub fn apply<IMG: Bitmap>(&self, canvas: &mut Canvas<IMG>) -> Self {
+        let mut inverse = Self::default();
 
-        while let Some(action) = self.0.pop() {
-            effect = action.apply(layers);
+        for (p, color) in self.pixels() {
+            if !canvas.is_in_bounds(p) {
+                continue;
+            }
+            inverse.push(p, canvas.pixel(p));
+            canvas.set_pixel(p, color);
         }
+        inverse.runs.shrink_to_fit();
 
-        effect
+        inverse
     }
 }
 
 pub enum AtomicAction<IMG> {
-    SetPixel(LayerIndex, Point<i32>, Color),
+    SetPixels(LayerIndex, PixelDiff),
     DestroyLayer(LayerIndex),
     CreateLayer(LayerIndex, Layer<IMG>),
     SetLayerCanvas(LayerIndex, IMG),
+    SwapLayers(LayerIndex, LayerIndex),
+    SetLayerVisibility(LayerIndex, bool),
+    SetLayerOpacity(LayerIndex, u8),
+    SetPalette(Palette),
+    SetSpritesheet(Size<u8>),
+    SetFreeImage(Option<FreeImage<IMG>>, Option<Selection>),
 }
 
 impl<IMG> Debug for AtomicAction<IMG> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         match self {
-            Self::SetPixel(i, p, c) => f
-                .debug_tuple("SetPixel")
+            Self::SetPixels(i, diff) => f
+                .debug_tuple("SetPixels")
                 .field(&i)
-                .field(&p)
-                .field(&c)
+                .field(&diff.run_count())
                 .finish(),
             Self::DestroyLayer(i) => f.debug_tuple("DestroyLayer").field(&i).finish(),
             Self::CreateLayer(i, _) => f.debug_tuple("CreateLayer").field(&i).finish(),
             Self::SetLayerCanvas(i, _) => f.debug_tuple("SetLayerCanvas").field(&i).finish(),
+            Self::SwapLayers(i, j) => f.debug_tuple("SwapLayers").field(&i).field(&j).finish(),
+            Self::SetLayerVisibility(i, v) => f
+                .debug_tuple("SetLayerVisibility")
+                .field(&i)
+                .field(&v)
+                .finish(),
+            Self::SetLayerOpacity(i, a) => f
+                .debug_tuple("SetLayerOpacity")
+                .field(&i)
+                .field(&a)
+                .finish(),
+            Self::SetPalette(p) => f.debug_tuple("SetPalette").field(&p).finish(),
+            Self::SetSpritesheet(s) => f.debug_tuple("SetSpritesheet").field(&s).finish(),
+            Self::SetFreeImage(img, s) => f
+                .debug_tuple("SetFreeImage")
+                .field(&img.as_ref().map(|img| img.rect))
+                .field(&s)
+                .finish(),
         }
     }
 }
 
 impl<IMG: Bitmap> AtomicAction<IMG> {
     pub fn set_pixel_vec(i: LayerIndex, values: Vec<(Point<i32>, Color)>) -> Vec<Self> {
-        values
-            .into_iter()
-            .map(|(p, c)| AtomicAction::SetPixel(i, p, c))
-            .collect()
+        if values.is_empty() {
+            return Vec::new();
+        }
+
+        vec![AtomicAction::SetPixels(i, PixelDiff::new(values))]
     }
 
-    pub fn apply(self, layers: &mut Layers<IMG>) -> CanvasEffect {
+    /// Approximate amount of memory used by this action, in bytes
+    pub fn memory_size(&self) -> usize {
+        let data = match self {
+            Self::SetPixels(_, diff) => diff.memory_size(),
+            Self::CreateLayer(_, layer) => layer.canvas().bytes().len(),
+            Self::SetLayerCanvas(_, img) => img.bytes().len(),
+            Self::SetPalette(palette) => size_of_val(palette.colors()),
+            Self::SetFreeImage(Some(img), _) => img.texture.bytes().len(),
+            _ => 0,
+        };
+
+        size_of::<Self>() + data
+    }
+
+    /// The [`CanvasEffect`] of applying this action. It is the same as the one
+    /// of applying its inverse, since both change the same pixels or layers.
+    pub fn canvas_effect(&self) -> CanvasEffect {
         match self {
-            Self::SetPixel(i, p, color) => {
-                layers.canvas_at_mut(i).set_pixel(p, color);
+            Self::SetPixels(i, diff) => match diff.bounds() {
+                Some(rect) => CanvasEffect::Update(*i, rect),
+                None => CanvasEffect::None,
+            },
+            Self::DestroyLayer(_)
+            | Self::CreateLayer(..)
+            | Self::SetLayerCanvas(..)
+            | Self::SwapLayers(..) => CanvasEffect::Layer,
+            // These are read from the state whenever the canvas is drawn
+            Self::SetLayerVisibility(..)
+            | Self::SetLayerOpacity(..)
+            | Self::SetPalette(_)
+            | Self::SetSpritesheet(_)
+            | Self::SetFreeImage(..) => CanvasEffect::None,
+        }
+    }
+
+    /// Apply this action, returning the [`CanvasEffect`] caused and the
+    /// action that reverts it
+    pub fn apply(self, state: &mut State<IMG>) -> (CanvasEffect, Self) {
+        let effect = self.canvas_effect();
+        let inverse = match self {
+            Self::SetPixels(i, diff) => {
+                let canvas = state.layers_mut().canvas_at_mut(i);
+                Self::SetPixels(i, diff.apply(canvas))
             }
             Self::DestroyLayer(i) => {
-                layers.delete(i);
+                let layer = state.layers_mut().delete(i);
+                Self::CreateLayer(i, layer)
             }
             Self::CreateLayer(i, layer) => {
-                layers.add_at(i, layer);
+                state.layers_mut().add_at(i, layer);
+                Self::DestroyLayer(i)
             }
             Self::SetLayerCanvas(i, img) => {
-                layers.canvas_at_mut(i).set_img(img);
+                let canvas = state.layers_mut().canvas_at_mut(i);
+                let old = canvas.take_inner();
+                canvas.set_img(img);
+                Self::SetLayerCanvas(i, old)
+            }
+            Self::SwapLayers(i, j) => {
+                state.layers_mut().swap(i, j);
+                Self::SwapLayers(i, j)
+            }
+            Self::SetLayerVisibility(i, visible) => {
+                let old = state.layers_mut().get(i).visible();
+                state.layers_mut().set_visibility(i, visible);
+                Self::SetLayerVisibility(i, old)
+            }
+            Self::SetLayerOpacity(i, opacity) => {
+                let old = state.layers_mut().get(i).opacity();
+                state.layers_mut().set_opacity(i, opacity);
+                Self::SetLayerOpacity(i, old)
+            }
+            Self::SetPalette(palette) => Self::SetPalette(state.replace_palette(palette)),
+            Self::SetSpritesheet(size) => Self::SetSpritesheet(state.replace_spritesheet(size)),
+            Self::SetFreeImage(img, selection) => {
+                let (old_img, old_selection) = state.replace_free_image(img, selection);
+                Self::SetFreeImage(old_img, old_selection)
+            }
+        };
+
+        (effect, inverse)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::TRANSPARENT;
+    use crate::TestImage;
+    use test_case::test_case;
+
+    const RED: Color = Color::new(255, 0, 0, 255);
+    const BLUE: Color = Color::new(0, 0, 255, 255);
+
+    #[test_case(vec![], 0)]
+    #[test_case(vec![((0, 0), RED)], 1)]
+    #[test_case(vec![((0, 0), RED), ((1, 0), RED), ((2, 0), RED)], 1)]
+    #[test_case(vec![((2, 0), RED), ((0, 0), RED), ((1, 0), RED)], 1)]
+    #[test_case(vec![((0, 0), RED), ((1, 0), BLUE), ((2, 0), RED)], 3)]
+    #[test_case(vec![((0, 0), RED), ((2, 0), RED)], 2)]
+    #[test_case(vec![((0, 0), RED), ((0, 1), RED)], 2)]
+    #[test_case(vec![((0, 0), RED), ((1, 0), RED), ((0, 0), BLUE)], 1)]
+    fn pixel_diff_runs(pixels: Vec<((i32, i32), Color)>, runs: usize) {
+        let pixels = pixels.into_iter().map(|(p, c)| (p.into(), c)).collect();
+        assert_eq!(PixelDiff::new(pixels).run_count(), runs);
+    }
+
+    #[test]
+    fn pixel_diff_keeps_first_color() {
+        let p = Point::new(1, 1);
+        let diff = PixelDiff::new(vec![(p, RED), (p, BLUE), (p, BLUE)]);
+        assert_eq!(diff.pixels().collect::<Vec<_>>(), vec![(p, RED)]);
+    }
+
+    #[test_case(vec![], None)]
+    #[test_case(vec![((2, 3), RED)], Some((2, 3, 1, 1)))]
+    #[test_case(vec![((4, 0), RED), ((1, 2), BLUE), ((2, 2), RED)], Some((1, 0, 4, 3)))]
+    fn pixel_diff_bounds(pixels: Vec<((i32, i32), Color)>, bounds: Option<(i32, i32, i32, i32)>) {
+        let pixels = pixels.into_iter().map(|(p, c)| (p.into(), c)).collect();
+        assert_eq!(PixelDiff::new(pixels).bounds(), bounds.map(Rect::from));
+    }
+
+    #[test]
+    fn pixel_diff_apply_and_revert() {
+        let mut canvas = Canvas::<TestImage>::new(Size::new(4, 4));
+        canvas.set_pixel(Point::new(1, 0), BLUE);
+        let pixels = (0..4).map(|x| (Point::new(x, 0), RED)).collect();
+
+        let inverse = PixelDiff::new(pixels).apply(&mut canvas);
+        for x in 0..4 {
+            assert_eq!(canvas.pixel(Point::new(x, 0)), RED);
+        }
+        assert_eq!(inverse.run_count(), 3);
+
+        inverse.apply(&mut canvas);
+        assert_eq!(canvas.pixel(Point::new(0, 0)), TRANSPARENT);
+        assert_eq!(canvas.pixel(Point::new(1, 0)), BLUE);
+        assert_eq!(canvas.pixel(Point::new(2, 0)), TRANSPARENT);
+    }
+
+    #[test]
+    fn compact_merges_pixels_per_layer() {
+        let mut action = Action::<TestImage>::new("Brush stroke", Vec::new());
+        action.append(AtomicAction::set_pixel_vec(
+            0,
+            vec![(Point::new(0, 0), RED)],
+        ));
+        action.append(AtomicAction::set_pixel_vec(
+            0,
+            vec![(Point::new(1, 0), RED)],
+        ));
+        action.append(AtomicAction::set_pixel_vec(
+            1,
+            vec![(Point::new(0, 0), RED)],
+        ));
+        action.append(AtomicAction::set_pixel_vec(
+            0,
+            vec![(Point::new(0, 0), BLUE)],
+        ));
+        action.compact();
+
+        assert_eq!(action.actions.len(), 2);
+        match &action.actions[0] {
+            AtomicAction::SetPixels(0, diff) => {
+                assert_eq!(
+                    diff.pixels().collect::<Vec<_>>(),
+                    vec![(Point::new(0, 0), RED), (Point::new(1, 0), RED)]
+                );
             }
+            other => panic!("unexpected action {:?}", other),
         }
-        CanvasEffect::Layer
     }
 }
@@ -1,5 +1,5 @@
 use {
-    crate::{color, Color, Error, Point, Result, Size},
+    crate::{color, Color, Error, Point, Rect, Result, Size},
     image::ImageFormat,
 };
 
@@ -34,6 +34,118 @@ pub trait Bitmap: Clone {
     /// one, i.e. ignoring pixels out of bounds
     fn set_from(&mut self, other: &Self);
 
+    /// Get the bytes of a row of pixels (4 per pixel, in RGBA order)
+    fn row(&self, y: i32) -> &[u8] {
+        let len = self.width() as usize * 4;
+        &self.bytes()[y as usize * len..][..len]
+    }
+
+    /// Get the bytes of a row of pixels (4 per pixel, in RGBA order) to change
+    /// them in place. The bulk operations (e.g. [`fill_rect`]) go through it
+    /// when it is available, and set one pixel at a time when it is `None`
+    /// (the default), so implementing it is enough to make them fast.
+    ///
+    /// [`fill_rect`]: Bitmap::fill_rect
+    fn row_mut(&mut self, _y: i32) -> Option<&mut [u8]> {
+        None
+    }
+
+    /// Set all the pixels of a rectangle to a color. The parts of the
+    /// rectangle out of bounds are ignored.
+    fn fill_rect(&mut self, rect: Rect<i32>, color: Color) {
+        let rect = rect.clip_to(Rect::new(0, 0, self.width(), self.height()));
+        let rgba = [color.r, color.g, color.b, color.a];
+
+        for y in rect.y..rect.y + rect.h {
+            match self.row_mut(y) {
+                Some(row) => row_span(row, rect.x, rect.w)
+                    .chunks_exact_mut(4)
+                    .for_each(|pixel| pixel.copy_from_slice(&rgba)),
+                None => {
+                    for x in rect.x..rect.x + rect.w {
+                        self.set_pixel(Point::new(x, y), color);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Copy a rectangle of another image into this one, with its top left
+    /// corner at `dest`, replacing the pixels there. The parts out of bounds of
+    /// either image are ignored.
+    fn copy_rect(&mut self, src: &Self, rect: Rect<i32>, dest: Point<i32>) {
+        let (from, to) = copy_area(src.size(), rect, self.size(), dest);
+
+        for j in 0..to.h {
+            match self.row_mut(to.y + j) {
+                Some(row) => row_span(row, to.x, to.w).copy_from_slice(
+                    &src.row(from.y + j)[from.x as usize * 4..][..to.w as usize * 4],
+                ),
+                None => {
+                    for i in 0..to.w {
+                        let color = src.pixel(Point::new(from.x + i, from.y + j));
+                        self.set_pixel(Point::new(to.x + i, to.y + j), color);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Draw a rectangle of another image over this one, with its top left
+    /// corner at `dest`, blending the colors (see [`Color::blend_over`]). The
+    /// alpha of the other image is multiplied by `opacity` first, as with the
+    /// opacity of a layer. The parts out of bounds of either image are ignored.
+    fn blit(&mut self, src: &Self, rect: Rect<i32>, dest: Point<i32>, opacity: u8) {
+        let (from, to) = copy_area(src.size(), rect, self.size(), dest);
+
+        for j in 0..to.h {
+            match self.row_mut(to.y + j) {
+                Some(row) => {
+                    let src_row = &src.row(from.y + j)[from.x as usize * 4..][..to.w as usize * 4];
+
+                    for (dst, src) in row_span(row, to.x, to.w)
+                        .chunks_exact_mut(4)
+                        .zip(src_row.chunks_exact(4))
+                    {
+                        let fg = Color::new(src[0], src[1], src[2], src[3]);
+                        let bg = Color::new(dst[0], dst[1], dst[2], dst[3]);
+                        let color = fg.with_multiplied_alpha(opacity).blend_over(bg);
+                        dst.copy_from_slice(&[color.r, color.g, color.b, color.a]);
+                    }
+                }
+                None => {
+                    for i in 0..to.w {
+                        let fg = src.pixel(Point::new(from.x + i, from.y + j));
+                        let p = Point::new(to.x + i, to.y + j);
+                        let color = fg.with_multiplied_alpha(opacity).blend_over(self.pixel(p));
+                        self.set_pixel(p, color);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Replace the color of every pixel by the result of a function of it
+    fn map_pixels<F: FnMut(Color) -> Color>(&mut self, mut f: F) {
+        for y in 0..self.height() {
+            match self.row_mut(y) {
+                Some(row) => {
+                    for pixel in row.chunks_exact_mut(4) {
+                        let color = f(Color::new(pixel[0], pixel[1], pixel[2], pixel[3]));
+                        pixel.copy_from_slice(&[color.r, color.g, color.b, color.a]);
+                    }
+                }
+                None => {
+                    for x in 0..self.width() {
+                        let p = Point::new(x, y);
+                        let color = f(self.pixel(p));
+                        self.set_pixel(p, color);
+                    }
+                }
+            }
+        }
+    }
+
     // TODO: use this and next method to save/load files
     /// Get the the PNG representation of this image as a sequence of bytes
     fn png_bytes(&self) -> Result<Vec<u8>> {
@@ -70,6 +182,30 @@ pub trait Bitmap: Clone {
     }
 }
 
+/// The bytes of `w` pixels of a row, starting at column `x`
+fn row_span(row: &mut [u8], x: i32, w: i32) -> &mut [u8] {
+    &mut row[x as usize * 4..][..w as usize * 4]
+}
+
+/// Where a rectangle of an image of size `src` lands in an image of size `dst`
+/// when its top left corner is placed at `dest`. Returns the top left corner of
+/// the part of the source that lands inside both images, and the rectangle it
+/// covers in the destination.
+fn copy_area(
+    src: Size<i32>,
+    rect: Rect<i32>,
+    dst: Size<i32>,
+    dest: Point<i32>,
+) -> (Point<i32>, Rect<i32>) {
+    let clipped = rect.clip_to(Rect::new(0, 0, src.x, src.y));
+    let shifted = dest + (clipped.pos() - rect.pos());
+    let to = Rect::new(shifted.x, shifted.y, clipped.w, clipped.h)
+        .clip_to(Rect::new(0, 0, dst.x, dst.y));
+    let from = clipped.pos() + (to.pos() - shifted);
+
+    (from, to)
+}
+
 pub use test::TestImage;
 
 mod test {
@@ -79,7 +215,10 @@ mod test {
     // TODO implementing this showed that our trait is not that generic. We were
     // forced to add this `bytes` field which defeats the purpose of having a
     // vec of vec of color as storage. We should improve this.
-    /// Terribly slow but easy to use image type just for tests' sake
+    /// Terribly slow but easy to use image type just for tests' sake. Use
+    /// [`RgbaBitmap`] for anything else.
+    ///
+    /// [`RgbaBitmap`]: crate::RgbaBitmap
     #[derive(Clone, Serialize, Deserialize)]
     pub struct TestImage {
         size: Size<i32>,
@@ -133,8 +272,20 @@ mod test {
         fn bytes(&self) -> &[u8] {
             &self.bytes
         }
-        fn from_parts(_size: Size<i32>, _bytes: &[u8]) -> Self {
-            todo!()
+        fn from_parts(size: Size<i32>, bytes: &[u8]) -> Self {
+            let pixels = bytes
+                .chunks_exact(4)
+                .map(|c| Color::new(c[0], c[1], c[2], c[3]))
+                .collect::<Vec<_>>()
+                .chunks(size.x as usize)
+                .map(|row| row.to_vec())
+                .collect();
+
+            Self {
+                size,
+                pixels,
+                bytes: bytes.to_vec(),
+            }
         }
         fn set_from(&mut self, other: &Self) {
             self.pixels = other.pixels.clone();
@@ -159,6 +310,8 @@ mod test {
                     }
                 }
             }
+
+            self.update_bytes();
         }
     }
 }
@@ -0,0 +1,306 @@
+//! The footprint of the drawing tools
+
+use crate::graphics::is_corner;
+use crate::{Bitmap, Canvas, Color, Point};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Shape of the footprint of the [`Brush`]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BrushShape {
+    #[default]
+    Square,
+    Circle,
+    /// A custom footprint, made of the pixels at these offsets from the point
+    /// being drawn. Its size does not depend on the size of the brush.
+    Stamp(Vec<Point<i32>>),
+}
+
+impl BrushShape {
+    /// Make a stamp out of the pixels of an image that are not fully
+    /// transparent, centered on the middle of the image
+    pub fn stamp_from_image<IMG: Bitmap>(img: &IMG) -> Self {
+        let center = Point::new(img.width() / 2, img.height() / 2);
+        let mut offsets = Vec::new();
+
+        for y in 0..img.height() {
+            for x in 0..img.width() {
+                let p = Point::new(x, y);
+                if img.pixel(p).a > 0 {
+                    offsets.push(p - center);
+                }
+            }
+        }
+
+        Self::Stamp(offsets)
+    }
+}
+
+/// Settings of the footprint of the brush, which are shared by the eraser and
+/// the tools that draw shapes (e.g. lines)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Brush {
+    /// Width and height of the footprint, in pixels
+    pub size: u8,
+    pub shape: BrushShape,
+    /// Remove the doubled corners ("L" shapes) of freehand strokes as they are
+    /// drawn, so that they are one pixel thick. Only applies to the brush and
+    /// the eraser.
+    pub pixel_perfect: bool,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Self {
+            size: 1,
+            shape: BrushShape::Square,
+            pixel_perfect: false,
+        }
+    }
+}
+
+impl Brush {
+    /// The offsets of the pixels painted around each point drawn. When the
+    /// size is even, the point is at the top left of the middle of the
+    /// footprint.
+    pub fn footprint(&self) -> Vec<Point<i32>> {
+        let n = self.size.max(1) as i32;
+        let low = -(n - 1) / 2;
+        let square = (low..low + n).flat_map(|y| (low..low + n).map(move |x| Point::new(x, y)));
+
+        match &self.shape {
+            BrushShape::Stamp(offsets) => offsets.clone(),
+            BrushShape::Square => square.collect(),
+            BrushShape::Circle if n <= 2 => square.collect(),
+            BrushShape::Circle => {
+                // Pixels whose center is inside the circle, minus the ones
+                // that barely touch it, so that small circles look round
+                let center = low as f32 + (n - 1) as f32 / 2.;
+                let radius_sq = (n as f32 / 2.).powi(2) - 0.5;
+                let dist_sq = |v: i32| (v as f32 - center).powi(2);
+
+                square
+                    .filter(|p| dist_sq(p.x) + dist_sq(p.y) <= radius_sq)
+                    .collect()
+            }
+        }
+    }
+
+    /// All the pixels painted by drawing the footprint at each point of a path
+    /// (e.g. a line), without repetition
+    pub fn stamp(&self, path: &[Point<i32>]) -> Vec<Point<i32>> {
+        let footprint = self.footprint();
+
+        let mut points: Vec<_> = path
+            .iter()
+            .flat_map(|p| footprint.iter().map(|offset| *p + *offset))
+            .collect();
+        points.sort_by_key(|p| (p.y, p.x));
+        points.dedup();
+
+        points
+    }
+}
+
+/// A freehand stroke in progress, drawn in pixel-perfect mode (see
+/// [`Brush::pixel_perfect`])
+#[derive(Debug, Default)]
+pub(crate) struct Stroke {
+    /// The last points of the path of the stroke
+    path: Vec<Point<i32>>,
+    /// The color each pixel painted by the stroke had before it, and how many
+    /// points of the path paint it
+    painted: HashMap<Point<i32>, (Color, u32)>,
+}
+
+impl Stroke {
+    /// Draw the stroke along a path (e.g. a line to the latest mouse
+    /// position). A point that becomes the corner of an "L" is taken out of
+    /// the stroke, and the pixels only it painted get their original color
+    /// back. If `wrap` is set (tile mode), the pixels past an edge of the
+    /// canvas are painted on the opposite edge. Returns a set of reversals.
+    pub fn extend<IMG: Bitmap>(
+        &mut self,
+        canvas: &mut Canvas<IMG>,
+        path: &[Point<i32>],
+        brush: &Brush,
+        color: Color,
+        wrap: bool,
+    ) -> Vec<(Point<i32>, Color)> {
+        let footprint = brush.footprint();
+        let pixels = |canvas: &Canvas<IMG>, p: Point<i32>| -> Vec<Point<i32>> {
+            footprint
+                .iter()
+                .map(|offset| p + *offset)
+                .map(|q| if wrap { canvas.wrap(q) } else { q })
+                .collect()
+        };
+        let mut reversals = Vec::new();
+
+        for p in path {
+            if self.path.last() == Some(p) {
+                continue;
+            }
+            self.path.push(*p);
+
+            for q in pixels(canvas, *p) {
+                if !canvas.is_in_bounds(q) {
+                    continue;
+                }
+                let (_, count) = self.painted.entry(q).or_insert((canvas.pixel(q), 0));
+                *count += 1;
+                reversals.extend(canvas.set_pixel(q, color));
+            }
+
+            if let [a, b, c] = self.path[..] {
+                if is_corner(a, b, c) {
+                    self.path.remove(1);
+                    for q in pixels(canvas, b) {
+                        reversals.extend(self.unpaint(canvas, q));
+                    }
+                } else {
+                    self.path.remove(0);
+                }
+            }
+        }
+
+        reversals
+    }
+
+    /// Stop painting a pixel from one point of the path, restoring its original
+    /// color if no other point paints it
+    fn unpaint<IMG: Bitmap>(
+        &mut self,
+        canvas: &mut Canvas<IMG>,
+        q: Point<i32>,
+    ) -> Option<(Point<i32>, Color)> {
+        let (original, count) = self.painted.get_mut(&q)?;
+        *count -= 1;
+
+        if *count == 0 {
+            let original = *original;
+            self.painted.remove(&q);
+            return canvas.set_pixel(q, original);
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{BLACK, TRANSPARENT};
+    use crate::{Size, TestImage};
+    use test_case::test_case;
+
+    const RED: Color = Color::new(255, 0, 0, 255);
+
+    fn brush(size: u8, shape: BrushShape) -> Brush {
+        Brush {
+            size,
+            shape,
+            ..Default::default()
+        }
+    }
+
+    #[test_case(brush(0, BrushShape::Square), 1)]
+    #[test_case(brush(1, BrushShape::Circle), 1)]
+    #[test_case(brush(2, BrushShape::Square), 4)]
+    #[test_case(brush(2, BrushShape::Circle), 4)]
+    #[test_case(brush(3, BrushShape::Circle), 5)]
+    #[test_case(brush(4, BrushShape::Circle), 12)]
+    #[test_case(brush(5, BrushShape::Circle), 21)]
+    #[test_case(brush(5, BrushShape::Square), 25)]
+    fn footprint_size(brush: Brush, pixels: usize) {
+        assert_eq!(brush.footprint().len(), pixels);
+    }
+
+    #[test_case(1, (0, 0), (0, 0))]
+    #[test_case(2, (0, 0), (1, 1))]
+    #[test_case(3, (-1, -1), (1, 1))]
+    #[test_case(4, (-1, -1), (2, 2))]
+    fn footprint_extent(size: u8, min: (i32, i32), max: (i32, i32)) {
+        let footprint = brush(size, BrushShape::Square).footprint();
+
+        assert_eq!(
+            footprint.iter().min_by_key(|p| (p.x, p.y)),
+            Some(&min.into())
+        );
+        assert_eq!(
+            footprint.iter().max_by_key(|p| (p.x, p.y)),
+            Some(&max.into())
+        );
+    }
+
+    #[test]
+    fn stamp_along_path() {
+        let brush = brush(2, BrushShape::Square);
+        let path = [Point::new(0, 0), Point::new(1, 0), Point::new(2, 0)];
+
+        assert_eq!(brush.stamp(&path).len(), 8);
+    }
+
+    #[test]
+    fn stamp_from_image() {
+        let mut img = TestImage::new(Size::new(3, 3), TRANSPARENT);
+        img.set_pixel(Point::new(1, 1), BLACK);
+        img.set_pixel(Point::new(2, 0), BLACK);
+        let shape = BrushShape::stamp_from_image(&img);
+
+        assert_eq!(
+            shape,
+            BrushShape::Stamp(vec![Point::new(1, -1), Point::new(0, 0)])
+        );
+        assert_eq!(brush(9, shape).footprint().len(), 2);
+    }
+
+    #[test_case((0, 0), (1, 0), (1, 1), true)]
+    #[test_case((0, 0), (0, 1), (1, 1), true)]
+    #[test_case((0, 0), (1, 0), (2, 0), false)]
+    #[test_case((0, 0), (1, 1), (2, 1), false)]
+    #[test_case((0, 0), (1, 0), (2, 1), false)]
+    fn corner(a: (i32, i32), b: (i32, i32), c: (i32, i32), res: bool) {
+        assert_eq!(is_corner(a.into(), b.into(), c.into()), res);
+    }
+
+    #[test]
+    fn pixel_perfect_stroke() {
+        let mut canvas = Canvas::<TestImage>::new(Size::new(4, 4));
+        canvas.set_pixel(Point::new(1, 0), RED);
+        let mut stroke = Stroke::default();
+        let path = [Point::new(0, 0), Point::new(1, 0), Point::new(1, 1)];
+        stroke.extend(&mut canvas, &path, &Brush::default(), BLACK, false);
+
+        assert_eq!(canvas.pixel(Point::new(0, 0)), BLACK);
+        assert_eq!(canvas.pixel(Point::new(1, 0)), RED);
+        assert_eq!(canvas.pixel(Point::new(1, 1)), BLACK);
+
+        // The next segment continues from the end of the previous one
+        let path = [Point::new(1, 1), Point::new(2, 1), Point::new(2, 2)];
+        stroke.extend(&mut canvas, &path, &Brush::default(), BLACK, false);
+
+        assert_eq!(canvas.pixel(Point::new(2, 1)), TRANSPARENT);
+        assert_eq!(canvas.pixel(Point::new(2, 2)), BLACK);
+    }
+
+    #[test]
+    fn pixel_perfect_keeps_overlapping_pixels() {
+        let mut canvas = Canvas::<TestImage>::new(Size::new(4, 4));
+        let mut stroke = Stroke::default();
+        let path = [Point::new(0, 0), Point::new(1, 0), Point::new(1, 1)];
+        stroke.extend(
+            &mut canvas,
+            &path,
+            &brush(2, BrushShape::Square),
+            BLACK,
+            false,
+        );
+
+        // Only the pixel painted by the corner alone is restored
+        assert_eq!(canvas.pixel(Point::new(2, 0)), TRANSPARENT);
+        for p in [(0, 0), (1, 0), (0, 1), (1, 1), (2, 1), (2, 2)] {
+            assert_eq!(canvas.pixel(p.into()), BLACK);
+        }
+    }
+}
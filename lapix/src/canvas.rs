@@ -1,20 +1,44 @@
 use crate::color::TRANSPARENT;
-use crate::{graphics, Bitmap, Color, FreeImage, Point, Rect, Size};
+use crate::shape::Shape;
+use crate::{
+    fill, graphics, Bitmap, Brush, BucketOptions, Color, FreeImage, Mask, PathShape, Point, Rect,
+    ShapeOptions, Size,
+};
 use serde::{Deserialize, Serialize};
 
 /// Effects that certain actions can have on the canvas
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum CanvasEffect {
     /// Action does not affect the canvas at all
+    #[default]
     None,
-    /// Action forces the canvas to be updated
-    Update,
+    /// Action changed the pixels of an area of the layer with the specified
+    /// index, which must be updated
+    Update(usize, Rect<i32>),
     /// Action forces the canvas to be recreated
     New,
     /// Action forces the layers to be updated
     Layer,
 }
 
+impl CanvasEffect {
+    /// Combine two effects into one that covers both, e.g. to update the screen
+    /// once for all the actions of a frame. Updates of the same layer are
+    /// joined into the rectangle containing both areas, and updates of
+    /// different layers become a [`Layer`] update.
+    ///
+    /// [`Layer`]: CanvasEffect::Layer
+    pub fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::New, _) | (_, Self::New) => Self::New,
+            (Self::Layer, _) | (_, Self::Layer) => Self::Layer,
+            (Self::None, fx) | (fx, Self::None) => fx,
+            (Self::Update(i, a), Self::Update(j, b)) if i == j => Self::Update(i, a.union(b)),
+            (Self::Update(..), Self::Update(..)) => Self::Layer,
+        }
+    }
+}
+
 /// The canvas is the area where drawing can take place. Each layer has a
 /// canvas, and the canvas in turn holds an image internally to represent the
 /// drawing on it.
@@ -70,6 +94,13 @@ impl<IMG: Bitmap> Canvas<IMG> {
         p.x >= 0 && p.y >= 0 && p.x < self.width() && p.y < self.height()
     }
 
+    /// The pixel of the canvas that a point stands for in tile mode, in which
+    /// the canvas is repeated around itself (e.g. the point just past the
+    /// right edge is the first pixel of the row)
+    pub fn wrap(&self, p: Point<i32>) -> Point<i32> {
+        Point::new(p.x.rem_euclid(self.width()), p.y.rem_euclid(self.height()))
+    }
+
     /// Set the image of the canvas with a predefined one
     pub fn set_img(&mut self, img: IMG) {
         self.inner = img;
@@ -119,194 +150,262 @@ impl<IMG: Bitmap> Canvas<IMG> {
         None
     }
 
-    /// Draw a line between two points in the canvas with a certain color.
-    /// Returns a set of reversals (points and the colors they need to be set to
-    /// in order to reverse the action).
+    /// Draw a line between two points in the canvas with a certain color and
+    /// [`Brush`]. Returns a set of reversals (points and the colors they need
+    /// to be set to in order to reverse the action).
     pub fn line(
         &mut self,
         p1: Point<i32>,
         p2: Point<i32>,
+        brush: &Brush,
         color: Color,
     ) -> Vec<(Point<i32>, Color)> {
-        let line = graphics::line(p1, p2);
-        let mut reversals = Vec::new();
-
-        for p in line {
-            if let Some(action) = self.set_pixel(p, color) {
-                reversals.push(action);
-            }
-        }
-        reversals
+        self.set_pixels(brush.stamp(&graphics::line(p1, p2)), color)
     }
 
-    /// Draw a rectangle (outline) between two points in the canvas with a
-    /// certain color. Returns a set of reversals (points and the colors they
-    /// need to be set to in order to reverse the action).
+    /// Draw a rectangle with corners at two points in the canvas. Its outline
+    /// is drawn with a certain color and [`Brush`], and whether it is filled
+    /// is set by the [`ShapeOptions`]. Returns a set of reversals (points and
+    /// the colors they need to be set to in order to reverse the action).
     pub fn rectangle(
         &mut self,
         p1: Point<i32>,
         p2: Point<i32>,
+        brush: &Brush,
         color: Color,
+        options: &ShapeOptions,
     ) -> Vec<(Point<i32>, Color)> {
-        let rect = graphics::rectangle(p1, p2);
-        let mut reversals = Vec::new();
-
-        for p in rect {
-            if let Some(action) = self.set_pixel(p, color) {
-                reversals.push(action);
-            }
-        }
-        reversals
+        let pixels = Shape::Rectangle.pixels(p1, p2, brush, color, options);
+        pixels
+            .into_iter()
+            .filter_map(|(p, color)| self.set_pixel(p, color))
+            .collect()
     }
 
-    /// Draw an ellipse (outline) between two points in the canvas with a
-    /// certain color. Returns a set of reversals (points and the colors they
-    /// need to be set to in order to reverse the action).
+    /// Draw an ellipse inside the rectangle with corners at two points in the
+    /// canvas. Its outline is drawn with a certain color and [`Brush`], and
+    /// whether it is filled is set by the [`ShapeOptions`]. Returns a set of
+    /// reversals (points and the colors they need to be set to in order to
+    /// reverse the action).
     pub fn ellipse(
         &mut self,
         p1: Point<i32>,
         p2: Point<i32>,
+        brush: &Brush,
         color: Color,
+        options: &ShapeOptions,
     ) -> Vec<(Point<i32>, Color)> {
-        let rect = graphics::ellipse(p1, p2);
-        let mut reversals = Vec::new();
+        let pixels = Shape::Ellipse.pixels(p1, p2, brush, color, options);
+        pixels
+            .into_iter()
+            .filter_map(|(p, color)| self.set_pixel(p, color))
+            .collect()
+    }
 
-        for p in rect {
-            if let Some(action) = self.set_pixel(p, color) {
-                reversals.push(action);
-            }
-        }
-        reversals
+    /// Draw a polyline, polygon or Bezier curve through some points in the
+    /// canvas (see [`PathShape`]). Its outline is drawn with a certain color
+    /// and [`Brush`], and whether a polygon is filled is set by the
+    /// [`ShapeOptions`]. Returns a set of reversals (points and the colors they
+    /// need to be set to in order to reverse the action).
+    pub fn path(
+        &mut self,
+        shape: PathShape,
+        points: &[Point<i32>],
+        brush: &Brush,
+        color: Color,
+        options: &ShapeOptions,
+    ) -> Vec<(Point<i32>, Color)> {
+        let pixels = shape.pixels(points, brush, color, options);
+        pixels
+            .into_iter()
+            .filter_map(|(p, color)| self.set_pixel(p, color))
+            .collect()
+    }
+
+    /// Set some pixels to a color, returning the reversals of the ones that
+    /// changed
+    fn set_pixels(&mut self, points: Vec<Point<i32>>, color: Color) -> Vec<(Point<i32>, Color)> {
+        points
+            .into_iter()
+            .filter_map(|p| self.set_pixel(p, color))
+            .collect()
     }
 
     /// Set an area of the canvas (determined by a rectangle) to a certain
     /// color. Returns a set of reversals (points and colors they need to be set
     /// to in order to reverse the action).
     pub fn set_area(&mut self, area: Rect<i32>, color: Color) -> Vec<(Point<i32>, Color)> {
+        let area = area.clip_to(self.rect());
         let mut reversals = Vec::new();
 
-        for i in 0..area.w {
-            for j in 0..area.h {
-                if let Some(action) = self.set_pixel((i + area.x, j + area.y).into(), color) {
-                    reversals.push(action);
+        for y in area.y..area.y + area.h {
+            let row = self.inner.row(y);
+
+            for x in area.x..area.x + area.w {
+                let old = pixel_at(row, x);
+                if old != color {
+                    reversals.push((Point::new(x, y), old));
                 }
             }
         }
+        self.inner.fill_rect(area, color);
 
         reversals
     }
 
+    /// Set the pixels selected by a [`Mask`] to a certain color. Returns a set
+    /// of reversals (points and colors they need to be set to in order to
+    /// reverse the action).
+    pub fn set_mask(&mut self, mask: &Mask, color: Color) -> Vec<(Point<i32>, Color)> {
+        if mask.is_rect() {
+            return self.set_area(mask.bounds(), color);
+        }
+
+        mask.pixels()
+            .filter_map(|p| self.set_pixel(p, color))
+            .collect()
+    }
+
     /// Paste a free image into the canvas, overriding the contents that existed
     /// below that area. Returns a set of reversals (points and colors they need
     /// to be set to in order to reverse the action).
     pub fn paste_obj(&mut self, obj: &FreeImage<IMG>) -> Vec<(Point<i32>, Color)> {
+        let area = obj.rect.clip_to(self.rect());
         let mut reversals = Vec::new();
-        for i in 0..obj.rect.w {
-            for j in 0..obj.rect.h {
-                let ij = Point::new(i, j);
-                let color = obj.texture.pixel(ij);
-                let p = ij + obj.rect.pos();
-
-                if self.is_in_bounds(p) {
-                    let blended = color.blend_over(self.pixel(p));
-                    if let Some(action) = self.set_pixel(p, blended) {
-                        reversals.push(action);
-                    }
+
+        for y in area.y..area.y + area.h {
+            let row = self.inner.row(y);
+            let obj_row = obj.texture.row(y - obj.rect.y);
+
+            for x in area.x..area.x + area.w {
+                let old = pixel_at(row, x);
+                let blended = pixel_at(obj_row, x - obj.rect.x).blend_over(old);
+                if old != blended {
+                    reversals.push((Point::new(x, y), old));
                 }
             }
         }
 
+        let src = Rect::new(0, 0, obj.rect.w, obj.rect.h);
+        self.inner.blit(&obj.texture, src, obj.rect.pos(), 255);
+
         reversals
     }
 
-    /// Paint an enclosed area with a certain color. Returns a set of reversals
-    /// (points and colors they need to be set to in order to reverse the
-    /// action).
-    pub fn bucket(&mut self, p: Point<i32>, color: Color) -> Vec<(Point<i32>, Color)> {
-        let old_color = self.inner.pixel(p);
-
-        if color == old_color {
-            return Vec::new();
+    /// Paint an enclosed area with a certain color, or every pixel of the
+    /// color of the clicked one, depending on the [`BucketOptions`]. The colors
+    /// are compared in `sample` if given (e.g. the visible image), or in this
+    /// canvas otherwise. If `wrap` is set (tile mode), the area continues
+    /// across the edges of the canvas. Returns a set of reversals (points and
+    /// colors they need to be set to in order to reverse the action).
+    ///
+    /// Several points can be clicked at once (e.g. the copies of a symmetric
+    /// fill): every area is found before any of them is painted, and a point
+    /// that is in the area of a previous one is skipped.
+    pub fn bucket(
+        &mut self,
+        points: &[Point<i32>],
+        color: Color,
+        options: BucketOptions,
+        sample: Option<&IMG>,
+        wrap: bool,
+    ) -> Vec<(Point<i32>, Color)> {
+        let mut spans: Vec<fill::Span> = Vec::new();
+        for p in points {
+            let filled = spans
+                .iter()
+                .any(|s| s.y == p.y && p.x >= s.x && p.x < s.x + s.len);
+            if !filled {
+                spans.extend(self.fill_spans(*p, options, sample, wrap));
+            }
         }
 
-        let w = self.inner.width() as usize;
-        let h = self.inner.height() as usize;
-
-        let mut marked = vec![false; w * h];
-        let mut visit = vec![(p.x, p.y)];
-
         let mut reversals = Vec::new();
-
-        loop {
-            if visit.is_empty() {
-                break;
-            }
-
-            let mut new_visit = Vec::new();
-            while let Some((vx, vy)) = visit.pop() {
-                marked[(vy as usize) * w + vx as usize] = true;
-
-                if let Some(action) = self.set_pixel((vx, vy).into(), color) {
-                    reversals.push(action);
-                }
-
-                for (nx, ny) in self.neighbors(vx, vy).into_iter().flatten() {
-                    let ind = (ny as usize) * w + nx as usize;
-                    if self.inner.pixel((nx, ny).into()) == old_color && !marked[ind] {
-                        new_visit.push((nx, ny));
-                        marked[ind] = true;
-                    }
+        for span in spans {
+            let row = self.inner.row(span.y);
+            for x in span.x..span.x + span.len {
+                let old = pixel_at(row, x);
+                if old != color {
+                    reversals.push((Point::new(x, span.y), old));
                 }
             }
-
-            visit.append(&mut new_visit);
+            self.inner
+                .fill_rect(Rect::new(span.x, span.y, span.len, 1), color);
         }
 
         reversals
     }
 
-    fn neighbors(&self, x: i32, y: i32) -> [Option<(i32, i32)>; 4] {
-        let mut neighbors = [None; 4];
-        let w = self.inner.width();
-        let h = self.inner.height();
+    /// The pixels that [`bucket`] would paint, as a [`Mask`]. This is the
+    /// area selected by the magic wand.
+    ///
+    /// [`bucket`]: Canvas::bucket
+    pub fn fill_area(
+        &self,
+        p: Point<i32>,
+        options: BucketOptions,
+        sample: Option<&IMG>,
+        wrap: bool,
+    ) -> Mask {
+        let spans = self.fill_spans(p, options, sample, wrap);
+
+        Mask::from_pixels(
+            spans
+                .into_iter()
+                .flat_map(|s| (s.x..s.x + s.len).map(move |x| Point::new(x, s.y))),
+        )
+    }
 
-        if x + 1 < w {
-            neighbors[0] = Some((x + 1, y));
-        }
-        if x > 0 {
-            neighbors[1] = Some((x - 1, y));
-        }
-        if y + 1 < h {
-            neighbors[2] = Some((x, y + 1));
-        }
-        if y > 0 {
-            neighbors[3] = Some((x, y - 1));
+    /// The spans of pixels filled by clicking on a point (see [`fill::spans`])
+    fn fill_spans(
+        &self,
+        p: Point<i32>,
+        options: BucketOptions,
+        sample: Option<&IMG>,
+        wrap: bool,
+    ) -> Vec<fill::Span> {
+        let sample = sample.unwrap_or(&self.inner);
+        let color_at = |p: Point<i32>| pixel_at(sample.row(p.y), p.x);
+
+        fill::spans(self.size(), p, options, wrap, color_at)
+    }
+
+    /// Get an image from the pixels of the canvas selected by a [`Mask`], in
+    /// its bounding box. The pixels that are not selected are transparent.
+    pub fn img_from_mask(&self, mask: &Mask) -> IMG {
+        let rect = mask.bounds();
+        let mut img = self.img_from_area(rect);
+
+        if !mask.is_rect() {
+            for y in 0..rect.h {
+                for x in 0..rect.w {
+                    if !mask.contains(Point::new(rect.x + x, rect.y + y)) {
+                        img.set_pixel(Point::new(x, y), TRANSPARENT);
+                    }
+                }
+            }
         }
 
-        neighbors
+        img
     }
 
     /// Get an image from a certain area of the canvas (determined by a
     /// rectangle).
     pub fn img_from_area(&self, area: Rect<i32>) -> IMG {
         let mut img = IMG::new((area.w, area.h).into(), TRANSPARENT);
-
-        for i in 0..area.w {
-            for j in 0..area.h {
-                let ij = Point::new(i, j);
-                let p = area.pos() + ij;
-
-                if p.x < self.width() && p.y < self.height() {
-                    let color = self.pixel(p);
-                    img.set_pixel(ij, color);
-                }
-            }
-        }
+        img.copy_rect(&self.inner, area, Point::ZERO);
 
         img
     }
 }
 
+/// The color of a pixel in a row of RGBA bytes
+fn pixel_at(row: &[u8], x: i32) -> Color {
+    let i = x as usize * 4;
+
+    Color::new(row[i], row[i + 1], row[i + 2], row[i + 3])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -314,6 +413,19 @@ mod tests {
     use crate::color::TRANSPARENT;
     use test_case::test_case;
 
+    const A: CanvasEffect = CanvasEffect::Update(0, Rect::new(0, 0, 2, 2));
+    const B: CanvasEffect = CanvasEffect::Update(0, Rect::new(3, 1, 1, 1));
+
+    #[test_case(CanvasEffect::None, A, A)]
+    #[test_case(A, B, CanvasEffect::Update(0, Rect::new(0, 0, 4, 2)))]
+    #[test_case(A, CanvasEffect::Update(1, Rect::new(0, 0, 2, 2)), CanvasEffect::Layer)]
+    #[test_case(CanvasEffect::Layer, A, CanvasEffect::Layer)]
+    #[test_case(CanvasEffect::Layer, CanvasEffect::New, CanvasEffect::New)]
+    fn merge_effects(a: CanvasEffect, b: CanvasEffect, merged: CanvasEffect) {
+        assert_eq!(a.merge(b), merged);
+        assert_eq!(b.merge(a), merged);
+    }
+
     #[test]
     fn basic_properties() {
         let canvas = Canvas::<TestImage>::new(Size::new(5, 10));
@@ -398,7 +510,7 @@ mod tests {
     fn line<P: Into<Point<i32>>>(p: P, q: P, line: Vec<(i32, i32)>) {
         let mut canvas = Canvas::<TestImage>::new(Size::new(5, 5));
         let black = Color::new(0, 0, 0, 255);
-        canvas.line(p.into(), q.into(), black);
+        canvas.line(p.into(), q.into(), &Brush::default(), black);
         assert_points(&canvas, &line);
     }
 
@@ -406,7 +518,13 @@ mod tests {
     fn rect() {
         let mut canvas = Canvas::<TestImage>::new(Size::new(5, 5));
         let black = Color::new(0, 0, 0, 255);
-        canvas.rectangle((0, 0).into(), (2, 2).into(), black);
+        canvas.rectangle(
+            (0, 0).into(),
+            (2, 2).into(),
+            &Brush::default(),
+            black,
+            &ShapeOptions::default(),
+        );
 
         assert_points(
             &canvas,
@@ -433,7 +551,13 @@ mod tests {
         canvas.set_pixel(Point::new(1, 2), black);
         canvas.set_pixel(Point::new(2, 2), black);
         canvas.set_pixel(Point::new(3, 1), black);
-        canvas.bucket(Point::new(1, 1), black);
+        canvas.bucket(
+            &[Point::new(1, 1)],
+            black,
+            BucketOptions::default(),
+            None,
+            false,
+        );
         assert_points(
             &canvas,
             &[
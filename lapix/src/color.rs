@@ -6,6 +6,8 @@ use serde::{Deserialize, Serialize};
 pub const TRANSPARENT: Color = Color::new(0, 0, 0, 0);
 /// The color black
 pub const BLACK: Color = Color::new(0, 0, 0, 255);
+/// The color white
+pub const WHITE: Color = Color::new(255, 255, 255, 255);
 
 /// Represents an RGBA color, with component values from 0-255
 #[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
@@ -182,6 +184,30 @@ impl Color {
         }
     }
 
+    /// How different two colors look, from 0 (the same) to 1 (e.g. black and
+    /// white). The RGB distance uses the "redmean" approximation of how the
+    /// eye weighs each channel, on the colors premultiplied by their alpha, so
+    /// that transparent colors look alike. A difference of alpha counts too.
+    pub fn perceptual_dist(&self, other: &Self) -> f32 {
+        let premultiplied = |c: &Self| {
+            let a = c.a as f32 / 255.;
+            (c.r as f32 * a, c.g as f32 * a, c.b as f32 * a)
+        };
+        let (r1, g1, b1) = premultiplied(self);
+        let (r2, g2, b2) = premultiplied(other);
+
+        let r_mean = (r1 + r2) / 2.;
+        let rgb = ((2. + r_mean / 256.) * (r1 - r2).powi(2)
+            + 4. * (g1 - g2).powi(2)
+            + (2. + (255. - r_mean) / 256.) * (b1 - b2).powi(2))
+        .sqrt();
+        // The distance between black and white
+        let max_rgb = 255. * (8. + 255. / 256_f32).sqrt();
+        let alpha = (self.a as f32 - other.a as f32).abs() / 255.;
+
+        (rgb / max_rgb).max(alpha).min(1.)
+    }
+
     /// Get the hexadecimal representation of this color (with uppercase
     /// letters and a leading `#` sign).
     pub fn hex(&self) -> String {
@@ -249,6 +275,19 @@ mod tests {
         assert_eq!(a.into().with_multiplied_alpha(alpha), res.into());
     }
 
+    #[test_case((0, 0, 0, 255), (255, 255, 255, 255), 1.)]
+    #[test_case((12, 34, 56, 78), (12, 34, 56, 78), 0.)]
+    #[test_case((255, 0, 0, 0), (0, 255, 0, 0), 0.)]
+    #[test_case((0, 0, 0, 255), (0, 0, 0, 0), 1.)]
+    #[test_case((0, 0, 0, 255), (40, 40, 40, 255), 0.157)]
+    #[test_case((0, 0, 0, 255), (0, 40, 0, 255), 0.105)]
+    #[test_case((0, 0, 0, 255), (0, 0, 40, 255), 0.091)]
+    fn perceptual_dist<C: Into<Color>>(a: C, b: C, dist: f32) {
+        let (a, b) = (a.into(), b.into());
+        assert!((a.perceptual_dist(&b) - dist).abs() < 0.001);
+        assert_eq!(a.perceptual_dist(&b), b.perceptual_dist(&a));
+    }
+
     #[test_case((0, 0, 0, 255), "#000000FF")]
     #[test_case((255, 255, 255, 255), "#FFFFFFFF")]
     #[test_case((127, 127, 127, 255), "#7F7F7FFF")]
@@ -20,4 +20,21 @@ pub enum Error {
     ReversalNotSet,
     #[error("Codec error: {0}")]
     CodecError(#[from] bincode::Error),
+    #[error("Not a project file")]
+    InvalidProjectFile,
+    #[error(
+        "Project file format version {0} is not supported (latest supported is {latest}), try a newer version of the program",
+        latest = crate::project::PROJECT_FORMAT_VERSION
+    )]
+    UnsupportedProjectVersion(u32),
+    #[error("Corrupt project file: {0}")]
+    CorruptProject(String),
+    #[error("There is no layer at index {0}")]
+    InvalidLayer(usize),
+    #[error("Layer at index {0} cannot be moved further")]
+    CannotMoveLayer(usize),
+    #[error("Cannot delete the only layer")]
+    CannotDeleteOnlyLayer,
+    #[error("Invalid spritesheet: {0}")]
+    InvalidSpritesheet(String),
 }
@@ -1,4 +1,7 @@
-pub use crate::{CanvasEffect, Color, Point, Position, Size, Tool, Transform};
+pub use crate::{
+    Brush, BucketOptions, CanvasEffect, Color, HistoryLimits, Point, Position, ShapeOptions, Size,
+    Symmetry, Tool, Transform,
+};
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 use std::path::PathBuf;
@@ -31,6 +34,9 @@ pub enum Event {
     SetTool(Tool),
     /// Set the main color used by most tools
     SetMainColor(Color),
+    /// Set the size and shape of the brush, which are also used by the eraser
+    /// and the tools that draw shapes
+    SetBrush(Brush),
     /// Add a color to the palette, if it is not already there
     AddToPalette(Color),
     /// Remove a color from the palette
@@ -53,6 +59,9 @@ pub enum Event {
     LoadPalette(PathBuf),
     /// Apply bucket to a point (fill with color)
     Bucket(Point<i32>),
+    /// Set the options of the bucket tool (tolerance, whether to fill all the
+    /// matching pixels of the layer, etc.)
+    SetBucketOptions(BucketOptions),
     /// Similar to `BrushStart`, but for eraser
     EraseStart,
     /// Similar to `BrushEnd`, but for eraser
@@ -65,6 +74,16 @@ pub enum Event {
     /// Draw a line between this point and the previous point specified with
     /// `LineStart`
     LineEnd(Point<i32>),
+    /// Set the options of the tools that draw shapes (whether they are filled,
+    /// the fill color, etc.)
+    SetShapeOptions(ShapeOptions),
+    /// Set how the brush, eraser, bucket, lines and shapes are mirrored or
+    /// rotated to draw copies of what they draw
+    SetSymmetry(Symmetry),
+    /// Turn tile mode on or off. In tile mode, what is drawn past an edge of
+    /// the canvas continues from the opposite edge, and the bucket fills areas
+    /// across the edges, so that the image can be repeated seamlessly.
+    SetTileMode(bool),
     /// Start drawing a rectangle at the specified point
     RectStart(Point<i32>),
     /// Draw a rectangle with corners at this point and the point specified at
@@ -75,6 +94,16 @@ pub enum Event {
     /// Draw an ellipse with corners at this point and the point specified at
     /// `EllipseStart`
     EllipseEnd(Point<i32>),
+    /// Add a point to the path of the polyline, polygon, Bezier or lasso
+    /// tools. The first two points of a Bezier curve are its ends, and they get
+    /// two control points between them, which can then be moved with
+    /// `MovePathPoint`
+    PathPoint(Point<i32>),
+    /// Move the point of the path with the specified index to another point
+    MovePathPoint(usize, Point<i32>),
+    /// Draw the shape of the path that was started with `PathPoint`, or with
+    /// the lasso tools, select the area inside it
+    PathEnd,
     /// Create a new layer above the current layer
     NewLayerAbove,
     /// Create a new layer below the current layer
@@ -97,11 +126,15 @@ pub enum Event {
     /// (default is (1, 1), that is, just one frame). This is useful for
     /// displaying animations
     SetSpritesheet(Size<u8>),
-    /// Start a rectangular selection at the specified point
+    /// Start a rectangular selection at the specified point (or an elliptical
+    /// one, with the ellipse selection tool)
     StartSelection(Point<i32>),
     /// Select a rectangle with corners at this point and the point specified by
-    /// `StartSelection`
+    /// `StartSelection`, or the ellipse inside it
     EndSelection(Point<i32>),
+    /// Select the area that the bucket would fill when clicking on the
+    /// specified point, with the same options (magic wand)
+    MagicWand(Point<i32>),
     /// Clear the selection
     ClearSelection,
     /// Delete the selected area or free image
@@ -125,6 +158,20 @@ pub enum Event {
     ApplyTransform(Transform),
     /// Undo the last undoable action
     Undo,
+    /// Redo the last undone action. Only possible if no new undoable action
+    /// happened since the undo
+    Redo,
+    /// Undo or redo actions until the [`History`] reaches the specified
+    /// position (the number of applied actions, 0 being the state before any
+    /// action)
+    ///
+    /// [`History`]: crate::History
+    GoToHistory(usize),
+    /// Change the limits on how many actions (or how much memory) the
+    /// [`History`] can keep
+    ///
+    /// [`History`]: crate::History
+    SetHistoryLimits(HistoryLimits),
 }
 
 impl Event {
@@ -133,25 +180,13 @@ impl Event {
         std::mem::discriminant(self) == std::mem::discriminant(other)
     }
 
-    // TODO: maybe this should return a vec of fx, not a single one
-    /// Returns the [`CanvasEffect`] caused by this event
+    /// Returns the [`CanvasEffect`] caused by this event on the layers as a
+    /// whole. The pixels it changes depend on the state, so they are added to
+    /// this by [`State::execute`].
+    ///
+    /// [`State::execute`]: crate::State::execute
     pub fn canvas_effect(&self) -> CanvasEffect {
         match self {
-            Self::ClearCanvas
-            | Self::DeleteSelection
-            | Self::BrushStart
-            | Self::BrushStroke(_)
-            | Self::LineEnd(_)
-            | Self::RectEnd(_)
-            | Self::EllipseEnd(_)
-            | Self::Bucket(_)
-            | Self::MoveStart(_)
-            | Self::MoveEnd(_)
-            | Self::Paste(_)
-            | Self::FlipHorizontal
-            | Self::FlipVertical
-            | Self::ApplyTransform(_)
-            | Self::Erase(_) => CanvasEffect::Update,
             Self::ResizeCanvas(_) | Self::OpenFile(_) => CanvasEffect::New,
             Self::NewLayerAbove
             | Self::NewLayerBelow
@@ -159,7 +194,6 @@ impl Event {
             | Self::MoveLayerDown(_)
             | Self::MoveLayerUp(_)
             | Self::LoadProject(_) => CanvasEffect::Layer,
-            x if x.triggers_anchoring() => CanvasEffect::Update,
             _ => CanvasEffect::None,
         }
     }
@@ -169,6 +203,8 @@ impl Event {
         matches!(
             self,
             Self::Undo
+                | Self::Redo
+                | Self::GoToHistory(_)
                 | Self::NewLayerAbove
                 | Self::NewLayerBelow
                 | Self::DeleteLayer(_)
@@ -213,6 +249,9 @@ impl Event {
                 | Self::RectEnd(_)
                 | Self::EllipseStart(_)
                 | Self::EllipseEnd(_)
+                | Self::PathPoint(_)
+                | Self::MovePathPoint(_, _)
+                | Self::PathEnd
                 | Self::NewLayerAbove
                 | Self::NewLayerBelow
                 | Self::FlipHorizontal
@@ -225,15 +264,70 @@ impl Event {
                 | Self::MoveEnd(_)
                 | Self::StartSelection(_)
                 | Self::EndSelection(_)
+                | Self::MagicWand(_)
                 | Self::Paste(_)
                 | Self::LoadPalette(_)
                 | Self::OpenFile(_)
                 | Self::MoveLayerDown(_)
                 | Self::MoveLayerUp(_)
                 | Self::ApplyTransform(_)
+                | Self::SetSpritesheet(_)
+        )
+    }
+
+    /// Whether this event is kept when recording a macro. Events that deal
+    /// with files or with the history as a whole are not.
+    pub fn recordable(&self) -> bool {
+        !matches!(
+            self,
+            Self::Save(_)
+                | Self::OpenFile(_)
+                | Self::SaveProject(_)
+                | Self::LoadProject(_)
+                | Self::LoadPalette(_)
+                | Self::GoToHistory(_)
+                | Self::SetHistoryLimits(_)
         )
     }
 
+    /// The point of the canvas where this event happens, if any
+    pub fn point(&self) -> Option<Point<i32>> {
+        self.clone().point_mut().copied()
+    }
+
+    /// This event moved by an offset, if it happens at a point of the canvas
+    pub fn translate(&self, offset: Point<i32>) -> Self {
+        let mut event = self.clone();
+        if let Some(p) = event.point_mut() {
+            *p = *p + offset;
+        }
+
+        event
+    }
+
+    fn point_mut(&mut self) -> Option<&mut Point<i32>> {
+        match self {
+            Self::BrushStroke(p)
+            | Self::Bucket(p)
+            | Self::Erase(p)
+            | Self::LineStart(p)
+            | Self::LineEnd(p)
+            | Self::RectStart(p)
+            | Self::RectEnd(p)
+            | Self::EllipseStart(p)
+            | Self::EllipseEnd(p)
+            | Self::PathPoint(p)
+            | Self::MovePathPoint(_, p)
+            | Self::StartSelection(p)
+            | Self::EndSelection(p)
+            | Self::MagicWand(p)
+            | Self::MoveStart(p)
+            | Self::MoveEnd(p)
+            | Self::Paste(p) => Some(p),
+            _ => None,
+        }
+    }
+
     /// Whether this event causes the selection to be cleared
     pub fn clears_selection(&self) -> bool {
         matches!(
@@ -246,11 +340,30 @@ impl Event {
                 | Self::SetTool(Tool::Rectangle)
                 | Self::SetTool(Tool::Ellipse)
                 | Self::SetTool(Tool::Line)
+                | Self::SetTool(Tool::Polyline)
+                | Self::SetTool(Tool::Polygon)
+                | Self::SetTool(Tool::Bezier)
                 | Self::MoveLayerDown(_)
                 | Self::MoveLayerUp(_)
         )
     }
 
+    /// Whether the path of the polyline, polygon or Bezier tool is kept when
+    /// this event happens while it is being drawn. Other events cancel it.
+    pub fn keeps_path(&self) -> bool {
+        matches!(
+            self,
+            Self::PathPoint(_)
+                | Self::MovePathPoint(_, _)
+                | Self::PathEnd
+                | Self::SetMainColor(_)
+                | Self::SetBrush(_)
+                | Self::SetShapeOptions(_)
+                | Self::SetSymmetry(_)
+                | Self::SetTileMode(_)
+        )
+    }
+
     /// Whether this event causes any [`FreeImage`] to be anchored to the canvas
     ///
     /// [`FreeImage`]: crate::FreeImage
@@ -264,12 +377,23 @@ impl Event {
                 | Self::LineEnd(_)
                 | Self::RectEnd(_)
                 | Self::EllipseEnd(_)
+                | Self::MovePathPoint(_, _)
+                | Self::PathEnd
                 | Self::FlipHorizontal
                 | Self::FlipVertical
                 | Self::DeleteSelection
                 | Self::MoveLayerDown(_)
                 | Self::MoveLayerUp(_)
                 | Self::ApplyTransform(_)
+                | Self::Undo
+                | Self::Redo
+                | Self::GoToHistory(_)
+                | Self::SetHistoryLimits(_)
+                | Self::SetBrush(_)
+                | Self::SetBucketOptions(_)
+                | Self::SetShapeOptions(_)
+                | Self::SetSymmetry(_)
+                | Self::SetTileMode(_)
         )
     }
 }
@@ -0,0 +1,238 @@
+//! Finding the area filled by the bucket tool
+
+use crate::{Color, Point, Size};
+use serde::{Deserialize, Serialize};
+
+/// Options of the bucket tool
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BucketOptions {
+    /// How different a color can be from the one of the clicked pixel and
+    /// still be filled, in percent of the [perceptual distance]. With 0 only
+    /// the exact same color is filled.
+    ///
+    /// [perceptual distance]: Color::perceptual_dist
+    pub tolerance: u8,
+    /// Fill every matching pixel of the layer, not only the ones connected to
+    /// the clicked one
+    pub global: bool,
+    /// Pixels touching only by a corner are connected too (8-way fill)
+    pub diagonal: bool,
+    /// Compare the colors of the visible image (all layers blended) instead of
+    /// the ones of the active layer. Only the active layer is painted.
+    pub sample_merged: bool,
+}
+
+/// A horizontal run of pixels to fill, from `x` to `x + len` (exclusive)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub x: i32,
+    pub y: i32,
+    pub len: i32,
+}
+
+/// Find the spans of pixels filled by clicking on `start` of an image, given
+/// the color of each of its pixels. The connected area is found one row at a
+/// time (scanline flood fill), so each pixel is only checked a few times. If
+/// `wrap` is set (tile mode), the pixels on opposite edges of the image are
+/// connected too, as if it were repeated around itself.
+pub fn spans<F>(
+    size: Size<i32>,
+    start: Point<i32>,
+    options: BucketOptions,
+    wrap: bool,
+    color_at: F,
+) -> Vec<Span>
+where
+    F: Fn(Point<i32>) -> Color,
+{
+    let target = color_at(start);
+    let tolerance = options.tolerance as f32 / 100.;
+    let matches = |x, y| target.perceptual_dist(&color_at(Point::new(x, y))) <= tolerance;
+
+    if options.global {
+        return (0..size.y)
+            .flat_map(|y| row_spans(y, 0, size.x, move |x| matches(x, y)))
+            .collect();
+    }
+
+    let index = |x: i32, y: i32| (y * size.x + x.rem_euclid(size.x)) as usize;
+    let mut filled = vec![false; (size.x * size.y) as usize];
+    let is_free =
+        |filled: &[bool], x: i32, y| !filled[index(x, y)] && matches(x.rem_euclid(size.x), y);
+    let mut seeds = vec![start];
+    let mut spans = Vec::new();
+
+    while let Some(Point { x, y }) = seeds.pop() {
+        if !is_free(&filled, x, y) {
+            continue;
+        }
+
+        // When wrapping, a span may cross the left or right edge, but it is
+        // never longer than a row
+        let mut x0 = x;
+        while (x0 > 0 || wrap) && x + 1 - x0 < size.x && is_free(&filled, x0 - 1, y) {
+            x0 -= 1;
+        }
+        let mut x1 = x + 1;
+        while (x1 < size.x || wrap) && x1 - x0 < size.x && is_free(&filled, x1, y) {
+            x1 += 1;
+        }
+
+        for x in x0..x1 {
+            filled[index(x, y)] = true;
+        }
+        spans.extend(split_at_edges(size.x, x0, x1, y));
+
+        // The rows above and below are connected to this span, or also to the
+        // pixels diagonal to its ends
+        let (x0, x1) = match (options.diagonal, wrap) {
+            (false, _) => (x0, x1),
+            (true, false) => ((x0 - 1).max(0), (x1 + 1).min(size.x)),
+            (true, true) if x1 - x0 + 2 > size.x => (0, size.x),
+            (true, true) => (x0 - 1, x1 + 1),
+        };
+        for ny in [y - 1, y + 1] {
+            let ny = match ny {
+                ny if (0..size.y).contains(&ny) => ny,
+                ny if wrap => ny.rem_euclid(size.y),
+                _ => continue,
+            };
+
+            let is_seed = |nx| is_free(&filled, nx, ny);
+            let seed = |s: Span| Point::new(s.x.rem_euclid(size.x), ny);
+            seeds.extend(row_spans(ny, x0, x1, is_seed).map(seed));
+        }
+    }
+
+    spans
+}
+
+/// The span from `x0` to `x1` (exclusive) of a row of an image of a certain
+/// width, which may cross one of its edges, as the spans inside the image
+fn split_at_edges(width: i32, x0: i32, x1: i32, y: i32) -> Vec<Span> {
+    let span = |x0: i32, x1: i32| Span {
+        x: x0,
+        y,
+        len: x1 - x0,
+    };
+
+    if x0 < 0 {
+        vec![span(x0 + width, width), span(0, x1)]
+    } else if x1 > width {
+        vec![span(x0, width), span(0, x1 - width)]
+    } else {
+        vec![span(x0, x1)]
+    }
+}
+
+/// The runs of pixels of a row, between `x0` and `x1` (exclusive), for which a
+/// condition holds
+fn row_spans<F>(y: i32, x0: i32, x1: i32, f: F) -> impl Iterator<Item = Span>
+where
+    F: Fn(i32) -> bool,
+{
+    let mut x = x0;
+
+    std::iter::from_fn(move || {
+        while x < x1 && !f(x) {
+            x += 1;
+        }
+        let start = x;
+        while x < x1 && f(x) {
+            x += 1;
+        }
+
+        (x > start).then_some(Span {
+            x: start,
+            y,
+            len: x - start,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{BLACK, TRANSPARENT};
+    use test_case::test_case;
+
+    const GRAY: Color = Color::new(40, 40, 40, 255);
+
+    /// Read an image from rows of characters: `#` is black, `+` is dark gray
+    /// and anything else is transparent
+    fn image<'a>(rows: &'a [&'a str]) -> (Size<i32>, impl Fn(Point<i32>) -> Color + 'a) {
+        let size = Size::new(rows[0].len() as i32, rows.len() as i32);
+        let color_at = move |p: Point<i32>| match rows[p.y as usize].as_bytes()[p.x as usize] {
+            b'#' => BLACK,
+            b'+' => GRAY,
+            _ => TRANSPARENT,
+        };
+
+        (size, color_at)
+    }
+
+    fn count(spans: &[Span]) -> i32 {
+        spans.iter().map(|s| s.len).sum()
+    }
+
+    const RING: [&str; 5] = [".....", ".###.", ".#.#.", ".###.", "....."];
+    const DIAGONAL: [&str; 4] = ["..#.", ".#.#", "#.#.", ".#.."];
+    const SHADES: [&str; 3] = ["..+#", "#+..", "...."];
+
+    #[test_case(&RING, (0, 0), BucketOptions::default(), 16)]
+    #[test_case(&RING, (2, 2), BucketOptions::default(), 1)]
+    #[test_case(&RING, (1, 1), BucketOptions::default(), 8)]
+    #[test_case(&RING, (2, 2), BucketOptions { global: true, ..Default::default() }, 17)]
+    #[test_case(&DIAGONAL, (0, 0), BucketOptions::default(), 3)]
+    #[test_case(&DIAGONAL, (0, 0), BucketOptions { diagonal: true, ..Default::default() }, 10)]
+    #[test_case(&DIAGONAL, (1, 1), BucketOptions { diagonal: true, ..Default::default() }, 6)]
+    #[test_case(&SHADES, (3, 0), BucketOptions::default(), 1)]
+    #[test_case(&SHADES, (3, 0), BucketOptions { tolerance: 20, ..Default::default() }, 2)]
+    #[test_case(&SHADES, (3, 0), BucketOptions { tolerance: 20, global: true, ..Default::default() }, 4)]
+    #[test_case(&SHADES, (0, 0), BucketOptions { tolerance: 100, ..Default::default() }, 12)]
+    fn filled_pixels(rows: &[&str], start: (i32, i32), options: BucketOptions, filled: i32) {
+        let (size, color_at) = image(rows);
+        let spans = spans(size, start.into(), options, false, color_at);
+
+        assert_eq!(count(&spans), filled);
+    }
+
+    #[test]
+    fn spans_do_not_overlap() {
+        let (size, color_at) = image(&["......", ".#..#.", "..##..", "......"]);
+        let options = BucketOptions {
+            diagonal: true,
+            ..Default::default()
+        };
+
+        let mut pixels: Vec<_> = spans(size, Point::new(0, 0), options, false, color_at)
+            .iter()
+            .flat_map(|s| (s.x..s.x + s.len).map(move |x| (x, s.y)))
+            .collect();
+        let total = pixels.len();
+        pixels.sort();
+        pixels.dedup();
+
+        assert_eq!(pixels.len(), total);
+        assert_eq!(total, 20);
+    }
+
+    #[test_case(&["..#..", "..#..", "..#.."], false, 6)]
+    #[test_case(&["..#..", "..#..", "..#.."], true, 12)]
+    #[test_case(&["....", "####", "...."], false, 4)]
+    #[test_case(&["....", "####", "...."], true, 8)]
+    #[test_case(&[".#..", "####", "####"], true, 3)]
+    fn wrapped(rows: &[&str], wrap: bool, filled: i32) {
+        let (size, color_at) = image(rows);
+        let spans = spans(
+            size,
+            Point::new(0, 0),
+            BucketOptions::default(),
+            wrap,
+            color_at,
+        );
+
+        assert!(spans.iter().all(|s| s.x >= 0 && s.x + s.len <= size.x));
+        assert_eq!(count(&spans), filled);
+    }
+}
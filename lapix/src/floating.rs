@@ -1,16 +1,24 @@
 use crate::color::TRANSPARENT;
-use crate::{graphics, Bitmap, Canvas, Color, Point, Position, Rect, Size};
+use crate::shape::Shape;
+use crate::{
+    graphics, Bitmap, Brush, Canvas, Color, Mask, PathShape, Point, Position, Rect, ShapeOptions,
+    Size,
+};
 use serde::{Deserialize, Serialize};
 
 /// Represents an image that is not in any [`Canvas`], but floats freely on
 /// the screen until it is *anchored* back into the canvas. Typical uses of this
 /// are imported images and selections -- they can be moved around and
 /// manipulated before being integrated into the canvas (by anchoring).
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FreeImage<IMG> {
     pub rect: Rect<i32>,
     pub pivot: Option<Point<i32>>,
     pub texture: IMG,
+    /// The pixels of the image that are selected, starting at (0, 0). Only
+    /// these are copied from the canvas when it is created from a selection of
+    /// any shape, and they are selected again when it is anchored.
+    pub mask: Mask,
 }
 
 impl<IMG: Bitmap> FreeImage<IMG> {
@@ -19,6 +27,7 @@ impl<IMG: Bitmap> FreeImage<IMG> {
     pub fn new(p: Position<i32>, img: IMG) -> Self {
         Self {
             rect: Rect::new(p.x, p.y, img.width(), img.height()),
+            mask: Mask::from_rect(Rect::new(0, 0, img.width(), img.height())),
             texture: img,
             pivot: None,
         }
@@ -33,9 +42,19 @@ impl<IMG: Bitmap> FreeImage<IMG> {
         area: Rect<i32>,
         pivot: Option<Point<i32>>,
     ) -> Self {
+        Self::from_canvas_mask(canvas, &Mask::from_rect(area), pivot)
+    }
+
+    /// Creates a free image from the pixels of the canvas selected by a
+    /// [`Mask`], the other pixels of its bounding box being transparent. The
+    /// pivot is the same as in [`from_canvas_area`].
+    ///
+    /// [`from_canvas_area`]: FreeImage::from_canvas_area
+    pub fn from_canvas_mask(canvas: &Canvas<IMG>, mask: &Mask, pivot: Option<Point<i32>>) -> Self {
         Self {
-            rect: area,
-            texture: canvas.img_from_area(area),
+            rect: mask.bounds(),
+            texture: canvas.img_from_mask(mask),
+            mask: mask.moved_to(Point::ZERO),
             pivot,
         }
     }
@@ -61,30 +80,67 @@ impl<IMG: Bitmap> FreeImage<IMG> {
         Self::new(offset, img)
     }
 
-    /// Creates a free image with a line between two points in a certain color.
-    pub fn line_preview(p0: Point<i32>, p: Point<i32>, color: Color) -> Self {
-        let span = p.abs_diff(p0);
-        let offset = p.rect_min_corner(p0);
+    /// Creates a free image with a set of pixels, each with its color, just
+    /// big enough to hold them. All other pixels will be transparent.
+    pub(crate) fn from_points(pixels: Vec<(Point<i32>, Color)>) -> Self {
+        let min = Point::new(
+            pixels.iter().map(|(p, _)| p.x).min().unwrap_or(0),
+            pixels.iter().map(|(p, _)| p.y).min().unwrap_or(0),
+        );
+        let max = Point::new(
+            pixels.iter().map(|(p, _)| p.x).max().unwrap_or(0),
+            pixels.iter().map(|(p, _)| p.y).max().unwrap_or(0),
+        );
+
+        let mut img = IMG::new(max - min + Point::ONE, TRANSPARENT);
+        for (p, color) in pixels {
+            img.set_pixel(p - min, color);
+        }
 
-        FreeImage::from_pixels(span + Point::ONE, graphics::line(p0, p), color, offset)
+        Self::new(min, img)
     }
 
-    /// Creates a free image with a rectangle between two points in a certain
-    /// color.
-    pub fn rect_preview(p0: Point<i32>, p: Point<i32>, color: Color) -> Self {
-        let span = p.abs_diff(p0);
-        let offset = p.rect_min_corner(p0);
+    /// Creates a free image with a line between two points in a certain color,
+    /// drawn with a [`Brush`].
+    pub fn line_preview(p0: Point<i32>, p: Point<i32>, brush: &Brush, color: Color) -> Self {
+        let points = brush.stamp(&graphics::line(p0, p));
+        Self::from_points(points.into_iter().map(|p| (p, color)).collect())
+    }
 
-        FreeImage::from_pixels(span + Point::ONE, graphics::rectangle(p0, p), color, offset)
+    /// Creates a free image with a rectangle with corners at two points, as
+    /// drawn by [`Canvas::rectangle`].
+    pub fn rect_preview(
+        p0: Point<i32>,
+        p: Point<i32>,
+        brush: &Brush,
+        color: Color,
+        options: &ShapeOptions,
+    ) -> Self {
+        Self::from_points(Shape::Rectangle.pixels(p0, p, brush, color, options))
     }
 
-    /// Creates a free image with an ellipse between two points in a certain
-    /// color.
-    pub fn ellipse_preview(p0: Point<i32>, p: Point<i32>, color: Color) -> Self {
-        let span = p.abs_diff(p0);
-        let offset = p.rect_min_corner(p0);
+    /// Creates a free image with an ellipse inside the rectangle with corners
+    /// at two points, as drawn by [`Canvas::ellipse`].
+    pub fn ellipse_preview(
+        p0: Point<i32>,
+        p: Point<i32>,
+        brush: &Brush,
+        color: Color,
+        options: &ShapeOptions,
+    ) -> Self {
+        Self::from_points(Shape::Ellipse.pixels(p0, p, brush, color, options))
+    }
 
-        FreeImage::from_pixels(span + Point::ONE, graphics::ellipse(p0, p), color, offset)
+    /// Creates a free image with a polyline, polygon or Bezier curve through
+    /// some points, as drawn by [`Canvas::path`].
+    pub fn path_preview(
+        shape: PathShape,
+        points: &[Point<i32>],
+        brush: &Brush,
+        color: Color,
+        options: &ShapeOptions,
+    ) -> Self {
+        Self::from_points(shape.pixels(points, brush, color, options))
     }
 
     /// Change the position of the free image considering that the passed point
@@ -97,8 +153,9 @@ impl<IMG: Bitmap> FreeImage<IMG> {
         self.rect.y = dy;
     }
 
-    /// Flips the free image horizontally
+    /// Flips the free image (and its mask) horizontally
     pub fn flip_horizontally(&mut self) {
+        self.mask.flip_horizontally();
         for i in 0..(self.rect.w / 2) {
             for j in 0..self.rect.h {
                 let c1 = self.texture.pixel((i, j).into());
@@ -109,8 +166,9 @@ impl<IMG: Bitmap> FreeImage<IMG> {
         }
     }
 
-    /// Flips the free image vertically
+    /// Flips the free image (and its mask) vertically
     pub fn flip_vertically(&mut self) {
+        self.mask.flip_vertically();
         for j in 0..(self.rect.h / 2) {
             for i in 0..self.rect.w {
                 let c1 = self.texture.pixel((i, j).into());
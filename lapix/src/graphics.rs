@@ -1,39 +1,96 @@
 //! Functions to calculate graphics like lines, rectangles, etc. in a discrete
 //! 2D space
 
-use std::collections::HashSet;
-
-use crate::{Point, Rect};
+use crate::Point;
 
 /// Get the distance between two [`Point`]s
 pub fn distance(p1: Point<i32>, p2: Point<i32>) -> f32 {
     ((((p1.x - p2.x) as i64).pow(2) + ((p1.y - p2.y) as i64).pow(2)) as f64).sqrt() as f32
 }
 
-/// Get the set of [`Point`]s needed to draw a line between two points
+/// Get the set of [`Point`]s needed to draw a line between two points, in
+/// order from `p1` to `p2`. This is Bresenham's algorithm, so every step moves
+/// to one of the 8 neighbors of a pixel. The line between two points has the
+/// same pixels whichever end it starts from.
 pub fn line(p1: Point<i32>, p2: Point<i32>) -> Vec<Point<i32>> {
-    let mut line = Vec::new();
-    let diff = p2 - p1;
-    let dist = distance(p1, p2);
-    let dx = if dist < 0.1 { 0. } else { diff.x as f32 / dist };
-    let dy = if dist < 0.1 { 0. } else { diff.y as f32 / dist };
-
-    for i in 0..=dist.round() as usize {
-        let x = (p1.x as f32 + (i as f32 * dx)).round() as i32;
-        let y = (p1.y as f32 + (i as f32 * dy)).round() as i32;
-
-        if let Some(Point { x: x0, y: y0 }) = line.last() {
-            if x == *x0 && y == *y0 {
-                continue;
-            }
-        }
+    if (p2.y, p2.x) < (p1.y, p1.x) {
+        let mut line = line(p2, p1);
+        line.reverse();
+        return line;
+    }
 
-        line.push((x, y).into());
+    let (dx, dy) = ((p2.x - p1.x).abs(), -(p2.y - p1.y).abs());
+    let sx = if p1.x < p2.x { 1 } else { -1 };
+    let sy = if p1.y < p2.y { 1 } else { -1 };
+    let mut err = dx + dy;
+    let mut p = p1;
+    let mut line = vec![p];
+
+    while p != p2 {
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            p.x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            p.y += sy;
+        }
+        line.push(p);
     }
 
     line
 }
 
+/// The slopes of "clean" pixel art lines, i.e. the ones made of runs of the
+/// same length: horizontal, vertical, 1:1, 2:1, 1:2 and so on
+const CLEAN_SLOPES: [(i32, i32); 9] = [
+    (1, 0),
+    (0, 1),
+    (1, 1),
+    (2, 1),
+    (1, 2),
+    (3, 1),
+    (1, 3),
+    (4, 1),
+    (1, 4),
+];
+
+/// Move the end of a line from `p0` to `p` to the nearest point that makes it
+/// a clean pixel art line (see [`line`]). A line with a slope of 2:1, for
+/// example, is made of whole runs of 2 pixels.
+pub fn snap_to_slope(p0: Point<i32>, p: Point<i32>) -> Point<i32> {
+    let d = p.abs_diff(p0);
+    let sign = Point::new(
+        if p.x < p0.x { -1 } else { 1 },
+        if p.y < p0.y { -1 } else { 1 },
+    );
+
+    // The end after `k` runs along a slope: a sloped line ends on the last
+    // pixel of its last run
+    let end = |(a, b): (i32, i32), k: i32| {
+        if a == 0 || b == 0 {
+            Point::new(a * k, b * k)
+        } else {
+            Point::new(a * k - 1, b * k - 1)
+        }
+    };
+    let dist_sq = |q: Point<i32>| (q.x - d.x).pow(2) + (q.y - d.y).pow(2);
+
+    let snapped = CLEAN_SLOPES
+        .iter()
+        .flat_map(|&(a, b)| {
+            let k = ((d.x * a + d.y * b) as f32 / (a * a + b * b) as f32).round() as i32;
+            (k - 1..=k + 1)
+                .filter(|k| *k >= 1)
+                .map(move |k| end((a, b), k))
+        })
+        .min_by_key(|q| dist_sq(*q))
+        .unwrap_or(Point::ZERO);
+
+    p0 + Point::new(snapped.x * sign.x, snapped.y * sign.y)
+}
+
 /// Get the set of [`Point`]s needed to draw a rectangle between two points
 pub fn rectangle(p1: Point<i32>, p2: Point<i32>) -> Vec<Point<i32>> {
     let l1 = line((p1.x, p1.y).into(), (p1.x, p2.y).into());
@@ -44,55 +101,187 @@ pub fn rectangle(p1: Point<i32>, p2: Point<i32>) -> Vec<Point<i32>> {
     vec![l1, l2, l3, l4].into_iter().flatten().collect()
 }
 
-/// Get the set of [`Point`]s needed to draw an ellipse between two points
-/// TODO there are still some imperfections here
+/// Get the set of [`Point`]s needed to draw an ellipse that fits exactly the
+/// rectangle with corners at two points. This is the midpoint algorithm, in a
+/// version that also handles even sizes (whose center is between pixels), so
+/// the ellipse is always symmetric.
 pub fn ellipse(p1: Point<i32>, p2: Point<i32>) -> Vec<Point<i32>> {
-    let a = (p1.x - p2.x).abs() as f32 / 2.0;
-    let b = (p1.y - p2.y).abs() as f32 / 2.0;
-
-    let low_x = std::cmp::min(p1.x, p2.x);
-    let low_y = std::cmp::min(p1.y, p2.y);
-    let high_x = std::cmp::max(p1.x, p2.x);
-    let high_y = std::cmp::max(p1.y, p2.y);
-    let bounds = Rect::new(low_x, low_y, high_x - low_x, high_y - low_y);
-    let xspan = ((p1.x - p2.x).abs() as f32 / 2.0).round() as i32;
-    let yspan = ((p1.y - p2.y).abs() as f32 / 2.0).round() as i32;
-
-    let mut points = HashSet::new();
-
-    let sampling_level = yspan;
-    let step = 1. / sampling_level as f32;
-
-    // For each x, we'll find the corresponding y values
-    for x in 0..(xspan) {
-        for delta in 0..sampling_level {
-            let x = x as f32 + (delta as f32 * step);
-            // Formula:
-            // sqrt(((a2-x2)*b2)/a2)
-            let inner = (a.powf(2.0) - x.powf(2.0)) * b.powf(2.0) / a.powf(2.0);
-            let root = inner.sqrt();
-
-            let ys = vec![root, -root];
-
-            for y in ys {
-                let xx = x.round() as i32 + low_x + xspan;
-                let yy = y.round() as i32 + low_y + yspan;
-
-                if bounds.contains(xx, yy) {
-                    points.insert(Point::new(xx, yy));
-                }
-
-                let xx = -x.round() as i32 + low_x + xspan;
-                let yy = y.round() as i32 + low_y + yspan;
-
-                if bounds.contains(xx, yy) {
-                    points.insert(Point::new(xx, yy));
-                }
-            }
+    let a = (p2.x - p1.x).abs() as i64;
+    let b = (p2.y - p1.y).abs() as i64;
+    let b1 = b & 1;
+    let mut x0 = p1.x.min(p2.x) as i64;
+    let mut x1 = x0 + a;
+    let mut y0 = p1.y.min(p2.y) as i64 + (b + 1) / 2;
+    let mut y1 = y0 - b1;
+
+    // Error increments, and the error of the first step
+    let mut dx = 4 * (1 - a) * b * b;
+    let mut dy = 4 * (b1 + 1) * a * a;
+    let mut err = dx + dy + b1 * a * a;
+    let (a8, b8) = (8 * a * a, 8 * b * b);
+
+    let mut points = Vec::new();
+    let mut plot = |x: i64, y: i64| points.push(Point::new(x as i32, y as i32));
+
+    // Each quadrant at a time, from the middle of the sides to the top and
+    // bottom
+    while x0 <= x1 {
+        plot(x1, y0);
+        plot(x0, y0);
+        plot(x0, y1);
+        plot(x1, y1);
+
+        let e2 = 2 * err;
+        if e2 <= dy {
+            y0 += 1;
+            y1 -= 1;
+            dy += a8;
+            err += dy;
+        }
+        if e2 >= dx || 2 * err > dy {
+            x0 += 1;
+            x1 -= 1;
+            dx += b8;
+            err += dx;
+        }
+    }
+
+    // Very narrow ellipses stop too early, so their tips are finished here
+    while y0 - y1 <= b {
+        plot(x0 - 1, y0);
+        plot(x1 + 1, y0);
+        plot(x0 - 1, y1);
+        plot(x1 + 1, y1);
+        y0 += 1;
+        y1 -= 1;
+    }
+
+    points.sort_by_key(|p| (p.y, p.x));
+    points.dedup();
+
+    points
+}
+
+/// Get the set of [`Point`]s needed to draw connected lines through some
+/// points, in order
+pub fn polyline(points: &[Point<i32>]) -> Vec<Point<i32>> {
+    let mut path: Vec<_> = points.first().copied().into_iter().collect();
+    for segment in points.windows(2) {
+        path.extend(line(segment[0], segment[1]).into_iter().skip(1));
+    }
+
+    path
+}
+
+/// Get the set of [`Point`]s needed to draw the outline of a polygon with some
+/// vertices, in order. The last vertex is connected to the first one.
+pub fn polygon(points: &[Point<i32>]) -> Vec<Point<i32>> {
+    let mut closed = points.to_vec();
+    closed.extend(points.first());
+    let mut outline = polyline(&closed);
+    if points.len() > 1 {
+        outline.pop();
+    }
+
+    outline
+}
+
+/// Get the set of [`Point`]s inside a polygon with some vertices, including
+/// its outline. A pixel is inside if its center is, by the even-odd rule, so
+/// the parts where the polygon crosses itself are left out.
+pub fn polygon_area(points: &[Point<i32>]) -> Vec<Point<i32>> {
+    let mut area = polygon(points);
+    let (Some(min_y), Some(max_y)) = (
+        points.iter().map(|p| p.y).min(),
+        points.iter().map(|p| p.y).max(),
+    ) else {
+        return area;
+    };
+
+    let edges: Vec<_> = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .filter(|(a, b)| a.y != b.y)
+        .collect();
+
+    for y in min_y..=max_y {
+        let mut crossings: Vec<f64> = edges
+            .iter()
+            .filter(|(a, b)| (a.y <= y) != (b.y <= y))
+            .map(|(a, b)| a.x as f64 + (y - a.y) as f64 * (b.x - a.x) as f64 / (b.y - a.y) as f64)
+            .collect();
+        crossings.sort_by(f64::total_cmp);
+
+        for pair in crossings.chunks_exact(2) {
+            let (x0, x1) = (pair[0].ceil() as i32, pair[1].floor() as i32);
+            area.extend((x0..=x1).map(|x| Point::new(x, y)));
+        }
+    }
+
+    area.sort_by_key(|p| (p.y, p.x));
+    area.dedup();
+
+    area
+}
+
+/// Get the set of [`Point`]s needed to draw a Bezier curve with some control
+/// points (3 for a quadratic curve, 4 for a cubic one, and so on), in order.
+/// The curve is a clean pixel art line: every step moves to one of the 8
+/// neighbors of a pixel, and there are no "L" shaped corners.
+pub fn bezier(points: &[Point<i32>]) -> Vec<Point<i32>> {
+    let Some(first) = points.first() else {
+        return Vec::new();
+    };
+
+    // Sampling at least twice per pixel of the control polygon is enough for
+    // consecutive samples to be close, and the gaps are filled with lines
+    let length: f32 = points.windows(2).map(|s| distance(s[0], s[1])).sum();
+    let steps = (2. * length).ceil().max(1.) as usize;
+    let mut path = vec![*first];
+    for i in 1..=steps {
+        let p = de_casteljau(points, i as f32 / steps as f32);
+        let last = path[path.len() - 1];
+        if p != last {
+            path.extend(line(last, p).into_iter().skip(1));
+        }
+    }
+
+    let mut curve: Vec<Point<i32>> = Vec::with_capacity(path.len());
+    for p in path {
+        if curve.len() >= 2 && is_corner(curve[curve.len() - 2], curve[curve.len() - 1], p) {
+            curve.pop();
         }
+        curve.push(p);
+    }
+
+    curve
+}
+
+/// The point at `t` (from 0 to 1) of the Bezier curve with some control
+/// points, rounded to a pixel
+fn de_casteljau(points: &[Point<i32>], t: f32) -> Point<i32> {
+    let mut points: Vec<Point<f32>> = points.iter().map(|p| (*p).into()).collect();
+    while points.len() > 1 {
+        points = points
+            .windows(2)
+            .map(|s| {
+                Point::new(
+                    s[0].x + (s[1].x - s[0].x) * t,
+                    s[0].y + (s[1].y - s[0].y) * t,
+                )
+            })
+            .collect();
     }
 
-    points.into_iter().collect()
+    points[0].into()
+}
+
+/// Whether `b` is the corner of an "L" going from `a` to `c`, i.e. `a` and `c`
+/// touch diagonally and `b` touches both of them by a side
+pub(crate) fn is_corner(a: Point<i32>, b: Point<i32>, c: Point<i32>) -> bool {
+    let touches_side = |p: Point<i32>, q: Point<i32>| (p.x - q.x).abs() + (p.y - q.y).abs() == 1;
+
+    (a.x - c.x).abs() == 1 && (a.y - c.y).abs() == 1 && touches_side(a, b) && touches_side(b, c)
 }
 
 #[cfg(test)]
@@ -117,7 +306,7 @@ mod tests {
         let p1 = (0, 0);
         let p2 = (2, 1);
         let expect = vec![(0, 0), (2, 1)];
-        let either = vec![(1, 0), (1, 1)];
+        let either = [(1, 0), (1, 1)];
         let l = line(p1.into(), p2.into());
 
         for expected in expect {
@@ -126,4 +315,155 @@ mod tests {
 
         assert!(l.contains(&either[0].into()) || l.contains(&either[1].into()));
     }
+
+    #[test_case((0, 0), (3, -1))]
+    #[test_case((5, 2), (0, 0))]
+    #[test_case((-2, 4), (3, -3))]
+    fn line_is_same_both_ways(p1: (i32, i32), p2: (i32, i32)) {
+        let forward = line(p1.into(), p2.into());
+        let mut backward = line(p2.into(), p1.into());
+        backward.reverse();
+
+        assert_eq!(forward, backward);
+        assert_eq!(forward.first(), Some(&p1.into()));
+        assert_eq!(forward.last(), Some(&p2.into()));
+    }
+
+    #[test_case((0, 0), (7, 1), (7, 1))]
+    #[test_case((0, 0), (9, 1), (9, 0))]
+    #[test_case((0, 0), (5, 3), (5, 2))]
+    #[test_case((0, 0), (4, 5), (4, 4))]
+    #[test_case((0, 0), (-6, 2), (-5, 2))]
+    #[test_case((2, 2), (1, -7), (2, -7))]
+    #[test_case((0, 0), (0, 0), (0, 0))]
+    fn snap(p0: (i32, i32), p: (i32, i32), expected: (i32, i32)) {
+        assert_eq!(snap_to_slope(p0.into(), p.into()), expected.into());
+    }
+
+    /// Lengths of the runs of pixels in the same row (or column, for steep
+    /// lines) of a line
+    fn runs(line: &[Point<i32>]) -> Vec<usize> {
+        let steep = line.first().map(|p| p.y) != line.get(1).map(|p| p.y);
+        let key = |p: &Point<i32>| if steep { p.x } else { p.y };
+
+        line.chunk_by(|p, q| key(p) == key(q))
+            .map(|run| run.len())
+            .collect()
+    }
+
+    #[test_case((13, 5))]
+    #[test_case((-9, 20))]
+    #[test_case((30, -1))]
+    #[test_case((-7, -8))]
+    fn snapped_lines_are_clean(p: (i32, i32)) {
+        let p0 = Point::new(0, 0);
+        let runs = runs(&line(p0, snap_to_slope(p0, p.into())));
+
+        assert!(runs.iter().all(|len| *len == runs[0]), "{:?}", runs);
+    }
+
+    #[test_case((0, 0), (4, 4))]
+    #[test_case((0, 0), (5, 5))]
+    #[test_case((0, 0), (9, 4))]
+    #[test_case((0, 0), (10, 3))]
+    #[test_case((0, 0), (1, 6))]
+    #[test_case((3, 7), (0, 0))]
+    #[test_case((0, 0), (15, 1))]
+    fn ellipse_fits_and_is_symmetric(p1: (i32, i32), p2: (i32, i32)) {
+        let (p1, p2): (Point<i32>, Point<i32>) = (p1.into(), p2.into());
+        let min = p1.rect_min_corner(p2);
+        let max = min + p1.abs_diff(p2);
+        let points = ellipse(p1, p2);
+
+        assert!(points.iter().all(|p| p.x >= min.x && p.x <= max.x));
+        assert!(points.iter().all(|p| p.y >= min.y && p.y <= max.y));
+        for (x, y) in [(min.x, -1), (max.x, -1), (-1, min.y), (-1, max.y)] {
+            assert!(points.iter().any(|p| p.x == x || p.y == y));
+        }
+
+        let mirrored = |f: &dyn Fn(Point<i32>) -> Point<i32>| {
+            let mut mirrored: Vec<_> = points.iter().map(|p| f(*p)).collect();
+            mirrored.sort_by_key(|p| (p.y, p.x));
+            mirrored
+        };
+        assert_eq!(mirrored(&|p| Point::new(min.x + max.x - p.x, p.y)), points);
+        assert_eq!(mirrored(&|p| Point::new(p.x, min.y + max.y - p.y)), points);
+
+        // No gaps: every pixel touches another one
+        for p in &points {
+            assert!(points
+                .iter()
+                .any(|q| q != p && (q.x - p.x).abs() <= 1 && (q.y - p.y).abs() <= 1));
+        }
+    }
+
+    #[test]
+    fn polyline_joins_segments() {
+        let points = [(0, 0), (2, 0), (2, 2)].map(Point::from);
+
+        assert_eq!(
+            polyline(&points),
+            [(0, 0), (1, 0), (2, 0), (2, 1), (2, 2)].map(Point::from)
+        );
+        assert_eq!(polygon(&points).len(), 6);
+    }
+
+    #[test]
+    fn polygon_area_of_triangle() {
+        let points = [(0, 0), (4, 0), (0, 4)].map(Point::from);
+        let area = polygon_area(&points);
+
+        assert_eq!(area.len(), 15);
+        assert!(polygon(&points).iter().all(|p| area.contains(p)));
+        assert!(area.iter().all(|p| p.x + p.y <= 4 && p.x >= 0 && p.y >= 0));
+    }
+
+    #[test]
+    fn self_crossing_polygon_area() {
+        // A bow tie, whose halves touch only in the middle
+        let points = [(0, 0), (6, 6), (6, 0), (0, 6)].map(Point::from);
+        let area = polygon_area(&points);
+
+        assert!(area.contains(&Point::new(1, 3)) && area.contains(&Point::new(5, 3)));
+        assert!(!area.contains(&Point::new(3, 1)) && !area.contains(&Point::new(3, 5)));
+    }
+
+    #[test_case(vec![(0, 0), (5, 10), (10, 0)])]
+    #[test_case(vec![(0, 0), (0, 8), (12, 8), (12, 0)])]
+    #[test_case(vec![(0, 0), (30, -20), (-10, 20), (20, 3)])]
+    #[test_case(vec![(3, 3), (3, 3), (3, 3)])]
+    fn bezier_is_clean(points: Vec<(i32, i32)>) {
+        let points: Vec<Point<i32>> = points.into_iter().map(Into::into).collect();
+        let curve = bezier(&points);
+
+        assert_eq!(curve.first(), points.first());
+        assert_eq!(curve.last(), points.last());
+        for step in curve.windows(2) {
+            let d = step[0].abs_diff(step[1]);
+            assert!(d != Point::ZERO && d.x <= 1 && d.y <= 1, "{:?}", step);
+        }
+        for corner in curve.windows(3) {
+            assert!(!is_corner(corner[0], corner[1], corner[2]), "{:?}", corner);
+        }
+    }
+
+    #[test]
+    fn straight_bezier_is_a_line() {
+        let points = [(0, 0), (2, 1), (4, 2), (6, 3)].map(Point::from);
+
+        assert_eq!(bezier(&points), line(points[0], points[3]));
+    }
+
+    #[test_case(1, 1, 1)]
+    #[test_case(2, 2, 4)]
+    #[test_case(3, 3, 4)]
+    #[test_case(4, 4, 8)]
+    #[test_case(5, 5, 12)]
+    #[test_case(6, 3, 10)]
+    #[test_case(1, 3, 3)]
+    fn small_ellipses(w: i32, h: i32, pixels: usize) {
+        let points = ellipse(Point::new(0, 0), Point::new(w - 1, h - 1));
+
+        assert_eq!(points.len(), pixels, "{:?}", points);
+    }
 }
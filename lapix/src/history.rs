@@ -0,0 +1,261 @@
+use crate::{Action, AtomicAction, Bitmap, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+/// Default maximum number of actions kept in the [`History`]
+pub const DEFAULT_MAX_STEPS: usize = 500;
+/// Default maximum amount of memory (in bytes) used by the [`History`]
+pub const DEFAULT_MAX_BYTES: usize = 256 * 1024 * 1024;
+
+/// Limits on how much the [`History`] can grow. When a new action is recorded
+/// and a limit is exceeded, the oldest actions are dropped (but the newest one
+/// is always kept, even if it alone exceeds the memory limit). Only the
+/// actions that can be undone count towards the limits. `None` means no limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryLimits {
+    /// Maximum number of actions that can be undone
+    pub max_steps: Option<usize>,
+    /// Maximum amount of memory used by the actions that can be undone, in
+    /// bytes
+    pub max_bytes: Option<usize>,
+}
+
+impl Default for HistoryLimits {
+    fn default() -> Self {
+        Self {
+            max_steps: Some(DEFAULT_MAX_STEPS),
+            max_bytes: Some(DEFAULT_MAX_BYTES),
+        }
+    }
+}
+
+/// The history of undoable actions of a [`State`]. Actions that were undone
+/// are kept until a new action is made, so that they can be redone.
+///
+/// [`State`]: crate::State
+pub struct History<IMG> {
+    done: Vec<Action<IMG>>,
+    undone: Vec<Action<IMG>>,
+    current: Option<Action<IMG>>,
+    limits: HistoryLimits,
+    /// Changes whenever an action is recorded, undone or redone
+    version: usize,
+}
+
+impl<IMG> Default for History<IMG> {
+    fn default() -> Self {
+        Self {
+            done: Vec::new(),
+            undone: Vec::new(),
+            current: None,
+            limits: HistoryLimits::default(),
+            version: 0,
+        }
+    }
+}
+
+impl<IMG> Debug for History<IMG> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("History")
+            .field("done", &self.done.len())
+            .field("undone", &self.undone.len())
+            .field("limits", &self.limits)
+            .finish()
+    }
+}
+
+impl<IMG: Bitmap> History<IMG> {
+    /// Get the number of actions in the history, including the ones that were
+    /// undone and can be redone
+    pub fn len(&self) -> usize {
+        self.done.len() + self.undone.len()
+    }
+
+    /// Whether the history has no actions
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the current position in the history, that is, the number of actions
+    /// that are applied. Position 0 is the state before any action.
+    pub fn position(&self) -> usize {
+        self.done.len()
+    }
+
+    /// Get the labels of all actions in chronological order. The first
+    /// [`position`] of them are applied, the rest were undone.
+    ///
+    /// [`position`]: History::position
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.done
+            .iter()
+            .chain(self.undone.iter().rev())
+            .map(|action| action.label())
+    }
+
+    /// Whether there is an action that can be undone
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    /// Whether there is an action that can be redone
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    /// Approximate amount of memory used by all the actions in the history, in
+    /// bytes
+    pub fn memory_size(&self) -> usize {
+        self.done
+            .iter()
+            .chain(self.undone.iter())
+            .chain(self.current.iter())
+            .map(|action| action.memory_size())
+            .sum()
+    }
+
+    /// A number that changes whenever an action is recorded, undone or redone,
+    /// so that the last action can be recognized later (positions cannot be
+    /// used, since they shift when the oldest actions are dropped)
+    pub(crate) fn version(&self) -> usize {
+        self.version
+    }
+
+    /// Get the limits on the growth of the history
+    pub fn limits(&self) -> HistoryLimits {
+        self.limits
+    }
+
+    /// Change the limits on the growth of the history, dropping the oldest
+    /// actions if they are exceeded
+    pub(crate) fn set_limits(&mut self, limits: HistoryLimits) {
+        self.limits = limits;
+        self.enforce_limits();
+    }
+
+    /// Drop all actions, keeping the limits
+    pub(crate) fn clear(&mut self) {
+        self.done.clear();
+        self.undone.clear();
+        self.current = None;
+        self.version += 1;
+    }
+
+    /// Start a new action. Any action that was in progress is finished first.
+    pub(crate) fn start<S: Into<String>>(&mut self, label: S) {
+        self.end();
+        self.current = Some(Action::new(label, Vec::new()));
+    }
+
+    /// Add reversals to the action in progress, starting one if needed
+    pub(crate) fn add(&mut self, actions: Vec<AtomicAction<IMG>>) -> Result<()> {
+        if self.current.is_none() {
+            self.start("Edit");
+        }
+        self.current
+            .as_mut()
+            .ok_or(Error::ReversalNotSet)?
+            .append(actions);
+
+        Ok(())
+    }
+
+    /// Finish the action in progress, if any, recording it
+    pub(crate) fn end(&mut self) {
+        if let Some(action) = self.current.take() {
+            self.push(action);
+        }
+    }
+
+    /// Record a new undoable action. Any actions that were undone before are
+    /// lost, since they cannot be redone on top of the new one
+    pub(crate) fn push(&mut self, mut action: Action<IMG>) {
+        self.end();
+
+        if action.is_empty() {
+            return;
+        }
+
+        action.compact();
+        self.done.push(action);
+        self.undone.clear();
+        self.version += 1;
+        self.enforce_limits();
+    }
+
+    /// Add reversals to the last recorded action, finishing the action in
+    /// progress first
+    pub(crate) fn extend_last(&mut self, actions: Vec<AtomicAction<IMG>>) -> Result<()> {
+        self.end();
+        self.done
+            .last_mut()
+            .ok_or(Error::ReversalNotSet)?
+            .append(actions);
+        self.enforce_limits();
+
+        Ok(())
+    }
+
+    /// Merge all the actions after a position (see [`position`]) into a single
+    /// one, finishing the action in progress first
+    ///
+    /// [`position`]: History::position
+    pub(crate) fn merge_since<S: Into<String>>(&mut self, position: usize, label: S) {
+        self.end();
+
+        if self.done.len() <= position {
+            return;
+        }
+
+        let mut merged = Action::new(label, Vec::new());
+        for action in self.done.split_off(position) {
+            merged.merge(action);
+        }
+        self.push(merged);
+    }
+
+    /// Drop the oldest actions until the history is within its limits
+    fn enforce_limits(&mut self) {
+        let mut excess = 0;
+
+        if let Some(max_steps) = self.limits.max_steps {
+            excess = self.done.len().saturating_sub(max_steps.max(1));
+        }
+
+        if let Some(max_bytes) = self.limits.max_bytes {
+            let mut total: usize = self.done[excess..]
+                .iter()
+                .map(|action| action.memory_size())
+                .sum();
+
+            while total > max_bytes && excess + 1 < self.done.len() {
+                total -= self.done[excess].memory_size();
+                excess += 1;
+            }
+        }
+
+        self.done.drain(..excess);
+    }
+
+    /// Take the next action to be undone
+    pub(crate) fn pop_undo(&mut self) -> Option<Action<IMG>> {
+        self.version += 1;
+        self.done.pop()
+    }
+
+    /// Take the next action to be redone
+    pub(crate) fn pop_redo(&mut self) -> Option<Action<IMG>> {
+        self.undone.pop()
+    }
+
+    /// Record the action that redoes an action that was just undone
+    pub(crate) fn push_redo(&mut self, action: Action<IMG>) {
+        self.undone.push(action);
+    }
+
+    /// Record the action that undoes an action that was just redone
+    pub(crate) fn push_undo(&mut self, action: Action<IMG>) {
+        self.version += 1;
+        self.done.push(action);
+    }
+}
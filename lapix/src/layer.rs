@@ -61,10 +61,18 @@ impl<IMG: Bitmap> Layers<IMG> {
     pub fn blended_area(&self, r: Rect<i32>) -> IMG {
         let mut result = IMG::new((r.w, r.h).into(), TRANSPARENT);
 
-        for i in 0..r.w {
-            for j in 0..r.h {
-                let ij = Point::new(i, j);
-                result.set_pixel(ij, self.visible_pixel(ij + r.pos()));
+        // Same as `visible_pixel` for every pixel, a layer at a time
+        for (i, layer) in self.inner.iter().enumerate() {
+            if !layer.visible() {
+                continue;
+            }
+
+            let img = layer.canvas().inner();
+            if i == 0 {
+                result.copy_rect(img, r, Point::ZERO);
+                result.map_pixels(|color| color.with_multiplied_alpha(layer.opacity()));
+            } else {
+                result.blit(img, r, Point::ZERO, layer.opacity());
             }
         }
 
@@ -110,6 +118,13 @@ impl<IMG: Bitmap> Layers<IMG> {
         self.inner.push(layer);
     }
 
+    /// Add a new [`Layer`] right below the active one. The new layer becomes
+    /// the active one
+    pub fn add_new_below(&mut self) {
+        let layer = Layer::new(self.active_canvas().size());
+        self.inner.insert(self.active, layer);
+    }
+
     /// Add a new [`Layer`] at the specified index
     pub fn add_at(&mut self, index: usize, layer: Layer<IMG>) {
         self.inner.insert(index, layer);
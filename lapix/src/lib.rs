@@ -3,33 +3,52 @@
 
 mod action;
 mod bitmap;
+mod brush;
 mod canvas;
 pub mod color;
 mod error;
 mod event;
+mod fill;
 mod floating;
 pub mod graphics;
+mod history;
 mod layer;
+mod mask;
 mod palette;
 pub mod primitives;
+mod project;
+#[cfg(feature = "rgba-bitmap")]
+mod rgba_bitmap;
+mod shape;
 mod state;
+mod storage;
+mod symmetry;
 mod tool;
 mod transform;
 mod util;
 
 use action::{Action, AtomicAction};
 pub use bitmap::Bitmap;
+pub use brush::{Brush, BrushShape};
 pub use canvas::{Canvas, CanvasEffect};
 pub use color::{Color, ColorF32};
 pub use error::{Error, Result};
 pub use event::Event;
+pub use fill::BucketOptions;
 pub use floating::FreeImage;
+pub use history::{History, HistoryLimits};
 pub use layer::{Layer, Layers};
+pub use mask::Mask;
 use palette::Palette;
 pub use primitives::*;
+pub use project::PROJECT_FORMAT_VERSION;
+#[cfg(feature = "rgba-bitmap")]
+pub use rgba_bitmap::RgbaBitmap;
+pub use shape::{PathShape, ShapeConstraints, ShapeOptions, ShapeStyle};
 pub use state::{Selection, State};
+pub use storage::{FileStorage, FnStorage, MemoryStorage, ProjectStorage};
+pub use symmetry::{Symmetry, SymmetryMode};
 pub use tool::Tool;
 pub use transform::Transform;
-pub use util::{LoadProject, SaveProject};
 
 pub use bitmap::TestImage;
@@ -0,0 +1,241 @@
+//! Selections of any shape, as the set of the selected pixels
+
+use crate::{Point, Position, Rect};
+use serde::{Deserialize, Serialize};
+
+/// The pixels of a selection, which may have any shape (e.g. the ones picked
+/// by the magic wand or inside a lasso). It is stored as whether each pixel of
+/// its bounding box is selected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mask {
+    bounds: Rect<i32>,
+    selected: Vec<bool>,
+}
+
+impl Mask {
+    /// A mask selecting every pixel of a rectangle
+    pub fn from_rect(rect: Rect<i32>) -> Self {
+        let bounds = Rect::new(rect.x, rect.y, rect.w.max(0), rect.h.max(0));
+
+        Self {
+            bounds,
+            selected: vec![true; (bounds.w * bounds.h) as usize],
+        }
+    }
+
+    /// A mask selecting some pixels, with the bounding box that fits them
+    pub fn from_pixels<I: IntoIterator<Item = Point<i32>>>(pixels: I) -> Self {
+        let pixels: Vec<_> = pixels.into_iter().collect();
+        let (Some(x0), Some(x1)) = (
+            pixels.iter().map(|p| p.x).min(),
+            pixels.iter().map(|p| p.x).max(),
+        ) else {
+            return Self::from_rect(Rect::new(0, 0, 0, 0));
+        };
+        let y0 = pixels.iter().map(|p| p.y).min().unwrap_or(0);
+        let y1 = pixels.iter().map(|p| p.y).max().unwrap_or(0);
+
+        let bounds = Rect::new(x0, y0, x1 - x0 + 1, y1 - y0 + 1);
+        let mut mask = Self {
+            bounds,
+            selected: vec![false; (bounds.w * bounds.h) as usize],
+        };
+        for p in pixels {
+            let i = mask.index(p);
+            mask.selected[i] = true;
+        }
+
+        mask
+    }
+
+    /// The bounding box of the mask
+    pub fn bounds(&self) -> Rect<i32> {
+        self.bounds
+    }
+
+    /// Whether a pixel is selected
+    pub fn contains(&self, p: Point<i32>) -> bool {
+        let Rect { x, y, w, h } = self.bounds;
+        let inside = p.x >= x && p.y >= y && p.x < x + w && p.y < y + h;
+
+        inside && self.selected[self.index(p)]
+    }
+
+    /// Whether no pixel is selected
+    pub fn is_empty(&self) -> bool {
+        !self.selected.contains(&true)
+    }
+
+    /// Whether every pixel of the bounding box is selected
+    pub fn is_rect(&self) -> bool {
+        !self.selected.contains(&false)
+    }
+
+    /// The selected pixels, row by row
+    pub fn pixels(&self) -> impl Iterator<Item = Point<i32>> + '_ {
+        let Rect { x, y, w, h } = self.bounds;
+
+        (y..y + h)
+            .flat_map(move |j| (x..x + w).map(move |i| Point::new(i, j)))
+            .filter(|p| self.contains(*p))
+    }
+
+    /// The same mask, with its bounding box starting at another position
+    pub fn moved_to(&self, p: Position<i32>) -> Self {
+        Self {
+            bounds: Rect::new(p.x, p.y, self.bounds.w, self.bounds.h),
+            selected: self.selected.clone(),
+        }
+    }
+
+    /// The selected pixels that are inside a rectangle (e.g. the canvas)
+    pub fn clip_to(&self, rect: Rect<i32>) -> Self {
+        if self.is_rect() {
+            return Self::from_rect(self.bounds.clip_to(rect));
+        }
+
+        let inside = |p: &Point<i32>| {
+            p.x >= rect.x && p.y >= rect.y && p.x < rect.x + rect.w && p.y < rect.y + rect.h
+        };
+        Self::from_pixels(self.pixels().filter(inside))
+    }
+
+    /// Flip the mask horizontally, inside its bounding box
+    pub fn flip_horizontally(&mut self) {
+        if self.bounds.w > 0 {
+            for row in self.selected.chunks_mut(self.bounds.w as usize) {
+                row.reverse();
+            }
+        }
+    }
+
+    /// Flip the mask vertically, inside its bounding box
+    pub fn flip_vertically(&mut self) {
+        if self.bounds.w > 0 {
+            let rows: Vec<_> = self.selected.chunks(self.bounds.w as usize).rev().collect();
+            self.selected = rows.concat();
+        }
+    }
+
+    /// The boundary of the selected area, i.e. the edges between a selected
+    /// pixel and one that is not. Each segment goes from a corner of a pixel
+    /// to another (the corners of the pixel (0, 0) being (0, 0) and (1, 1)),
+    /// and straight edges are joined into a single segment.
+    pub fn outline(&self) -> Vec<(Point<i32>, Point<i32>)> {
+        let Rect { x, y, w, h } = self.bounds;
+        let mut segments = Vec::new();
+
+        // Along the top of each row (and the bottom of the last one)
+        for j in y..=y + h {
+            let edge = |i| self.contains(Point::new(i, j - 1)) != self.contains(Point::new(i, j));
+            segments.extend(
+                runs(x, x + w, edge).map(|(i0, i1)| (Point::new(i0, j), Point::new(i1, j))),
+            );
+        }
+        // Along the left of each column (and the right of the last one)
+        for i in x..=x + w {
+            let edge = |j| self.contains(Point::new(i - 1, j)) != self.contains(Point::new(i, j));
+            segments.extend(
+                runs(y, y + h, edge).map(|(j0, j1)| (Point::new(i, j0), Point::new(i, j1))),
+            );
+        }
+
+        segments
+    }
+
+    /// The index of a pixel (inside the bounding box) in `selected`
+    fn index(&self, p: Point<i32>) -> usize {
+        ((p.y - self.bounds.y) * self.bounds.w + p.x - self.bounds.x) as usize
+    }
+}
+
+/// The runs of values between `start` and `end` (exclusive) for which a
+/// condition holds, each as its first value and the one after its last
+fn runs<F: Fn(i32) -> bool>(start: i32, end: i32, f: F) -> impl Iterator<Item = (i32, i32)> {
+    let mut i = start;
+
+    std::iter::from_fn(move || {
+        while i < end && !f(i) {
+            i += 1;
+        }
+        let run_start = i;
+        while i < end && f(i) {
+            i += 1;
+        }
+
+        (i > run_start).then_some((run_start, i))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    /// Read a mask from rows of characters, `#` being a selected pixel
+    fn mask(rows: &[&str]) -> Mask {
+        let pixels = rows.iter().enumerate().flat_map(|(y, row)| {
+            row.char_indices()
+                .filter(|(_, c)| *c == '#')
+                .map(move |(x, _)| Point::new(x as i32, y as i32))
+        });
+
+        Mask::from_pixels(pixels)
+    }
+
+    #[test]
+    fn from_pixels() {
+        let mask = mask(&["....", ".#..", "..##"]);
+
+        assert_eq!(mask.bounds(), Rect::new(1, 1, 3, 2));
+        assert!(mask.contains(Point::new(1, 1)));
+        assert!(!mask.contains(Point::new(2, 1)));
+        assert!(!mask.contains(Point::new(0, 0)));
+        assert_eq!(mask.pixels().count(), 3);
+    }
+
+    #[test_case(&["#"], 4 ; "pixel")]
+    #[test_case(&["##", "##"], 4 ; "square")]
+    #[test_case(&["##", "#."], 6 ; "corner")]
+    #[test_case(&["###", "#.#", "###"], 8 ; "ring")]
+    #[test_case(&["#.", ".#"], 6 ; "diagonal")]
+    fn outline(rows: &[&str], segments: usize) {
+        assert_eq!(mask(rows).outline().len(), segments);
+    }
+
+    #[test]
+    fn outline_of_a_rectangle() {
+        let mask = Mask::from_rect(Rect::new(1, 2, 3, 4));
+        let mut outline = mask.outline();
+        outline.sort_by_key(|(a, b)| (a.x, a.y, b.x, b.y));
+
+        let expected = [
+            ((1, 2), (1, 6)),
+            ((1, 2), (4, 2)),
+            ((1, 6), (4, 6)),
+            ((4, 2), (4, 6)),
+        ];
+        assert_eq!(outline, expected.map(|(a, b)| (a.into(), b.into())));
+    }
+
+    #[test]
+    fn flip() {
+        let mut mask = mask(&["##", "#."]);
+        mask.flip_horizontally();
+        assert!(!mask.contains(Point::new(0, 1)) && mask.contains(Point::new(1, 1)));
+
+        mask.flip_vertically();
+        assert!(!mask.contains(Point::new(0, 0)) && mask.contains(Point::new(1, 0)));
+    }
+
+    #[test]
+    fn clip_to() {
+        let mask = mask(&["#..", ".#.", "..#"]).clip_to(Rect::new(1, 0, 5, 5));
+
+        assert_eq!(mask.bounds(), Rect::new(1, 1, 2, 2));
+        assert_eq!(mask.pixels().count(), 2);
+        assert!(Mask::from_rect(Rect::new(-2, -2, 4, 4))
+            .clip_to(Rect::new(0, 0, 8, 8))
+            .is_rect());
+    }
+}
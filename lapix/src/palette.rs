@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 
 const MAX_PALETTE: usize = 200;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Palette(Vec<Color>);
 
 impl Default for Palette {
@@ -27,6 +27,12 @@ impl Default for Palette {
     }
 }
 
+impl From<Vec<Color>> for Palette {
+    fn from(colors: Vec<Color>) -> Self {
+        Self(colors)
+    }
+}
+
 impl Palette {
     pub fn from_file(path: &str) -> Result<Self> {
         let img = util::load_img_from_file(path)?;
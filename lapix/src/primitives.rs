@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 use std::cmp::PartialOrd;
 use std::fmt::Debug;
 use std::ops::{Add, Sub};
+use std::str::FromStr;
 
 /// Represents a number
 pub trait Number:
@@ -85,6 +86,20 @@ impl<T: Number> Point<T> {
     }
 }
 
+/// Parse a [`Size`] written as `WxH` (e.g. `64x32`). Returns `None` if it is
+/// not written like that or if a side is less than `min`.
+pub fn parse_size<T: Number + FromStr>(s: &str, min: T) -> Option<Size<T>> {
+    let (x, y) = s.split_once(['x', 'X'])?;
+    let x: T = x.trim().parse().ok()?;
+    let y: T = y.trim().parse().ok()?;
+
+    if x < min || y < min {
+        return None;
+    }
+
+    Some(Size::new(x, y))
+}
+
 impl Point<f32> {
     /// The point (0, 0)
     pub const ZERO_F32: Self = Point::new(0., 0.);
@@ -118,7 +133,7 @@ impl Point<i32> {
 }
 
 /// Represents one of the 4 basic 2D directions
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Direction {
     Up,
     Down,
@@ -137,7 +152,7 @@ pub struct Rect<T: Number> {
 
 impl<T: Number> Rect<T> {
     /// Create a new rectangle
-    pub fn new(x: T, y: T, w: T, h: T) -> Self {
+    pub const fn new(x: T, y: T, w: T, h: T) -> Self {
         Self { x, y, w, h }
     }
 
@@ -194,6 +209,21 @@ impl<T: Number + Ord> Rect<T> {
             h: y1 - y,
         }
     }
+
+    /// The smallest rectangle containing both this one and another
+    pub fn union(self, other: Self) -> Self {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let x1 = (self.x + self.w).max(other.x + other.w);
+        let y1 = (self.y + self.h).max(other.y + other.h);
+
+        Self {
+            x,
+            y,
+            w: x1 - x,
+            h: y1 - y,
+        }
+    }
 }
 
 impl From<Rect<u16>> for Rect<i32> {
@@ -269,6 +299,13 @@ mod tests {
         assert_eq!(r.into().clip_to(clip.into()), res.into());
     }
 
+    #[test_case((0, 0, 2, 2), (1, 1, 2, 2), (0, 0, 3, 3))]
+    #[test_case((0, 0, 4, 4), (1, 1, 1, 1), (0, 0, 4, 4))]
+    #[test_case((-2, 3, 1, 1), (5, 0, 1, 2), (-2, 0, 8, 4))]
+    fn rect_union<R: Into<Rect<i32>>>(r: R, other: R, res: R) {
+        assert_eq!(r.into().union(other.into()), res.into());
+    }
+
     #[test_case((0, 0, 1, 1), (0, 0), true)]
     #[test_case((0, 0, 2, 2), (1, 1), true)]
     #[test_case((0, 0, 1, 1), (0, 1), true)]
@@ -280,6 +317,15 @@ mod tests {
         assert_eq!(r.into().contains(p.0, p.1), res);
     }
 
+    #[test_case("64x32", Some((64, 32)))]
+    #[test_case("3 X 4", Some((3, 4)))]
+    #[test_case("0x4", None)]
+    #[test_case("64", None)]
+    #[test_case("ax4", None)]
+    fn size_from_str(s: &str, size: Option<(i32, i32)>) {
+        assert_eq!(parse_size(s, 1), size.map(Size::from));
+    }
+
     #[test]
     fn rect_extremes() {
         let r = Rect::new(0, 1, 2, 3);
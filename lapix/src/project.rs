@@ -0,0 +1,265 @@
+//! The project file format. A project file starts with a magic number and the
+//! version of the format, followed by the project data encoded with bincode.
+//! Files written by older versions of the format are migrated when loaded.
+
+use crate::{Bitmap, Color, Error, Layers, Palette, Point, Rect, Result, Size};
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+
+/// Identifies a project file
+const MAGIC: [u8; 5] = [0xfa, 0x1a, 0xfe, 0x1b, 0xee];
+
+/// Files written before the format was versioned start with this, followed by
+/// the version of the program and the magic number
+const LEGACY_PREFIX: &[u8] = b"tarsila ";
+
+/// The version of the project file format written by this version of lapix
+pub const PROJECT_FORMAT_VERSION: u32 = 1;
+
+/// The data of a project, that is, what is saved to and loaded from a project
+/// file
+pub(crate) struct Project<IMG> {
+    pub layers: Layers<IMG>,
+    pub palette: Palette,
+    pub spritesheet: Size<u8>,
+    pub main_color: Color,
+}
+
+/// Version 1 of the project file contents. Images are stored as PNG so that
+/// the format does not depend on how a [`Bitmap`] serializes itself.
+#[derive(Serialize, Deserialize)]
+struct ProjectV1 {
+    size: Size<i32>,
+    layers: Vec<LayerV1>,
+    active_layer: usize,
+    palette: Vec<Color>,
+    spritesheet: Size<u8>,
+    main_color: Color,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LayerV1 {
+    visible: bool,
+    opacity: u8,
+    png: Vec<u8>,
+}
+
+/// Encode a project in the current version of the file format
+pub(crate) fn encode<IMG: Bitmap>(
+    layers: &Layers<IMG>,
+    palette: &Palette,
+    spritesheet: Size<u8>,
+    main_color: Color,
+) -> Result<Vec<u8>> {
+    let data = ProjectV1 {
+        size: layers.active_canvas().size(),
+        layers: (0..layers.count())
+            .map(|i| {
+                let layer = layers.get(i);
+
+                Ok(LayerV1 {
+                    visible: layer.visible(),
+                    opacity: layer.opacity(),
+                    png: layer.canvas().inner().png_bytes()?,
+                })
+            })
+            .collect::<Result<_>>()?,
+        active_layer: layers.active_index(),
+        palette: palette.colors().to_vec(),
+        spritesheet,
+        main_color,
+    };
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(PROJECT_FORMAT_VERSION.to_le_bytes());
+    bytes.extend(bincode_options().serialize(&data)?);
+
+    Ok(bytes)
+}
+
+/// Decode a project file, of the current or any previous version of the format
+pub(crate) fn decode<IMG: Bitmap>(bytes: &[u8]) -> Result<Project<IMG>> {
+    let data = match bytes.strip_prefix(MAGIC.as_slice()) {
+        Some(rest) => {
+            let (version, body) = rest
+                .split_first_chunk::<4>()
+                .ok_or(Error::InvalidProjectFile)?;
+
+            match u32::from_le_bytes(*version) {
+                1 => deserialize(body)?,
+                version => return Err(Error::UnsupportedProjectVersion(version)),
+            }
+        }
+        None => migrate_legacy(legacy_body(bytes)?)?,
+    };
+
+    data.into_project()
+}
+
+/// Options equivalent to the ones of `bincode::serialize`
+fn bincode_options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+}
+
+/// Deserialize refusing to read more than the size of the input, so that a
+/// corrupt length in the file cannot cause a huge allocation
+fn deserialize<'a, T: Deserialize<'a>>(body: &'a [u8]) -> Result<T> {
+    Ok(bincode_options()
+        .with_limit(body.len() as u64)
+        .deserialize(body)?)
+}
+
+impl ProjectV1 {
+    fn into_project<IMG: Bitmap>(self) -> Result<Project<IMG>> {
+        if self.size.x <= 0 || self.size.y <= 0 {
+            return Err(Error::CorruptProject(format!(
+                "invalid canvas size {}x{}",
+                self.size.x, self.size.y
+            )));
+        }
+        let Size { x: cols, y: rows } = self.spritesheet;
+        if cols == 0
+            || rows == 0
+            || self.size.x % cols as i32 != 0
+            || self.size.y % rows as i32 != 0
+        {
+            return Err(Error::CorruptProject(format!(
+                "spritesheet of {cols}x{rows} frames for a {}x{} canvas",
+                self.size.x, self.size.y
+            )));
+        }
+        if self.active_layer >= self.layers.len() {
+            return Err(Error::CorruptProject(format!(
+                "active layer {} out of {}",
+                self.active_layer,
+                self.layers.len()
+            )));
+        }
+
+        let mut layers = Layers::new(self.size);
+
+        for (i, data) in self.layers.into_iter().enumerate() {
+            let img = IMG::try_from_file_bytes(data.png)?;
+
+            if img.size() != self.size {
+                return Err(Error::CorruptProject(format!(
+                    "layer {} is {}x{}, but the canvas is {}x{}",
+                    i + 1,
+                    img.width(),
+                    img.height(),
+                    self.size.x,
+                    self.size.y
+                )));
+            }
+
+            if i > 0 {
+                layers.add_new_above();
+            }
+            layers.canvas_at_mut(i).set_img(img);
+            layers.set_visibility(i, data.visible);
+            layers.set_opacity(i, data.opacity);
+        }
+        layers.switch_to(self.active_layer);
+
+        Ok(Project {
+            layers,
+            palette: self.palette.into(),
+            spritesheet: self.spritesheet,
+            main_color: self.main_color,
+        })
+    }
+}
+
+/// Get the body of a file written before the format was versioned, i.e.
+/// after the "tarsila VERSION " header and the magic number
+fn legacy_body(bytes: &[u8]) -> Result<&[u8]> {
+    if !bytes.starts_with(LEGACY_PREFIX) {
+        return Err(Error::InvalidProjectFile);
+    }
+
+    let start = bytes
+        .windows(MAGIC.len())
+        .position(|win| win == MAGIC)
+        .ok_or(Error::InvalidProjectFile)?;
+
+    Ok(&bytes[start + MAGIC.len()..])
+}
+
+// The legacy types below mirror the old layout field by field, since bincode
+// is positional. Fields that are not migrated are read only to skip over them.
+
+/// Layout of the `State` that was serialized directly by lapix 0.1, with
+/// images as PNG (as serialized by tarsila)
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct LegacyState {
+    layers: Vec<LegacyLayer>,
+    active_layer: usize,
+    tool: u32,
+    main_color: Color,
+    spritesheet: Size<u8>,
+    palette: Vec<Color>,
+    selection: Option<LegacySelection>,
+    free_image: Option<LegacyFreeImage>,
+    clipboard: Option<Vec<u8>>,
+}
+
+#[derive(Deserialize)]
+struct LegacyLayer {
+    png: Vec<u8>,
+    visible: bool,
+    opacity: u8,
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+enum LegacySelection {
+    Canvas(Rect<i32>),
+    FreeImage,
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct LegacyFreeImage {
+    rect: Rect<i32>,
+    pivot: Option<Point<i32>>,
+    texture: Vec<u8>,
+}
+
+/// Convert a file written before the format was versioned. The selection,
+/// floating image and clipboard are not part of the project anymore, so they
+/// are dropped.
+fn migrate_legacy(body: &[u8]) -> Result<ProjectV1> {
+    let legacy: LegacyState = deserialize(body)?;
+    let first = legacy
+        .layers
+        .first()
+        .ok_or_else(|| Error::CorruptProject("no layers".to_owned()))?;
+    let size = png_size(&first.png)?;
+
+    Ok(ProjectV1 {
+        size,
+        layers: legacy
+            .layers
+            .into_iter()
+            .map(|layer| LayerV1 {
+                visible: layer.visible,
+                opacity: layer.opacity,
+                png: layer.png,
+            })
+            .collect(),
+        active_layer: legacy.active_layer,
+        palette: legacy.palette,
+        spritesheet: legacy.spritesheet,
+        main_color: legacy.main_color,
+    })
+}
+
+fn png_size(png: &[u8]) -> Result<Size<i32>> {
+    let reader = image::ImageReader::new(std::io::Cursor::new(png)).with_guessed_format()?;
+    let (w, h) = reader.into_dimensions()?;
+
+    Ok(Size::new(w as i32, h as i32))
+}
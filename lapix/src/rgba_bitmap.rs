@@ -0,0 +1,250 @@
+use crate::{Bitmap, Color, Point, Result, Size};
+use image::{ImageFormat, Rgba, RgbaImage};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A [`Bitmap`] backed by an [`RgbaImage`], for when there is no window or
+/// GPU, e.g. in command-line tools and tests. It is serialized as PNG, like
+/// the images in project files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RgbaBitmap(pub RgbaImage);
+
+impl From<RgbaImage> for RgbaBitmap {
+    fn from(img: RgbaImage) -> Self {
+        Self(img)
+    }
+}
+
+impl From<RgbaBitmap> for RgbaImage {
+    fn from(bitmap: RgbaBitmap) -> Self {
+        bitmap.0
+    }
+}
+
+impl Serialize for RgbaBitmap {
+    fn serialize<S>(&self, ser: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::Error as _;
+        let bytes = self
+            .png_bytes()
+            .map_err(|e| S::Error::custom(format!("Error serializing image: {}", e)))?;
+
+        ser.serialize_bytes(&bytes)
+    }
+}
+
+impl<'a> Deserialize<'a> for RgbaBitmap {
+    fn deserialize<D>(d: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'a>,
+    {
+        use serde::de::Error as _;
+        let vec = Vec::<u8>::deserialize(d)?;
+        Self::try_from_file_bytes(vec)
+            .map_err(|e| D::Error::custom(format!("Error deserializing image: {}", e)))
+    }
+}
+
+impl Bitmap for RgbaBitmap {
+    fn new(size: Size<i32>, color: Color) -> Self {
+        let pixel = Rgba([color.r, color.g, color.b, color.a]);
+
+        Self(RgbaImage::from_pixel(size.x as u32, size.y as u32, pixel))
+    }
+
+    fn size(&self) -> Size<i32> {
+        (self.width(), self.height()).into()
+    }
+
+    fn width(&self) -> i32 {
+        self.0.width() as i32
+    }
+
+    fn height(&self) -> i32 {
+        self.0.height() as i32
+    }
+
+    fn pixel(&self, p: Point<i32>) -> Color {
+        let [r, g, b, a] = self.0.get_pixel(p.x as u32, p.y as u32).0;
+
+        Color::new(r, g, b, a)
+    }
+
+    fn set_pixel(&mut self, p: Point<i32>, color: Color) {
+        let pixel = Rgba([color.r, color.g, color.b, color.a]);
+        self.0.put_pixel(p.x as u32, p.y as u32, pixel);
+    }
+
+    fn bytes(&self) -> &[u8] {
+        self.0.as_raw()
+    }
+
+    fn row_mut(&mut self, y: i32) -> Option<&mut [u8]> {
+        let len = self.0.width() as usize * 4;
+        let bytes: &mut [u8] = &mut self.0;
+
+        Some(&mut bytes[y as usize * len..][..len])
+    }
+
+    fn from_parts(size: Size<i32>, bytes: &[u8]) -> Self {
+        let img = RgbaImage::from_raw(size.x as u32, size.y as u32, bytes.to_vec())
+            .expect("the number of bytes should match the size");
+
+        Self(img)
+    }
+
+    fn set_from(&mut self, other: &Self) {
+        let row_len = self.0.width().min(other.0.width()) as usize * 4;
+        let h = self.0.height().min(other.0.height()) as usize;
+        let self_stride = self.0.width() as usize * 4;
+        let other_stride = other.0.width() as usize * 4;
+
+        let dst: &mut [u8] = &mut self.0;
+        for y in 0..h {
+            let src = &other.0.as_raw()[y * other_stride..][..row_len];
+            dst[y * self_stride..][..row_len].copy_from_slice(src);
+        }
+    }
+
+    fn png_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        self.0.write_to(&mut bytes, ImageFormat::Png)?;
+
+        Ok(bytes.into_inner())
+    }
+
+    fn try_from_file_bytes(bytes: Vec<u8>) -> Result<Self> {
+        let reader = image::ImageReader::new(std::io::Cursor::new(bytes)).with_guessed_format()?;
+        let img = reader.decode()?;
+
+        Ok(Self(img.into_rgba8()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{BLACK, TRANSPARENT};
+    use crate::{Rect, TestImage};
+    use test_case::test_case;
+
+    const RED: Color = Color::new(255, 0, 0, 255);
+
+    #[test]
+    fn pixels_and_bytes() {
+        let mut img = RgbaBitmap::new(Size::new(3, 2), TRANSPARENT);
+        img.set_pixel(Point::new(2, 1), RED);
+
+        assert_eq!(img.pixel(Point::new(2, 1)), RED);
+        assert_eq!(img.bytes().len(), 3 * 2 * 4);
+        assert_eq!(&img.bytes()[20..], &[255, 0, 0, 255]);
+        assert_eq!(RgbaBitmap::from_parts(img.size(), img.bytes()), img);
+    }
+
+    #[test]
+    fn set_from_keeps_size() {
+        let mut small = RgbaBitmap::new(Size::new(2, 3), BLACK);
+        let mut big = RgbaBitmap::new(Size::new(4, 1), TRANSPARENT);
+        big.set_pixel(Point::new(1, 0), RED);
+        big.set_pixel(Point::new(3, 0), RED);
+
+        small.set_from(&big);
+        assert_eq!(small.size(), Size::new(2, 3));
+        assert_eq!(small.pixel(Point::new(0, 0)), TRANSPARENT);
+        assert_eq!(small.pixel(Point::new(1, 0)), RED);
+        assert_eq!(small.pixel(Point::new(1, 2)), BLACK);
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let mut img = RgbaBitmap::new(Size::new(5, 4), TRANSPARENT);
+        img.set_pixel(Point::new(4, 3), Color::new(1, 2, 3, 4));
+
+        let bytes = bincode::serialize(&img).unwrap();
+        assert_eq!(bincode::deserialize::<RgbaBitmap>(&bytes).unwrap(), img);
+    }
+
+    /// A 4x3 image with a different color in each pixel
+    fn gradient<IMG: Bitmap>() -> IMG {
+        let mut img = IMG::new(Size::new(4, 3), TRANSPARENT);
+        for x in 0..4 {
+            for y in 0..3 {
+                let v = (x * 3 + y) as u8;
+                img.set_pixel(Point::new(x, y), Color::new(v * 20, 255 - v, v, v * 21));
+            }
+        }
+
+        img
+    }
+
+    /// Check that an operation gives the same result going through the rows of
+    /// an `RgbaBitmap` as going one pixel at a time in a `TestImage`
+    fn same_as_per_pixel(op: impl Fn(&mut dyn BulkOps)) {
+        let mut fast = RgbaBitmap::new(Size::new(5, 4), BLACK);
+        let mut slow = TestImage::new(Size::new(5, 4), BLACK);
+        op(&mut fast);
+        op(&mut slow);
+
+        assert_eq!(fast.bytes(), slow.bytes());
+    }
+
+    /// The bulk operations of a `Bitmap`, taking a source image of the same
+    /// type, so that the test cases can be shared
+    trait BulkOps {
+        fn fill(&mut self, rect: Rect<i32>, color: Color);
+        fn copy(&mut self, rect: Rect<i32>, dest: Point<i32>);
+        fn blit(&mut self, rect: Rect<i32>, dest: Point<i32>, opacity: u8);
+        fn map(&mut self);
+    }
+
+    impl<IMG: Bitmap> BulkOps for IMG {
+        fn fill(&mut self, rect: Rect<i32>, color: Color) {
+            self.fill_rect(rect, color);
+        }
+        fn copy(&mut self, rect: Rect<i32>, dest: Point<i32>) {
+            self.copy_rect(&gradient(), rect, dest);
+        }
+        fn blit(&mut self, rect: Rect<i32>, dest: Point<i32>, opacity: u8) {
+            Bitmap::blit(self, &gradient(), rect, dest, opacity);
+        }
+        fn map(&mut self) {
+            self.map_pixels(|c| Color::new(c.g, c.r, c.b, c.a / 2));
+        }
+    }
+
+    #[test_case((1, 1, 2, 2))]
+    #[test_case((-2, 3, 4, 4))]
+    #[test_case((0, 0, 9, 9))]
+    #[test_case((6, 0, 1, 1))]
+    fn fill_rect(rect: (i32, i32, i32, i32)) {
+        same_as_per_pixel(|img| img.fill(rect.into(), RED));
+    }
+
+    #[test_case((0, 0, 4, 3), (0, 0))]
+    #[test_case((1, 1, 2, 2), (3, 0))]
+    #[test_case((-1, -1, 3, 3), (0, 0))]
+    #[test_case((0, 0, 4, 3), (-2, 2))]
+    #[test_case((2, 0, 9, 9), (4, 3))]
+    fn copy_rect(rect: (i32, i32, i32, i32), dest: (i32, i32)) {
+        same_as_per_pixel(|img| img.copy(rect.into(), dest.into()));
+    }
+
+    #[test_case((0, 0, 4, 3), (0, 0), 255)]
+    #[test_case((1, 0, 3, 2), (2, 3), 127)]
+    #[test_case((0, 0, 4, 3), (-1, 1), 0)]
+    fn blit(rect: (i32, i32, i32, i32), dest: (i32, i32), opacity: u8) {
+        same_as_per_pixel(|img| {
+            img.fill(Rect::new(0, 0, 2, 4), Color::new(0, 0, 255, 100));
+            img.blit(rect.into(), dest.into(), opacity);
+        });
+    }
+
+    #[test]
+    fn map_pixels() {
+        same_as_per_pixel(|img| {
+            img.copy(Rect::new(0, 0, 4, 3), Point::new(1, 1));
+            img.map();
+        });
+    }
+}
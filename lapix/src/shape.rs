@@ -0,0 +1,349 @@
+//! The shapes drawn by the rectangle, ellipse, polyline, polygon and Bezier
+//! tools
+
+use crate::color::WHITE;
+use crate::{graphics, Brush, Color, Point};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::f64::consts::TAU;
+
+/// Which parts of a shape are drawn
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShapeStyle {
+    /// Only the outline, in the main color
+    #[default]
+    Outline,
+    /// Only the inside, in the fill color
+    Filled,
+    /// The inside in the fill color, surrounded by the outline
+    FilledOutline,
+}
+
+/// Options of the tools that draw shapes (rectangle, ellipse and polygon).
+/// The thickness of the outline is the size of the [`Brush`].
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShapeOptions {
+    pub style: ShapeStyle,
+    /// Color of the inside of filled shapes
+    pub fill_color: Color,
+}
+
+impl Default for ShapeOptions {
+    fn default() -> Self {
+        Self {
+            style: ShapeStyle::Outline,
+            fill_color: WHITE,
+        }
+    }
+}
+
+/// Constraints on a shape (or line) being dragged, usually set while modifier
+/// keys are held
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct ShapeConstraints {
+    /// Make the shape a square or a circle, and snap lines to clean pixel art
+    /// slopes (see [`graphics::snap_to_slope`])
+    pub square: bool,
+    /// Draw the shape around the point where dragging started, instead of
+    /// from its corner
+    pub from_center: bool,
+}
+
+impl ShapeConstraints {
+    /// The opposite corners of the shape dragged from `p0` to `p`
+    pub fn corners(self, p0: Point<i32>, p: Point<i32>) -> (Point<i32>, Point<i32>) {
+        let mut d = p - p0;
+
+        if self.square {
+            let side = d.x.abs().max(d.y.abs());
+            let signed = |v: i32| if v < 0 { -side } else { side };
+            d = Point::new(signed(d.x), signed(d.y));
+        }
+
+        if self.from_center {
+            (p0 - d, p0 + d)
+        } else {
+            (p0, p0 + d)
+        }
+    }
+
+    /// The end of the line dragged from `p0` to `p`
+    pub fn line_end(self, p0: Point<i32>, p: Point<i32>) -> Point<i32> {
+        if self.square {
+            graphics::snap_to_slope(p0, p)
+        } else {
+            p
+        }
+    }
+}
+
+/// A kind of shape, drawn inside the rectangle between two corners
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Shape {
+    Rectangle,
+    Ellipse,
+}
+
+impl Shape {
+    /// The 1px outline of the shape
+    fn outline(self, p1: Point<i32>, p2: Point<i32>) -> Vec<Point<i32>> {
+        match self {
+            Self::Rectangle => graphics::rectangle(p1, p2),
+            Self::Ellipse => graphics::ellipse(p1, p2),
+        }
+    }
+
+    /// Points along the outline of the shape, in order, in coordinates in
+    /// which the center of the pixel (0, 0) is (0.5, 0.5): the corners of a
+    /// rectangle, or enough points of an ellipse for it to look smooth when
+    /// they are connected
+    pub(crate) fn vertices(self, p1: Point<i32>, p2: Point<i32>) -> Vec<(f64, f64)> {
+        let min = p1.rect_min_corner(p2);
+        let max = min + p1.abs_diff(p2);
+        let (x0, y0) = (min.x as f64 + 0.5, min.y as f64 + 0.5);
+        let (x1, y1) = (max.x as f64 + 0.5, max.y as f64 + 0.5);
+
+        match self {
+            Self::Rectangle => vec![(x0, y0), (x1, y0), (x1, y1), (x0, y1)],
+            Self::Ellipse => {
+                let (cx, cy) = ((x0 + x1) / 2., (y0 + y1) / 2.);
+                let (rx, ry) = ((x1 - x0) / 2., (y1 - y0) / 2.);
+                let n = (TAU * rx.max(ry)).ceil().max(8.) as usize;
+
+                (0..n)
+                    .map(|i| {
+                        let (sin, cos) = (TAU * i as f64 / n as f64).sin_cos();
+                        (cx + rx * cos, cy + ry * sin)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// All the pixels of the shape, i.e. the ones between the leftmost and the
+    /// rightmost pixel of its outline in each row
+    pub(crate) fn area(self, p1: Point<i32>, p2: Point<i32>) -> Vec<Point<i32>> {
+        let mut rows = BTreeMap::new();
+        for p in self.outline(p1, p2) {
+            let (x0, x1) = rows.entry(p.y).or_insert((p.x, p.x));
+            *x0 = p.x.min(*x0);
+            *x1 = p.x.max(*x1);
+        }
+
+        rows.into_iter()
+            .flat_map(|(y, (x0, x1))| (x0..=x1).map(move |x| Point::new(x, y)))
+            .collect()
+    }
+
+    /// The outline drawn with a [`Brush`]. It is kept inside the rectangle
+    /// between the corners, so a bigger brush makes the outline thicker
+    /// towards the inside.
+    fn thick_outline(self, p1: Point<i32>, p2: Point<i32>, brush: &Brush) -> Vec<Point<i32>> {
+        let footprint = brush.footprint();
+        let low = Point::new(
+            footprint.iter().map(|p| p.x).min().unwrap_or(0),
+            footprint.iter().map(|p| p.y).min().unwrap_or(0),
+        );
+        let high = Point::new(
+            footprint.iter().map(|p| p.x).max().unwrap_or(0),
+            footprint.iter().map(|p| p.y).max().unwrap_or(0),
+        );
+
+        let min = p1.rect_min_corner(p2) - low;
+        let max = p1.rect_min_corner(p2) + p1.abs_diff(p2) - high;
+
+        // The brush is bigger than the shape, so it covers all of it
+        if min.x > max.x || min.y > max.y {
+            return self.area(p1, p2);
+        }
+
+        brush.stamp(&self.outline(min, max))
+    }
+
+    /// The pixels of the shape between two corners and their colors, according
+    /// to the [`ShapeOptions`]. The outline is drawn with a [`Brush`] in a
+    /// certain color.
+    pub fn pixels(
+        self,
+        p1: Point<i32>,
+        p2: Point<i32>,
+        brush: &Brush,
+        color: Color,
+        options: &ShapeOptions,
+    ) -> Vec<(Point<i32>, Color)> {
+        styled(
+            || self.thick_outline(p1, p2, brush),
+            || self.area(p1, p2),
+            color,
+            options,
+        )
+    }
+}
+
+/// A kind of shape drawn through a path of points, added one at a time
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PathShape {
+    /// Lines connecting the points
+    Polyline,
+    /// A polygon with the points as vertices, which can be filled
+    Polygon,
+    /// A Bezier curve with the points as control points
+    Bezier,
+}
+
+impl PathShape {
+    /// The 1px outline of the shape
+    fn outline(self, points: &[Point<i32>]) -> Vec<Point<i32>> {
+        match self {
+            Self::Polyline => graphics::polyline(points),
+            Self::Polygon => graphics::polygon(points),
+            Self::Bezier => graphics::bezier(points),
+        }
+    }
+
+    /// The pixels of the shape through some points and their colors. Its
+    /// outline is drawn with a [`Brush`] in a certain color, and only a
+    /// polygon can be filled, according to the [`ShapeOptions`].
+    pub fn pixels(
+        self,
+        points: &[Point<i32>],
+        brush: &Brush,
+        color: Color,
+        options: &ShapeOptions,
+    ) -> Vec<(Point<i32>, Color)> {
+        let outline = || brush.stamp(&self.outline(points));
+
+        match self {
+            Self::Polygon => styled(outline, || graphics::polygon_area(points), color, options),
+            _ => outline().into_iter().map(|p| (p, color)).collect(),
+        }
+    }
+}
+
+/// The pixels of a shape with a certain outline and area (which includes the
+/// outline), according to the style of the [`ShapeOptions`]
+fn styled<O, A>(
+    outline: O,
+    area: A,
+    color: Color,
+    options: &ShapeOptions,
+) -> Vec<(Point<i32>, Color)>
+where
+    O: FnOnce() -> Vec<Point<i32>>,
+    A: FnOnce() -> Vec<Point<i32>>,
+{
+    let fill = options.fill_color;
+
+    match options.style {
+        ShapeStyle::Outline => outline().into_iter().map(|p| (p, color)).collect(),
+        ShapeStyle::Filled => area().into_iter().map(|p| (p, fill)).collect(),
+        ShapeStyle::FilledOutline => {
+            let outline = outline();
+            let on_outline: HashSet<_> = outline.iter().copied().collect();
+
+            area()
+                .into_iter()
+                .filter(|p| !on_outline.contains(p))
+                .map(|p| (p, fill))
+                .chain(outline.into_iter().map(|p| (p, color)))
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::BLACK;
+    use crate::BrushShape;
+    use test_case::test_case;
+
+    fn constraints(square: bool, from_center: bool) -> ShapeConstraints {
+        ShapeConstraints {
+            square,
+            from_center,
+        }
+    }
+
+    #[test_case(constraints(false, false), (3, 1), ((0, 0), (3, 1)))]
+    #[test_case(constraints(true, false), (3, 1), ((0, 0), (3, 3)))]
+    #[test_case(constraints(true, false), (-1, 2), ((0, 0), (-2, 2)))]
+    #[test_case(constraints(false, true), (3, 1), ((-3, -1), (3, 1)))]
+    #[test_case(constraints(true, true), (0, -2), ((-2, 2), (2, -2)))]
+    fn corners(c: ShapeConstraints, p: (i32, i32), expected: ((i32, i32), (i32, i32))) {
+        let (p1, p2) = c.corners(Point::ZERO, p.into());
+
+        assert_eq!((p1, p2), (expected.0.into(), expected.1.into()));
+    }
+
+    fn count(pixels: &[(Point<i32>, Color)], color: Color) -> usize {
+        pixels.iter().filter(|(_, c)| *c == color).count()
+    }
+
+    #[test_case(ShapeStyle::Outline, 1, 16, 0)]
+    #[test_case(ShapeStyle::Outline, 2, 24, 0)]
+    #[test_case(ShapeStyle::Outline, 9, 25, 0)]
+    #[test_case(ShapeStyle::Filled, 1, 0, 25)]
+    #[test_case(ShapeStyle::FilledOutline, 1, 16, 9)]
+    #[test_case(ShapeStyle::FilledOutline, 2, 24, 1)]
+    fn rectangle(style: ShapeStyle, size: u8, outline: usize, fill: usize) {
+        let brush = Brush {
+            size,
+            ..Default::default()
+        };
+        let options = ShapeOptions {
+            style,
+            ..Default::default()
+        };
+        let pixels =
+            Shape::Rectangle.pixels(Point::new(0, 0), Point::new(4, 4), &brush, BLACK, &options);
+
+        assert_eq!(count(&pixels, BLACK), outline);
+        assert_eq!(count(&pixels, WHITE), fill);
+        assert!(pixels
+            .iter()
+            .all(|(p, _)| (0..5).contains(&p.x) && (0..5).contains(&p.y)));
+    }
+
+    #[test]
+    fn thick_ellipse_stays_inside() {
+        let brush = Brush {
+            size: 3,
+            shape: BrushShape::Circle,
+            ..Default::default()
+        };
+        let options = ShapeOptions {
+            style: ShapeStyle::FilledOutline,
+            ..Default::default()
+        };
+        let pixels = Shape::Ellipse.pixels(
+            Point::new(0, 0),
+            Point::new(16, 10),
+            &brush,
+            BLACK,
+            &options,
+        );
+
+        assert!(pixels
+            .iter()
+            .all(|(p, _)| (0..=16).contains(&p.x) && (0..=10).contains(&p.y)));
+        assert!(count(&pixels, WHITE) > 0);
+        assert!(count(&pixels, BLACK) > 0);
+    }
+
+    #[test_case(PathShape::Polyline, ShapeStyle::FilledOutline, 7, 0)]
+    #[test_case(PathShape::Polygon, ShapeStyle::Outline, 8, 0)]
+    #[test_case(PathShape::Polygon, ShapeStyle::Filled, 0, 9)]
+    #[test_case(PathShape::Polygon, ShapeStyle::FilledOutline, 8, 1)]
+    fn path(shape: PathShape, style: ShapeStyle, outline: usize, fill: usize) {
+        let points = [(0, 0), (2, 0), (2, 2), (0, 2)].map(Point::from);
+        let options = ShapeOptions {
+            style,
+            ..Default::default()
+        };
+        let pixels = shape.pixels(&points, &Brush::default(), BLACK, &options);
+
+        assert_eq!(count(&pixels, BLACK), outline);
+        assert_eq!(count(&pixels, WHITE), fill);
+    }
+}
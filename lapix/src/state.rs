@@ -1,16 +1,21 @@
+use crate::brush::Stroke;
 use crate::color::{BLACK, TRANSPARENT};
-use crate::util::{LoadProject, SaveProject};
+use crate::shape::Shape;
+use crate::storage::Storage;
+use crate::symmetry::Figure;
 use crate::{
-    util, Action, AtomicAction, Bitmap, Canvas, CanvasEffect, Color, Error, Event, FreeImage,
-    Layers, Palette, Point, Position, Rect, Result, Size, Tool,
+    graphics, project, util, Action, AtomicAction, Bitmap, Brush, BucketOptions, Canvas,
+    CanvasEffect, Color, Error, Event, FreeImage, History, HistoryLimits, Layers, Mask, Palette,
+    PathShape, Point, Position, ProjectStorage, Rect, Result, ShapeConstraints, ShapeOptions, Size,
+    Symmetry, SymmetryMode, Tool,
 };
 use serde::{Deserialize, Serialize};
 
 /// Represents a selection
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Selection {
-    /// A selected portion of the canvas, based on a rectangular area
-    Canvas(Rect<i32>),
+    /// A selected portion of the canvas, which may have any shape
+    Canvas(Mask),
     // TODO: maybe this should contain the FreeImage
     /// A selected free image that is not part of the canvas until it's
     /// *anchored*
@@ -27,72 +32,187 @@ pub struct State<IMG> {
     layers: Layers<IMG>,
     #[serde(skip)]
     events: Vec<Event>,
+    #[serde(skip)]
+    exact: bool,
+    #[serde(skip)]
+    recording: Option<Vec<Event>>,
+    /// The pixels and layers changed by the event being executed
+    #[serde(skip)]
+    changes: CanvasEffect,
+    /// The freehand stroke being drawn, if in pixel-perfect mode, and its
+    /// copies given by the [`Symmetry`]
+    #[serde(skip)]
+    strokes: Vec<Stroke>,
+    #[serde(skip)]
+    shape_constraints: ShapeConstraints,
+    /// The points of the polyline, polygon or Bezier curve being drawn
+    #[serde(skip)]
+    path: Vec<Point<i32>>,
+    /// The version of the history (see [`History::version`]) right after the
+    /// last change of opacity, so that the changes made while typing a value
+    /// are a single action
+    #[serde(skip)]
+    opacity_edit: Option<usize>,
     tool: Tool,
     main_color: Color,
+    brush: Brush,
+    bucket_options: BucketOptions,
+    shape_options: ShapeOptions,
+    symmetry: Symmetry,
+    tile_mode: bool,
     spritesheet: Size<u8>,
     palette: Palette,
     selection: Option<Selection>,
     free_image: Option<FreeImage<IMG>>,
     clipboard: Option<IMG>,
-    #[serde(skip, default = "Vec::new")]
-    reversals: Vec<Action<IMG>>,
-    #[serde(skip, default = "Option::default")]
-    cur_reversal: Option<Action<IMG>>,
-    #[serde(skip, default = "Option::default")]
-    load_project_fn: Option<LoadProject>,
-    #[serde(skip, default = "Option::default")]
-    save_project_fn: Option<SaveProject>,
+    #[serde(skip, default = "History::default")]
+    history: History<IMG>,
+    #[serde(skip, default = "Storage::default")]
+    storage: Storage,
 }
 
 impl<IMG: Bitmap + Serialize + for<'de> Deserialize<'de>> State<IMG> {
-    /// Create a new default state for the editor, with a starting canvas size
-    pub fn new(
-        size: Size<i32>,
-        load_project_fn: Option<LoadProject>,
-        save_project_fn: Option<SaveProject>,
-    ) -> Self {
+    /// Create a new default state for the editor, with a starting canvas size.
+    /// Projects are saved to and loaded from the file system, unless another
+    /// storage is set with [`set_storage`].
+    ///
+    /// [`set_storage`]: State::set_storage
+    pub fn new(size: Size<i32>) -> Self {
         Self {
             layers: Layers::new(size),
             events: Vec::new(),
+            exact: false,
+            recording: None,
+            changes: CanvasEffect::None,
+            strokes: Vec::new(),
+            shape_constraints: ShapeConstraints::default(),
+            path: Vec::new(),
+            opacity_edit: None,
             tool: Tool::Brush,
             main_color: BLACK,
+            brush: Brush::default(),
+            bucket_options: BucketOptions::default(),
+            shape_options: ShapeOptions::default(),
+            symmetry: Symmetry::centered(SymmetryMode::Off, size),
+            tile_mode: false,
             spritesheet: Size::new(1, 1),
             palette: Palette::default(),
             selection: None,
             free_image: None,
             clipboard: None,
-            reversals: Vec::new(),
-            cur_reversal: None,
-            load_project_fn,
-            save_project_fn,
+            history: History::default(),
+            storage: Storage::default(),
         }
     }
 
-    fn start_action(&mut self) {
-        self.cur_reversal = Some(Action::default());
+    /// Set where projects are saved to and loaded from
+    pub fn set_storage<S: ProjectStorage + 'static>(&mut self, storage: S) {
+        self.storage = Storage(Box::new(storage));
     }
 
-    fn add_to_action(&mut self, actions: Vec<AtomicAction<IMG>>) -> Result<()> {
-        if self.cur_reversal.is_none() {
-            self.start_action();
+    /// Execute every event exactly as it is sent. By default, an event that
+    /// repeats the previous one (or, for some events, just its variant) is
+    /// ignored, since the UI tends to send the same event on every frame. That
+    /// is not wanted when running a script of events, e.g. filling the same
+    /// point twice should create two actions in the history.
+    pub fn set_exact(&mut self, exact: bool) {
+        self.exact = exact;
+    }
+
+    /// Start recording the events that are executed, e.g. to replay them
+    /// later as a macro. Only [recordable] events are kept.
+    ///
+    /// [recordable]: Event::recordable
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    /// Stop recording events, returning the ones recorded
+    pub fn stop_recording(&mut self) -> Vec<Event> {
+        self.recording.take().unwrap_or_default()
+    }
+
+    /// Whether the events executed are being recorded
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Execute recorded events moved by an offset, exactly as they were
+    /// recorded, and group everything they do in a single action of the
+    /// history. The selected tool is kept, and undo or redo events only affect
+    /// what was done by the replay itself. If an event fails, the ones before
+    /// it are kept.
+    pub fn replay<S: Into<String>>(
+        &mut self,
+        label: S,
+        events: &[Event],
+        offset: Point<i32>,
+    ) -> Result<CanvasEffect> {
+        let start = self.history.position();
+        let limits = self.history.limits();
+        let exact = self.exact;
+        let tool = self.tool;
+
+        // The actions must not be dropped before they are merged
+        self.history.set_limits(HistoryLimits {
+            max_steps: None,
+            max_bytes: None,
+        });
+        self.exact = true;
+
+        let mut result = Ok(CanvasEffect::None);
+        let mut undone = 0;
+
+        for event in events {
+            match event {
+                Event::Undo if self.history.position() <= start => continue,
+                Event::Undo => undone += 1,
+                Event::Redo if undone == 0 => continue,
+                Event::Redo => undone -= 1,
+                _ => (),
+            }
+
+            match self.execute(event.translate(offset)) {
+                Ok(effect) => result = result.map(|fx| fx.merge(effect)),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
         }
-        self.cur_reversal
-            .as_mut()
-            .ok_or(Error::ReversalNotSet)?
-            .append(actions);
 
-        Ok(())
+        self.history.merge_since(start, label);
+        self.history.set_limits(limits);
+        self.exact = exact;
+        self.tool = tool;
+
+        result
+    }
+
+    fn start_action<S: Into<String>>(&mut self, label: S) {
+        self.history.start(label);
+    }
+
+    fn add_to_action(&mut self, actions: Vec<AtomicAction<IMG>>) -> Result<()> {
+        self.record_changes(&actions);
+        self.history.add(actions)
     }
 
     fn end_action(&mut self) {
-        if let Some(action) = self.cur_reversal.take() {
-            self.reversals.push(action);
-        }
+        self.history.end();
     }
 
-    fn single_action(&mut self, action: Action<IMG>) {
-        self.end_action();
-        self.reversals.push(action);
+    fn single_action<S: Into<String>>(&mut self, label: S, actions: Vec<AtomicAction<IMG>>) {
+        self.record_changes(&actions);
+        self.history.push(Action::new(label, actions));
+    }
+
+    /// Add what was changed by some actions (known from their reversals) to
+    /// the [`CanvasEffect`] of the event being executed
+    fn record_changes(&mut self, actions: &[AtomicAction<IMG>]) {
+        for action in actions {
+            self.changes = self.changes.merge(action.canvas_effect());
+        }
     }
 
     fn add_to_pixels_action(&mut self, actions: Vec<(Point<i32>, Color)>) -> Result<()> {
@@ -101,43 +221,145 @@ impl<IMG: Bitmap + Serialize + for<'de> Deserialize<'de>> State<IMG> {
         self.add_to_action(actions)
     }
 
-    fn single_pixels_action(&mut self, actions: Vec<(Point<i32>, Color)>) {
+    fn single_pixels_action<S: Into<String>>(
+        &mut self,
+        label: S,
+        actions: Vec<(Point<i32>, Color)>,
+    ) {
         let actions = AtomicAction::set_pixel_vec(self.layers.active_index(), actions);
-        self.single_action(actions.into());
+        self.single_action(label, actions);
+    }
+
+    /// The pixel of the canvas a point stands for: the point itself, or in
+    /// tile mode, the pixel it wraps to (see [`Canvas::wrap`])
+    fn tile(&self, p: Point<i32>) -> Point<i32> {
+        if self.tile_mode {
+            self.canvas().wrap(p)
+        } else {
+            p
+        }
+    }
+
+    /// Draw a segment of a freehand stroke (with the brush or the eraser) and
+    /// its copies given by the [`Symmetry`], removing their corners if the
+    /// brush is pixel-perfect
+    fn stroke_line(
+        &mut self,
+        p0: Point<i32>,
+        p: Point<i32>,
+        color: Color,
+    ) -> Vec<(Point<i32>, Color)> {
+        let brush = self.brush.clone();
+        let symmetry = self.symmetry;
+        let tile_mode = self.tile_mode;
+        self.strokes.resize_with(symmetry.count(), Stroke::default);
+
+        let mut reversals = Vec::new();
+        for i in 0..symmetry.count() {
+            let (p0, p) = (symmetry.image(i, p0), symmetry.image(i, p));
+            if brush.pixel_perfect {
+                let path = graphics::line(p0, p);
+                let canvas = self.layers.active_canvas_mut();
+                let stroke = &mut self.strokes[i];
+                reversals.extend(stroke.extend(canvas, &path, &brush, color, tile_mode));
+            } else if tile_mode {
+                let pixels = brush.stamp(&graphics::line(p0, p));
+                let canvas = self.canvas_mut();
+                for q in pixels {
+                    reversals.extend(canvas.set_pixel(canvas.wrap(q), color));
+                }
+            } else {
+                reversals.extend(self.canvas_mut().line(p0, p, &brush, color));
+            }
+        }
+
+        reversals
+    }
+
+    /// The pixels of a line or shape and of its copies given by the
+    /// [`Symmetry`] (wrapped around the canvas in tile mode), drawn with the
+    /// current brush, color and shape options
+    fn figure_pixels(&self, figure: &Figure) -> Vec<(Point<i32>, Color)> {
+        self.symmetry
+            .figures(figure)
+            .iter()
+            .flat_map(|f| f.pixels(&self.brush, self.main_color, &self.shape_options))
+            .map(|(p, color)| (self.tile(p), color))
+            .collect()
+    }
+
+    /// Draw a line or shape (and its copies given by the [`Symmetry`]) as a
+    /// single action
+    fn draw_figure<S: Into<String>>(&mut self, label: S, figure: &Figure) {
+        let pixels = self.figure_pixels(figure);
+        let canvas = self.canvas_mut();
+        let reversals = pixels
+            .into_iter()
+            .filter_map(|(p, color)| canvas.set_pixel(p, color))
+            .collect();
+        self.single_pixels_action(label, reversals);
+    }
+
+    /// Show a line or shape being drawn (and its copies) as a [`FreeImage`]
+    fn preview_figure(&mut self, figure: &Figure) {
+        self.free_image = Some(FreeImage::from_points(self.figure_pixels(figure)));
+    }
+
+    /// Add the current state of the [`FreeImage`] (and the [`Selection`]) to
+    /// the current action, so that it can be restored by undoing it
+    fn add_free_image_to_action(&mut self) -> Result<()> {
+        let reversal = AtomicAction::SetFreeImage(self.free_image.clone(), self.selection.clone());
+
+        self.add_to_action(vec![reversal])
+    }
+
+    fn single_free_image_action<S: Into<String>>(&mut self, label: S) {
+        let reversal = AtomicAction::SetFreeImage(self.free_image.clone(), self.selection.clone());
+        self.single_action(label, vec![reversal]);
     }
 
     /// Execute an [`Event`]. This is the main way of changing the editor's
     /// state, and probably the most central method of this library. A
     /// [`CanvasEffect`] is returned to communicate to the caller what kind of
-    /// visual updates must be made.
+    /// visual updates must be made, e.g. which area of which layer changed.
     pub fn execute(&mut self, event: Event) -> Result<CanvasEffect> {
-        if let Some(prev_event) = self.events.last() {
+        if let Some(prev_event) = self.events.last().filter(|_| !self.exact) {
             if (prev_event == &event && !event.repeatable())
                 || (event.same_variant(prev_event) && !event.type_repeatable())
             {
                 return Ok(CanvasEffect::None);
             }
         }
+        self.changes = CanvasEffect::None;
 
         dbg!(&event);
         let t0 = std::time::SystemTime::now();
 
+        // The preview of a path is not part of the image, and it is updated
+        // after every event
+        if !self.path.is_empty() {
+            self.free_image = None;
+            if !event.keeps_path() {
+                self.path.clear();
+            }
+        }
+
         if event.triggers_anchoring() {
             self.anchor()?;
         }
 
         let mut skip_event = false;
+        // What is recorded for a macro, which may differ from the event itself
+        let mut recorded = event.clone();
 
         match event.clone() {
             Event::ClearCanvas => {
                 let img = self.canvas_mut().clear();
                 let reversal = AtomicAction::SetLayerCanvas(self.layers.active_index(), img);
-                self.start_action();
-                self.add_to_action(vec![reversal])?;
-                self.end_action();
+                self.single_action("Clear canvas", vec![reversal]);
             }
             Event::ResizeCanvas(size) => {
-                self.start_action();
+                self.start_action("Resize canvas");
                 let imgs = self.resize_canvas(size);
                 self.add_to_action(
                     imgs.into_iter()
@@ -148,7 +370,14 @@ impl<IMG: Bitmap + Serialize + for<'de> Deserialize<'de>> State<IMG> {
                 self.end_action();
             }
             Event::LineStart(_) | Event::RectStart(_) | Event::EllipseStart(_) => (),
-            Event::BrushStart | Event::EraseStart => self.start_action(),
+            Event::BrushStart => {
+                self.strokes.clear();
+                self.start_action("Brush stroke");
+            }
+            Event::EraseStart => {
+                self.strokes.clear();
+                self.start_action("Eraser stroke");
+            }
             Event::BrushEnd | Event::EraseEnd => self.end_action(),
             Event::LineEnd(p) => {
                 let last_event = self.events.last();
@@ -156,9 +385,9 @@ impl<IMG: Bitmap + Serialize + for<'de> Deserialize<'de>> State<IMG> {
                     Some(Event::LineStart(p0)) => *p0,
                     _ => return Err(Error::DrawingNotStarted),
                 };
-                let color = self.main_color;
-                let reversals = self.canvas_mut().line(p0, p, color);
-                self.single_pixels_action(reversals);
+                let p = self.shape_constraints.line_end(p0, p);
+                self.record_corners(p0, p, &mut recorded);
+                self.draw_figure("Line", &Figure::Path(PathShape::Polyline, vec![p0, p]));
                 self.free_image = None;
             }
             Event::RectEnd(p) => {
@@ -167,9 +396,8 @@ impl<IMG: Bitmap + Serialize + for<'de> Deserialize<'de>> State<IMG> {
                     Some(Event::RectStart(p0)) => *p0,
                     _ => return Err(Error::DrawingNotStarted),
                 };
-                let color = self.main_color;
-                let reversals = self.canvas_mut().rectangle(p0, p, color);
-                self.single_pixels_action(reversals);
+                let (p1, p2) = self.shape_corners(p0, p, &mut recorded);
+                self.draw_figure("Rectangle", &Figure::Shape(Shape::Rectangle, p1, p2));
                 self.free_image = None;
             }
             Event::EllipseEnd(p) => {
@@ -178,79 +406,123 @@ impl<IMG: Bitmap + Serialize + for<'de> Deserialize<'de>> State<IMG> {
                     Some(Event::EllipseStart(p0)) => *p0,
                     _ => return Err(Error::DrawingNotStarted),
                 };
-                let color = self.main_color;
-                let reversals = self.canvas_mut().ellipse(p0, p, color);
-                self.single_pixels_action(reversals);
+                let (p1, p2) = self.shape_corners(p0, p, &mut recorded);
+                self.draw_figure("Ellipse", &Figure::Shape(Shape::Ellipse, p1, p2));
                 self.free_image = None;
             }
+            Event::PathPoint(p) => match (self.tool, self.path.as_slice()) {
+                // The ends of a Bezier curve are set, so only its control
+                // points can be moved
+                (Tool::Bezier, [_, _, ..]) => skip_event = true,
+                (Tool::Bezier, [p0]) => {
+                    let p0 = *p0;
+                    let third =
+                        |k: i32| p0 + Point::new(k * (p.x - p0.x) / 3, k * (p.y - p0.y) / 3);
+                    self.path = vec![p0, third(1), third(2), p];
+                }
+                (_, [.., last]) if *last == p => skip_event = true,
+                _ => self.path.push(p),
+            },
+            Event::MovePathPoint(i, p) => match self.path.get_mut(i) {
+                Some(point) => *point = p,
+                None => skip_event = true,
+            },
+            Event::PathEnd => {
+                let path = std::mem::take(&mut self.path);
+                match self.tool.path_shape() {
+                    _ if path.is_empty() => skip_event = true,
+                    _ if matches!(self.tool, Tool::Lasso | Tool::PolygonLasso) => {
+                        self.select(Mask::from_pixels(graphics::polygon_area(&path)))?;
+                    }
+                    Some(shape) => {
+                        let label = match shape {
+                            PathShape::Polyline => "Polyline",
+                            PathShape::Polygon => "Polygon",
+                            PathShape::Bezier => "Bezier curve",
+                        };
+                        self.draw_figure(label, &Figure::Path(shape, path));
+                    }
+                    _ => skip_event = true,
+                }
+            }
             Event::BrushStroke(p) => {
                 let last_event = self.events.last();
 
-                let reversals = match last_event {
-                    Some(Event::BrushStroke(p0)) => {
-                        let color = self.main_color;
-                        let p0 = *p0;
-                        self.canvas_mut().line(p0, p, color)
-                    }
-                    Some(Event::BrushStart) => {
-                        let color = self.main_color;
-                        self.canvas_mut().set_pixel(p, color).into_iter().collect()
-                    }
-                    _ => Vec::new(),
+                let p0 = match last_event {
+                    Some(Event::BrushStroke(p0)) => Some(*p0),
+                    Some(Event::BrushStart) => Some(p),
+                    _ => None,
+                };
+                let reversals = match p0 {
+                    Some(p0) => self.stroke_line(p0, p, self.main_color),
+                    None => Vec::new(),
                 };
                 self.add_to_pixels_action(reversals)?;
             }
             Event::Erase(p) => {
                 let last_event = self.events.last();
 
-                let reversals = match last_event {
-                    Some(Event::Erase(p0)) => {
-                        let p0 = *p0;
-                        self.canvas_mut().line(p0, p, TRANSPARENT)
-                    }
-                    Some(Event::EraseStart) => self
-                        .canvas_mut()
-                        .set_pixel(p, TRANSPARENT)
-                        .into_iter()
-                        .collect(),
-                    _ => Vec::new(),
+                let p0 = match last_event {
+                    Some(Event::Erase(p0)) => Some(*p0),
+                    Some(Event::EraseStart) => Some(p),
+                    _ => None,
+                };
+                let reversals = match p0 {
+                    Some(p0) => self.stroke_line(p0, p, TRANSPARENT),
+                    None => Vec::new(),
                 };
                 self.add_to_pixels_action(reversals)?;
             }
             Event::SetTool(tool) => self.tool = tool,
             Event::SetMainColor(color) => self.main_color = color,
+            Event::SetBrush(brush) => self.brush = brush,
+            Event::SetBucketOptions(options) => self.bucket_options = options,
+            Event::SetShapeOptions(options) => self.shape_options = options,
+            Event::SetSymmetry(symmetry) => self.symmetry = symmetry,
+            Event::SetTileMode(tile_mode) => self.tile_mode = tile_mode,
             Event::Save(path) => self.save_image(path.to_string_lossy().as_ref())?,
-            Event::OpenFile(path) => self.import_image(path.to_string_lossy().as_ref())?,
+            Event::OpenFile(path) => {
+                self.start_action("Import image");
+                let result = self.import_image(path.to_string_lossy().as_ref());
+                self.end_action();
+                result?;
+            }
             Event::SaveProject(path) => {
-                if let Some(f) = &self.save_project_fn {
-                    let bytes = bincode::serialize(&self)?;
-                    (f.0)(path, bytes);
-                } else {
-                    eprintln!("Bug: Missing save project function");
-                }
+                let bytes = self.project_bytes()?;
+                self.storage.0.save(&path, bytes)?;
             }
             Event::LoadProject(path) => {
-                if let Some(f) = &self.load_project_fn {
-                    let bytes = (f.0)(path);
-                    let (save_fn, load_fn) =
-                        (self.save_project_fn.take(), self.load_project_fn.take());
-                    *self = bincode::deserialize(&bytes)?;
-                    self.save_project_fn = save_fn;
-                    self.load_project_fn = load_fn;
-                } else {
-                    eprintln!("Bug: Missing load project function");
-                }
+                let bytes = self.storage.0.load(&path)?;
+                self.load_project_bytes(&bytes)?;
             }
             Event::LoadPalette(path) => {
-                self.palette = Palette::from_file(path.to_string_lossy().as_ref())?
+                let palette = Palette::from_file(path.to_string_lossy().as_ref())?;
+                self.update_palette("Load palette", |p| *p = palette);
+            }
+            Event::AddToPalette(color) => {
+                self.update_palette("Add color to palette", |p| p.add_color(color))
+            }
+            Event::RemoveFromPalette(color) => {
+                self.update_palette("Remove color from palette", |p| p.remove_color(color))
             }
-            Event::AddToPalette(color) => self.palette.add_color(color),
-            Event::RemoveFromPalette(color) => self.palette.remove_color(color),
             Event::Bucket(p) => {
+                let p = self.tile(p);
                 if self.canvas().is_in_bounds(p) {
                     let color = self.main_color;
-                    let reversals = self.canvas_mut().bucket(p, color);
-                    self.single_pixels_action(reversals);
+                    let options = self.bucket_options;
+                    let merged = options.sample_merged.then(|| self.layers.blended());
+                    let wrap = self.tile_mode;
+
+                    let points: Vec<_> = self
+                        .symmetry
+                        .images(p)
+                        .into_iter()
+                        .map(|p| self.tile(p))
+                        .filter(|p| self.canvas().is_in_bounds(*p))
+                        .collect();
+                    let canvas = self.canvas_mut();
+                    let reversals = canvas.bucket(&points, color, options, merged.as_ref(), wrap);
+                    self.single_pixels_action("Bucket fill", reversals);
                 }
             }
             Event::ClearSelection => (),
@@ -259,16 +531,30 @@ impl<IMG: Bitmap + Serialize + for<'de> Deserialize<'de>> State<IMG> {
                 let last_event = self.events.last();
 
                 if let Some(Event::StartSelection(p0)) = last_event {
-                    let size = p.abs_diff(*p0);
-                    let corner = p.rect_min_corner(*p0);
-                    let rect = Rect::new(corner.x, corner.y, size.x + 1, size.y + 1);
-                    let r = rect.clip_to(self.canvas().rect());
-                    self.set_selection(Some(Selection::Canvas(r)))?;
+                    let mask = match self.tool {
+                        Tool::EllipseSelection => Mask::from_pixels(Shape::Ellipse.area(*p0, p)),
+                        _ => {
+                            let size = p.abs_diff(*p0);
+                            let corner = p.rect_min_corner(*p0);
+                            Mask::from_rect(Rect::new(corner.x, corner.y, size.x + 1, size.y + 1))
+                        }
+                    };
+                    self.select(mask)?;
                 }
             }
-            Event::Copy => match self.selection {
-                Some(Selection::Canvas(rect)) => {
-                    self.clipboard = Some(self.canvas().img_from_area(rect))
+            Event::MagicWand(p) => {
+                let p = self.tile(p);
+                if self.canvas().is_in_bounds(p) {
+                    let options = self.bucket_options;
+                    let merged = options.sample_merged.then(|| self.layers.blended());
+                    let canvas = self.canvas();
+                    let mask = canvas.fill_area(p, options, merged.as_ref(), self.tile_mode);
+                    self.select(mask)?;
+                }
+            }
+            Event::Copy => match &self.selection {
+                Some(Selection::Canvas(mask)) => {
+                    self.clipboard = Some(self.canvas().img_from_mask(mask))
                 }
                 Some(Selection::FreeImage) => {
                     self.clipboard = Some(
@@ -281,22 +567,26 @@ impl<IMG: Bitmap + Serialize + for<'de> Deserialize<'de>> State<IMG> {
                 }
                 None => (),
             },
-            Event::DeleteSelection => match self.selection {
-                Some(Selection::Canvas(rect)) => {
-                    let reversals = self.canvas_mut().set_area(rect, TRANSPARENT);
-                    self.single_pixels_action(reversals);
+            Event::DeleteSelection => match self.selection.clone() {
+                Some(Selection::Canvas(mask)) => {
+                    let reversals = self.canvas_mut().set_mask(&mask, TRANSPARENT);
+                    self.single_pixels_action("Delete selection", reversals);
                 }
                 Some(Selection::FreeImage) => {
+                    self.single_free_image_action("Delete selection");
                     self.free_image = None;
                     self.set_selection(None)?;
                 }
                 _ => (),
             },
-            Event::MoveStart(p) => match self.selection {
+            Event::MoveStart(p) => match &self.selection {
                 Some(Selection::Canvas(_)) => {
-                    self.free_image_from_selection(Some(p));
+                    self.start_action("Move selection");
+                    self.free_image_from_selection(Some(p))?;
                 }
                 Some(Selection::FreeImage) => {
+                    self.start_action("Move selection");
+                    self.add_free_image_to_action()?;
                     if let Some(free_image) = self.free_image.as_mut() {
                         free_image.pivot = Some(p - free_image.rect.pos());
                     }
@@ -308,81 +598,138 @@ impl<IMG: Bitmap + Serialize + for<'de> Deserialize<'de>> State<IMG> {
 
                 if let Some(Event::MoveStart(_)) = last_event {
                     self.move_free_image(p)?;
+                    self.end_action();
                 } else {
                     skip_event = true;
                 }
             }
             Event::Paste(p) => {
                 if let Some(img) = self.clipboard.as_ref().cloned() {
+                    self.single_free_image_action("Paste");
                     let img = FreeImage::new(p, img);
                     self.free_image = Some(img);
                     self.set_selection(Some(Selection::FreeImage))?;
                 }
             }
             Event::FlipHorizontal => {
-                if let Some(Selection::Canvas(_)) = self.selection {
-                    self.free_image_from_selection(None);
-                }
-                if let Some(free_img) = self.free_image.as_mut() {
-                    free_img.flip_horizontally();
-                }
+                self.edit_free_image("Flip horizontally", |img| img.flip_horizontally())?
             }
             Event::FlipVertical => {
-                if let Some(Selection::Canvas(_)) = self.selection {
-                    self.free_image_from_selection(None);
-                }
-                if let Some(free_img) = self.free_image.as_mut() {
-                    free_img.flip_vertically();
-                }
+                self.edit_free_image("Flip vertically", |img| img.flip_vertically())?
             }
             Event::ApplyTransform(t) => {
-                if let Some(Selection::Canvas(_)) = self.selection {
-                    self.free_image_from_selection(None);
-                }
-
                 let palette = self.palette().to_vec();
-                if let Some(free_img) = self.free_image.as_mut() {
-                    t.apply(&mut free_img.texture, palette);
-                }
+                let label = format!("Transform: {:?}", t);
+                self.edit_free_image(label, |img| t.apply(&mut img.texture, palette))?;
             }
             Event::NewLayerAbove => {
                 self.layers.add_new_above();
-                self.end_action();
-                self.cur_reversal = Some(Action::default());
                 let i = self.layers.count() - 1;
-                self.cur_reversal
-                    .as_mut()
-                    .ok_or(Error::ReversalNotSet)?
-                    .push(AtomicAction::DestroyLayer(i));
-                self.end_action();
+                let label = format!("New layer {}", i + 1);
+                self.single_action(label, vec![AtomicAction::DestroyLayer(i)]);
+            }
+            Event::NewLayerBelow => {
+                let i = self.layers.active_index();
+                self.layers.add_new_below();
+                let label = format!("New layer {}", i + 1);
+                self.single_action(label, vec![AtomicAction::DestroyLayer(i)]);
+            }
+            Event::SwitchLayer(i) => {
+                self.check_layer(i)?;
+                self.layers.switch_to(i);
+            }
+            Event::ChangeLayerVisibility(i, visible) => {
+                self.check_layer(i)?;
+                let old = self.layers.get(i).visible();
+                if old != visible {
+                    self.layers.set_visibility(i, visible);
+                    let verb = if visible { "Show" } else { "Hide" };
+                    let label = format!("{} layer {}", verb, i + 1);
+                    self.single_action(label, vec![AtomicAction::SetLayerVisibility(i, old)]);
+                }
+            }
+            Event::ChangeLayerOpacity(i, alpha) => {
+                self.check_layer(i)?;
+                let old = self.layers.get(i).opacity();
+                if old != alpha {
+                    let last = self.events.last();
+                    let same_layer =
+                        matches!(last, Some(Event::ChangeLayerOpacity(j, _)) if *j == i);
+                    let continued = same_layer && self.opacity_edit == Some(self.history.version());
+
+                    self.layers.set_opacity(i, alpha);
+                    let reversal = vec![AtomicAction::SetLayerOpacity(i, old)];
+                    if continued {
+                        self.record_changes(&reversal);
+                        self.history.extend_last(reversal)?;
+                    } else {
+                        let label = format!("Change opacity of layer {}", i + 1);
+                        self.single_action(label, reversal);
+                    }
+                    self.opacity_edit = Some(self.history.version());
+                }
             }
-            Event::NewLayerBelow => todo!(),
-            Event::SwitchLayer(i) => self.layers.switch_to(i),
-            Event::ChangeLayerVisibility(i, visible) => self.layers.set_visibility(i, visible),
-            Event::ChangeLayerOpacity(i, alpha) => self.layers.set_opacity(i, alpha),
-            // TODO: this should not only remove it, as we need to be able to
-            // undo this
             Event::DeleteLayer(i) => {
-                let img = self.layers.delete(i);
-                self.end_action();
-                self.cur_reversal = Some(Action::default());
-                self.cur_reversal
-                    .as_mut()
-                    .ok_or(Error::ReversalNotSet)?
-                    .push(AtomicAction::CreateLayer(i, img));
-                self.end_action();
+                self.check_layer(i)?;
+                if self.layers.count() == 1 {
+                    return Err(Error::CannotDeleteOnlyLayer);
+                }
+                let layer = self.layers.delete(i);
+                let label = format!("Delete layer {}", i + 1);
+                self.single_action(label, vec![AtomicAction::CreateLayer(i, layer)]);
+            }
+            Event::MoveLayerDown(i) => {
+                self.check_layer(i)?;
+                if i == 0 {
+                    return Err(Error::CannotMoveLayer(i));
+                }
+                self.layers.swap(i, i - 1);
+                let label = format!("Move layer {} down", i + 1);
+                self.single_action(label, vec![AtomicAction::SwapLayers(i, i - 1)]);
+            }
+            Event::MoveLayerUp(i) => {
+                self.check_layer(i)?;
+                if i + 1 == self.layers.count() {
+                    return Err(Error::CannotMoveLayer(i));
+                }
+                self.layers.swap(i, i + 1);
+                let label = format!("Move layer {} up", i + 1);
+                self.single_action(label, vec![AtomicAction::SwapLayers(i, i + 1)]);
+            }
+            Event::SetSpritesheet(size) => {
+                let old = self.spritesheet;
+                self.set_spritesheet(size)?;
+                if old != self.spritesheet {
+                    let label = "Change spritesheet";
+                    self.single_action(label, vec![AtomicAction::SetSpritesheet(old)]);
+                }
             }
-            Event::MoveLayerDown(i) => self.layers.swap(i, i - 1),
-            Event::MoveLayerUp(i) => self.layers.swap(i, i + 1),
-            Event::SetSpritesheet(size) => self.set_spritesheet(size),
             Event::Undo => {
                 // TODO: we should add UNDO to the events list
                 #[allow(unused_must_use)]
                 {
                     dbg!(t0.elapsed());
                 }
+                self.record(recorded);
                 return Ok(self.undo());
             }
+            Event::Redo => {
+                self.record(recorded);
+                return Ok(self.redo());
+            }
+            Event::GoToHistory(position) => {
+                let mut effect = CanvasEffect::None;
+
+                while self.history.position() > position && self.history.can_undo() {
+                    effect = effect.merge(self.undo());
+                }
+                while self.history.position() < position && self.history.can_redo() {
+                    effect = effect.merge(self.redo());
+                }
+
+                return Ok(effect);
+            }
+            Event::SetHistoryLimits(limits) => self.history.set_limits(limits),
         }
 
         if event.clears_selection() {
@@ -394,17 +741,29 @@ impl<IMG: Bitmap + Serialize + for<'de> Deserialize<'de>> State<IMG> {
             dbg!(t0.elapsed());
         }
 
+        let changes = std::mem::take(&mut self.changes);
+
         if skip_event {
             println!("Event skipped");
-            Ok(CanvasEffect::None)
+            Ok(changes)
         } else {
-            let effect = event.canvas_effect();
+            let effect = event.canvas_effect().merge(changes);
+            self.record(recorded);
             self.events.push(event);
 
             Ok(effect)
         }
     }
 
+    /// Add an event to the macro being recorded, if it can be recorded
+    fn record(&mut self, event: Event) {
+        if let Some(recording) = &mut self.recording {
+            if event.recordable() {
+                recording.push(event);
+            }
+        }
+    }
+
     fn resize_canvas(&mut self, size: Size<i32>) -> Vec<IMG> {
         self.layers.resize_all(size)
     }
@@ -428,6 +787,11 @@ impl<IMG: Bitmap + Serialize + for<'de> Deserialize<'de>> State<IMG> {
         &self.layers
     }
 
+    /// Get the [`History`] of undoable actions
+    pub fn history(&self) -> &History<IMG> {
+        &self.history
+    }
+
     /// Get the currently selected [`Tool`]
     pub fn selected_tool(&self) -> Tool {
         self.tool
@@ -439,6 +803,45 @@ impl<IMG: Bitmap + Serialize + for<'de> Deserialize<'de>> State<IMG> {
         self.main_color
     }
 
+    /// Get the [`Brush`] used by the drawing tools
+    pub fn brush(&self) -> &Brush {
+        &self.brush
+    }
+
+    /// Get the options of the bucket tool
+    pub fn bucket_options(&self) -> BucketOptions {
+        self.bucket_options
+    }
+
+    /// Get the options of the tools that draw shapes
+    pub fn shape_options(&self) -> ShapeOptions {
+        self.shape_options
+    }
+
+    /// Set the constraints on the shape being drawn (e.g. whether it is a
+    /// square). These usually follow the modifier keys, which can change while
+    /// the shape is dragged, so they are not set by an [`Event`].
+    pub fn set_shape_constraints(&mut self, constraints: ShapeConstraints) {
+        self.shape_constraints = constraints;
+    }
+
+    /// Get the [`Symmetry`] setting of the tools that draw
+    pub fn symmetry(&self) -> Symmetry {
+        self.symmetry
+    }
+
+    /// Whether tile mode is on (see [`Event::SetTileMode`])
+    pub fn tile_mode(&self) -> bool {
+        self.tile_mode
+    }
+
+    /// Get the points of the polyline, polygon or Bezier curve being drawn
+    /// (empty if there is none). The points of a Bezier curve are its control
+    /// points.
+    pub fn path(&self) -> &[Point<i32>] {
+        &self.path
+    }
+
     /// Get the spritesheet dimensions (number of horizontal and vertical
     /// frames). For a static image (not an animation) it will be `(1, 1)`.
     pub fn spritesheet(&self) -> Size<u8> {
@@ -447,15 +850,32 @@ impl<IMG: Bitmap + Serialize + for<'de> Deserialize<'de>> State<IMG> {
 
     /// Set the spritesheet dimensions (number of horizontal and vertical
     /// frames). For a static image (not an animation) it will be `(1, 1)`.
-    fn set_spritesheet(&mut self, size: Size<u8>) {
+    fn set_spritesheet(&mut self, size: Size<u8>) -> Result<()> {
+        if size.x == 0 || size.y == 0 {
+            return Err(Error::InvalidSpritesheet(
+                "there must be at least one frame".to_owned(),
+            ));
+        }
+        // TODO: relax this requirement
         if self.canvas().width() % size.x as i32 != 0 || self.canvas().height() % size.y as i32 != 0
         {
-            // TODO: relax this requirement
-            eprintln!("WARN: Canvas size should be a multiple of the spritesheet size");
-            return;
+            return Err(Error::InvalidSpritesheet(
+                "canvas size should be a multiple of the spritesheet size".to_owned(),
+            ));
         }
 
         self.spritesheet = size;
+
+        Ok(())
+    }
+
+    /// Check that there is a layer at an index
+    fn check_layer(&self, i: usize) -> Result<()> {
+        if i < self.layers.count() {
+            Ok(())
+        } else {
+            Err(Error::InvalidLayer(i))
+        }
     }
 
     /// Get the colors of the palette
@@ -464,8 +884,18 @@ impl<IMG: Bitmap + Serialize + for<'de> Deserialize<'de>> State<IMG> {
     }
 
     /// Get the [`Selection`]
-    pub fn selection(&self) -> Option<Selection> {
-        self.selection
+    pub fn selection(&self) -> Option<&Selection> {
+        self.selection.as_ref()
+    }
+
+    /// Get the image of what is selected: the selected pixels of the canvas
+    /// (with the other pixels of their bounding box transparent), or the
+    /// [`FreeImage`]
+    pub fn selection_image(&self) -> Option<IMG> {
+        match self.selection.as_ref()? {
+            Selection::Canvas(mask) => Some(self.canvas().img_from_mask(mask)),
+            Selection::FreeImage => self.free_image.as_ref().map(|img| img.texture.clone()),
+        }
     }
 
     /// Get the [`FreeImage`]
@@ -478,6 +908,15 @@ impl<IMG: Bitmap + Serialize + for<'de> Deserialize<'de>> State<IMG> {
         self.set_selection(None)
     }
 
+    /// Select the pixels of a [`Mask`] that are in the canvas, or clear the
+    /// selection if there are none
+    fn select(&mut self, mask: Mask) -> Result<()> {
+        let mask = mask.clip_to(self.canvas().rect());
+        let selection = (!mask.is_empty()).then_some(Selection::Canvas(mask));
+
+        self.set_selection(selection)
+    }
+
     /// Set the [`Selection`]
     fn set_selection(&mut self, selection: Option<Selection>) -> Result<()> {
         match selection {
@@ -498,21 +937,81 @@ impl<IMG: Bitmap + Serialize + for<'de> Deserialize<'de>> State<IMG> {
     fn anchor(&mut self) -> Result<()> {
         if let Some(free_image) = self.free_image.take() {
             println!("Anchoring");
+            let mask = free_image.mask.moved_to(free_image.rect.pos());
             let reversals = self.canvas_mut().paste_obj(&free_image);
-            self.single_pixels_action(reversals);
-            self.set_selection(Some(Selection::Canvas(
-                free_image.rect.clip_to(self.canvas().rect()),
-            )))?;
+            let mut actions = AtomicAction::set_pixel_vec(self.layers.active_index(), reversals);
+            let selection = self.selection.clone();
+            actions.push(AtomicAction::SetFreeImage(Some(free_image), selection));
+            self.single_action("Anchor selection", actions);
+            self.select(mask)?;
         }
 
         Ok(())
     }
 
+    /// Encode the project (layers, palette, etc.) in the project file format
+    pub fn project_bytes(&self) -> Result<Vec<u8>> {
+        project::encode(
+            &self.layers,
+            &self.palette,
+            self.spritesheet,
+            self.main_color,
+        )
+    }
+
+    /// Replace the project (layers, palette, etc.) with one in the project
+    /// file format. Files written by older versions of the format are
+    /// accepted as well. If the file cannot be read, the state is left
+    /// untouched.
+    pub fn load_project_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        let project = project::decode(bytes)?;
+
+        self.layers = project.layers;
+        self.palette = project.palette;
+        self.spritesheet = project.spritesheet;
+        self.main_color = project.main_color;
+        self.selection = None;
+        self.free_image = None;
+        self.history.clear();
+
+        Ok(())
+    }
+
+    /// Replace the project with a single layer holding an image read from the
+    /// bytes of an image file (e.g. PNG). The canvas takes the size of the
+    /// image. If the image cannot be read, the state is left untouched.
+    pub fn load_image_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        let img = IMG::try_from_file_bytes(bytes.to_vec())?;
+        let mut layers = Layers::new(img.size());
+        layers.canvas_at_mut(0).set_img(img);
+
+        self.layers = layers;
+        self.selection = None;
+        self.free_image = None;
+        self.history.clear();
+
+        Ok(())
+    }
+
     /// Undo the last undoable action. Returns the [`CanvasEffect`] to signal to
     /// the caller what needs to be updated visually
     fn undo(&mut self) -> CanvasEffect {
-        if let Some(action) = self.reversals.pop() {
-            return action.apply(&mut self.layers);
+        if let Some(action) = self.history.pop_undo() {
+            let (effect, redo) = action.apply(self);
+            self.history.push_redo(redo);
+            return effect;
+        }
+
+        CanvasEffect::None
+    }
+
+    /// Redo the last undone action. Returns the [`CanvasEffect`] to signal to
+    /// the caller what needs to be updated visually
+    fn redo(&mut self) -> CanvasEffect {
+        if let Some(action) = self.history.pop_redo() {
+            let (effect, reversal) = action.apply(self);
+            self.history.push_undo(reversal);
+            return effect;
         }
 
         CanvasEffect::None
@@ -525,6 +1024,7 @@ impl<IMG: Bitmap + Serialize + for<'de> Deserialize<'de>> State<IMG> {
     /// whenever the mouse moves, in order to update this preview image.
     pub fn update_free_image(&mut self, mouse_canvas: Position<i32>) -> Result<()> {
         match self.events.last() {
+            _ if !self.path.is_empty() => self.update_path_preview(mouse_canvas),
             Some(Event::MoveStart(_)) => self.move_free_image(mouse_canvas)?,
             Some(Event::LineStart(p)) => self.update_line_preview(*p, mouse_canvas),
             Some(Event::RectStart(p)) => self.update_rect_preview(*p, mouse_canvas),
@@ -544,29 +1044,115 @@ impl<IMG: Bitmap + Serialize + for<'de> Deserialize<'de>> State<IMG> {
         Ok(())
     }
 
-    fn free_image_from_selection(&mut self, mouse_pos: Option<Point<i32>>) {
-        if let Some(Selection::Canvas(rect)) = self.selection {
-            self.free_image = Some(FreeImage::from_canvas_area(
+    /// Turn the selected area of the canvas into a [`FreeImage`]. The changes
+    /// are added to the current action.
+    fn free_image_from_selection(&mut self, mouse_pos: Option<Point<i32>>) -> Result<()> {
+        if let Some(Selection::Canvas(mask)) = self.selection.clone() {
+            self.add_free_image_to_action()?;
+            self.free_image = Some(FreeImage::from_canvas_mask(
                 self.canvas(),
-                rect,
-                mouse_pos.map(|p| p - rect.pos()),
+                &mask,
+                mouse_pos.map(|p| p - mask.bounds().pos()),
             ));
-            let reversals = self.canvas_mut().set_area(rect, TRANSPARENT);
-            self.single_pixels_action(reversals);
+            let reversals = self.canvas_mut().set_mask(&mask, TRANSPARENT);
+            self.add_to_pixels_action(reversals)?;
             self.selection = Some(Selection::FreeImage);
         }
+
+        Ok(())
+    }
+
+    /// Modify the [`FreeImage`] (creating one from the selection, if needed) as
+    /// a single undoable action
+    fn edit_free_image<S, F>(&mut self, label: S, f: F) -> Result<()>
+    where
+        S: Into<String>,
+        F: FnOnce(&mut FreeImage<IMG>),
+    {
+        self.start_action(label);
+        self.free_image_from_selection(None)?;
+
+        if self.free_image.is_some() {
+            self.add_free_image_to_action()?;
+        }
+        if let Some(free_img) = self.free_image.as_mut() {
+            f(free_img);
+        }
+        self.end_action();
+
+        Ok(())
+    }
+
+    fn update_palette<S: Into<String>, F: FnOnce(&mut Palette)>(&mut self, label: S, f: F) {
+        let old = self.palette.clone();
+        f(&mut self.palette);
+
+        if old != self.palette {
+            self.single_action(label, vec![AtomicAction::SetPalette(old)]);
+        }
     }
 
     fn update_line_preview(&mut self, p0: Point<i32>, p: Point<i32>) {
-        self.free_image = Some(FreeImage::line_preview(p0, p, self.main_color()));
+        let p = self.shape_constraints.line_end(p0, p);
+        self.preview_figure(&Figure::Path(PathShape::Polyline, vec![p0, p]));
     }
 
     fn update_rect_preview(&mut self, p0: Point<i32>, p: Point<i32>) {
-        self.free_image = Some(FreeImage::rect_preview(p0, p, self.main_color()));
+        let (p1, p2) = self.shape_constraints.corners(p0, p);
+        self.preview_figure(&Figure::Shape(Shape::Rectangle, p1, p2));
     }
 
     fn update_ellipse_preview(&mut self, p0: Point<i32>, p: Point<i32>) {
-        self.free_image = Some(FreeImage::ellipse_preview(p0, p, self.main_color()));
+        let (p1, p2) = self.shape_constraints.corners(p0, p);
+        self.preview_figure(&Figure::Shape(Shape::Ellipse, p1, p2));
+    }
+
+    /// Preview the path being drawn, as if the mouse was its next point. A
+    /// Bezier curve follows the mouse only until its ends are set.
+    fn update_path_preview(&mut self, p: Point<i32>) {
+        let Some(shape) = self.tool.path_shape() else {
+            return;
+        };
+        let mut points = self.path.clone();
+        match shape {
+            PathShape::Bezier if points.len() > 1 => (),
+            _ if points.last() == Some(&p) => (),
+            _ => points.push(p),
+        }
+
+        self.preview_figure(&Figure::Path(shape, points));
+    }
+
+    /// The corners of the shape dragged from `p0` to `p`, given the
+    /// [`ShapeConstraints`] (see [`record_corners`])
+    ///
+    /// [`record_corners`]: State::record_corners
+    fn shape_corners(
+        &mut self,
+        p0: Point<i32>,
+        p: Point<i32>,
+        recorded: &mut Event,
+    ) -> (Point<i32>, Point<i32>) {
+        let (p1, p2) = self.shape_constraints.corners(p0, p);
+        self.record_corners(p1, p2, recorded);
+
+        (p1, p2)
+    }
+
+    /// Record the start and end of a line or shape with the points where it
+    /// was actually drawn, which differ from the ones of the events when there
+    /// are [`ShapeConstraints`]. This way, a macro draws the same thing
+    /// without the constraints.
+    fn record_corners(&mut self, p1: Point<i32>, p2: Point<i32>, recorded: &mut Event) {
+        if let Some(
+            Event::LineStart(start) | Event::RectStart(start) | Event::EllipseStart(start),
+        ) = self.recording.as_mut().and_then(|events| events.last_mut())
+        {
+            *start = p1;
+        }
+        if let Event::LineEnd(end) | Event::RectEnd(end) | Event::EllipseEnd(end) = recorded {
+            *end = p2;
+        }
     }
 
     fn save_image(&self, path: &str) -> Result<()> {
@@ -581,9 +1167,16 @@ impl<IMG: Bitmap + Serialize + for<'de> Deserialize<'de>> State<IMG> {
         if img.width() as i32 > self.canvas().width()
             || img.height() as i32 > self.canvas().height()
         {
-            self.resize_canvas((img.width() as i32, img.height() as i32).into());
+            let imgs = self.resize_canvas((img.width() as i32, img.height() as i32).into());
+            self.add_to_action(
+                imgs.into_iter()
+                    .enumerate()
+                    .map(|(i, img)| AtomicAction::SetLayerCanvas(i, img))
+                    .collect(),
+            )?;
         }
 
+        self.add_free_image_to_action()?;
         let img: IMG = util::img_from_raw(img);
         let img = FreeImage::new(Point::ZERO, img);
         self.free_image = Some(img);
@@ -592,3 +1185,28 @@ impl<IMG: Bitmap + Serialize + for<'de> Deserialize<'de>> State<IMG> {
         Ok(())
     }
 }
+
+impl<IMG: Bitmap> State<IMG> {
+    pub(crate) fn layers_mut(&mut self) -> &mut Layers<IMG> {
+        &mut self.layers
+    }
+
+    pub(crate) fn replace_palette(&mut self, palette: Palette) -> Palette {
+        std::mem::replace(&mut self.palette, palette)
+    }
+
+    pub(crate) fn replace_spritesheet(&mut self, size: Size<u8>) -> Size<u8> {
+        std::mem::replace(&mut self.spritesheet, size)
+    }
+
+    pub(crate) fn replace_free_image(
+        &mut self,
+        free_image: Option<FreeImage<IMG>>,
+        selection: Option<Selection>,
+    ) -> (Option<FreeImage<IMG>>, Option<Selection>) {
+        let old_img = std::mem::replace(&mut self.free_image, free_image);
+        let old_selection = std::mem::replace(&mut self.selection, selection);
+
+        (old_img, old_selection)
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{Error, Result};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Where project files are read from and written to when handling
+/// [`Event::LoadProject`] and [`Event::SaveProject`]. By default a [`State`]
+/// uses [`FileStorage`].
+///
+/// [`Event::LoadProject`]: crate::Event::LoadProject
+/// [`Event::SaveProject`]: crate::Event::SaveProject
+/// [`State`]: crate::State
+pub trait ProjectStorage {
+    /// Read the bytes of the project file at a path
+    fn load(&mut self, path: &Path) -> Result<Vec<u8>>;
+
+    /// Write the bytes of a project file to a path
+    fn save(&mut self, path: &Path, bytes: Vec<u8>) -> Result<()>;
+}
+
+/// Stores project files in the file system
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FileStorage;
+
+impl ProjectStorage for FileStorage {
+    fn load(&mut self, path: &Path) -> Result<Vec<u8>> {
+        Ok(std::fs::read(path)?)
+    }
+
+    fn save(&mut self, path: &Path, bytes: Vec<u8>) -> Result<()> {
+        Ok(std::fs::write(path, bytes)?)
+    }
+}
+
+/// Stores project files in memory. Clones share the same files, so a clone
+/// can be kept to inspect what was saved (e.g. in tests).
+#[derive(Debug, Default, Clone)]
+pub struct MemoryStorage {
+    files: Arc<Mutex<HashMap<PathBuf, Vec<u8>>>>,
+}
+
+impl MemoryStorage {
+    /// Create an empty storage
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the bytes of the file at a path, if there is one
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<Vec<u8>> {
+        self.files().get(path.as_ref()).cloned()
+    }
+
+    /// Add a file at a path, replacing any file already there
+    pub fn insert<P: Into<PathBuf>>(&self, path: P, bytes: Vec<u8>) {
+        self.files().insert(path.into(), bytes);
+    }
+
+    fn files(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, Vec<u8>>> {
+        // A panic while holding the lock cannot leave the map inconsistent
+        self.files.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl ProjectStorage for MemoryStorage {
+    fn load(&mut self, path: &Path) -> Result<Vec<u8>> {
+        self.get(path).ok_or_else(|| {
+            Error::IoError(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} not found", path.display()),
+            ))
+        })
+    }
+
+    fn save(&mut self, path: &Path, bytes: Vec<u8>) -> Result<()> {
+        self.insert(path, bytes);
+
+        Ok(())
+    }
+}
+
+/// Storage defined by a pair of closures, one to load and one to save
+pub struct FnStorage<L, S> {
+    load: L,
+    save: S,
+}
+
+impl<L, S> FnStorage<L, S>
+where
+    L: FnMut(&Path) -> Result<Vec<u8>>,
+    S: FnMut(&Path, Vec<u8>) -> Result<()>,
+{
+    /// Create a storage from a closure that loads and one that saves
+    pub fn new(load: L, save: S) -> Self {
+        Self { load, save }
+    }
+}
+
+impl<L, S> ProjectStorage for FnStorage<L, S>
+where
+    L: FnMut(&Path) -> Result<Vec<u8>>,
+    S: FnMut(&Path, Vec<u8>) -> Result<()>,
+{
+    fn load(&mut self, path: &Path) -> Result<Vec<u8>> {
+        (self.load)(path)
+    }
+
+    fn save(&mut self, path: &Path, bytes: Vec<u8>) -> Result<()> {
+        (self.save)(path, bytes)
+    }
+}
+
+/// The storage held by a [`State`](crate::State)
+pub(crate) struct Storage(pub Box<dyn ProjectStorage>);
+
+impl Default for Storage {
+    fn default() -> Self {
+        Self(Box::new(FileStorage))
+    }
+}
+
+impl Debug for Storage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.write_str("Storage(dyn ProjectStorage)")
+    }
+}
@@ -0,0 +1,215 @@
+//! Symmetric drawing, in which what is drawn is copied across one or two axes
+//! or around a center
+
+use crate::shape::Shape;
+use crate::{Brush, Color, PathShape, Point, ShapeOptions};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::TAU;
+
+/// How the drawing is copied
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SymmetryMode {
+    /// Nothing is copied
+    #[default]
+    Off,
+    /// Mirrored from left to right, across a vertical axis
+    Horizontal,
+    /// Mirrored from top to bottom, across a horizontal axis
+    Vertical,
+    /// Mirrored across both axes, making 4 copies
+    Both,
+    /// Rotated around the point where the axes cross, making this many copies
+    /// (including the original)
+    Radial(u8),
+}
+
+/// The symmetry setting of the tools that draw: brush, eraser, bucket, lines
+/// and shapes. Everything they draw is drawn again in each of its copies, as a
+/// single action.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Symmetry {
+    pub mode: SymmetryMode,
+    /// Position of the vertical axis (x) and of the horizontal axis (y), in
+    /// pixels from the top left corner of the canvas. An axis at a whole
+    /// number lies between two columns (or rows) of pixels, and one at a half
+    /// lies along the middle of a column, which is its own mirror.
+    pub axis: Point<f32>,
+}
+
+impl Symmetry {
+    /// Symmetry with the axes in the middle of a canvas of a certain size
+    pub fn centered(mode: SymmetryMode, size: Point<i32>) -> Self {
+        Self {
+            mode,
+            axis: Point::new(size.x as f32 / 2., size.y as f32 / 2.),
+        }
+    }
+
+    /// How many copies of what is drawn there are, including the original
+    pub fn count(&self) -> usize {
+        match self.mode {
+            SymmetryMode::Off => 1,
+            SymmetryMode::Horizontal | SymmetryMode::Vertical => 2,
+            SymmetryMode::Both => 4,
+            SymmetryMode::Radial(n) => n.max(1) as usize,
+        }
+    }
+
+    /// The copy `i` of a pixel (copy 0 being the pixel itself)
+    pub fn image(&self, i: usize, p: Point<i32>) -> Point<i32> {
+        let (x, y) = self.image_f64(i, p.x as f64 + 0.5, p.y as f64 + 0.5);
+
+        // A tiny margin keeps rotations by right angles exact
+        Point::new((x + 1e-6).floor() as i32, (y + 1e-6).floor() as i32)
+    }
+
+    /// All the copies of a pixel, starting with the pixel itself
+    pub fn images(&self, p: Point<i32>) -> Vec<Point<i32>> {
+        (0..self.count()).map(|i| self.image(i, p)).collect()
+    }
+
+    /// The copy `i` of a point in continuous coordinates, in which the center
+    /// of the pixel (0, 0) is (0.5, 0.5)
+    fn image_f64(&self, i: usize, x: f64, y: f64) -> (f64, f64) {
+        let (ax, ay) = (self.axis.x as f64, self.axis.y as f64);
+        let mirror_x = |x: f64| 2. * ax - x;
+        let mirror_y = |y: f64| 2. * ay - y;
+
+        match (self.mode, i) {
+            (_, 0) => (x, y),
+            (SymmetryMode::Horizontal, _) | (SymmetryMode::Both, 1) => (mirror_x(x), y),
+            (SymmetryMode::Vertical, _) | (SymmetryMode::Both, 2) => (x, mirror_y(y)),
+            (SymmetryMode::Both, _) => (mirror_x(x), mirror_y(y)),
+            (SymmetryMode::Radial(n), _) => {
+                let (sin, cos) = (TAU * i as f64 / n as f64).sin_cos();
+                let (dx, dy) = (x - ax, y - ay);
+                (ax + dx * cos - dy * sin, ay + dx * sin + dy * cos)
+            }
+            (SymmetryMode::Off, _) => (x, y),
+        }
+    }
+
+    /// Whether the copy `i` keeps horizontal lines horizontal and vertical
+    /// lines vertical (i.e. it is a mirror, or a rotation by a right angle)
+    fn keeps_axes(&self, i: usize) -> bool {
+        match self.mode {
+            SymmetryMode::Radial(n) => (4 * i).is_multiple_of(n.max(1) as usize),
+            _ => true,
+        }
+    }
+
+    /// A figure and all its copies, starting with the figure itself
+    pub(crate) fn figures(&self, figure: &Figure) -> Vec<Figure> {
+        (0..self.count())
+            .map(|i| match figure {
+                Figure::Shape(shape, p1, p2) if self.keeps_axes(i) => {
+                    Figure::Shape(*shape, self.image(i, *p1), self.image(i, *p2))
+                }
+                Figure::Shape(shape, p1, p2) => {
+                    let outline = shape.vertices(*p1, *p2);
+                    let points = outline.into_iter().map(|(x, y)| {
+                        let (x, y) = self.image_f64(i, x, y);
+                        Point::new(x.floor() as i32, y.floor() as i32)
+                    });
+                    let mut vertices: Vec<_> = points.collect();
+                    vertices.dedup();
+                    Figure::Path(PathShape::Polygon, vertices)
+                }
+                Figure::Path(shape, points) => {
+                    let points = points.iter().map(|p| self.image(i, *p)).collect();
+                    Figure::Path(*shape, points)
+                }
+            })
+            .collect()
+    }
+}
+
+/// Something drawn by the line and shape tools, before it is turned into
+/// pixels
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Figure {
+    /// A shape between two corners
+    Shape(Shape, Point<i32>, Point<i32>),
+    /// A shape through a path of points. A line is a polyline of two points.
+    Path(PathShape, Vec<Point<i32>>),
+}
+
+impl Figure {
+    /// The pixels of the figure and their colors (see [`Shape::pixels`] and
+    /// [`PathShape::pixels`])
+    pub(crate) fn pixels(
+        &self,
+        brush: &Brush,
+        color: Color,
+        options: &ShapeOptions,
+    ) -> Vec<(Point<i32>, Color)> {
+        match self {
+            Self::Shape(shape, p1, p2) => shape.pixels(*p1, *p2, brush, color, options),
+            Self::Path(shape, points) => shape.pixels(points, brush, color, options),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn symmetry(mode: SymmetryMode) -> Symmetry {
+        Symmetry::centered(mode, Point::new(16, 15))
+    }
+
+    #[test_case(SymmetryMode::Off, vec![(2, 3)])]
+    #[test_case(SymmetryMode::Horizontal, vec![(2, 3), (13, 3)])]
+    #[test_case(SymmetryMode::Vertical, vec![(2, 3), (2, 11)])]
+    #[test_case(SymmetryMode::Both, vec![(2, 3), (13, 3), (2, 11), (13, 11)])]
+    #[test_case(SymmetryMode::Radial(2), vec![(2, 3), (13, 11)])]
+    fn images(mode: SymmetryMode, expected: Vec<(i32, i32)>) {
+        let expected: Vec<Point<i32>> = expected.into_iter().map(Into::into).collect();
+
+        assert_eq!(symmetry(mode).images(Point::new(2, 3)), expected);
+    }
+
+    #[test]
+    fn axis_along_a_row() {
+        // In an odd height, the middle row is its own mirror
+        let symmetry = symmetry(SymmetryMode::Vertical);
+
+        assert_eq!(symmetry.image(1, Point::new(4, 7)), Point::new(4, 7));
+        assert_eq!(symmetry.image(1, Point::new(4, 0)), Point::new(4, 14));
+    }
+
+    #[test]
+    fn radial_by_right_angles() {
+        let symmetry = Symmetry {
+            mode: SymmetryMode::Radial(4),
+            axis: Point::new(8., 8.),
+        };
+        let images = symmetry.images(Point::new(9, 1));
+
+        assert_eq!(images, [(9, 1), (14, 9), (6, 14), (1, 6)].map(Point::from));
+        // Going around brings the pixel back to itself
+        for p in images {
+            assert_eq!(symmetry.images(p).len(), 4);
+            assert!(symmetry.images(p).contains(&Point::new(9, 1)));
+        }
+    }
+
+    #[test]
+    fn rotated_rectangle_is_a_polygon() {
+        let symmetry = Symmetry {
+            mode: SymmetryMode::Radial(8),
+            axis: Point::new(10., 10.),
+        };
+        let rect = Figure::Shape(Shape::Rectangle, Point::new(12, 8), Point::new(16, 12));
+        let figures = symmetry.figures(&rect);
+
+        assert_eq!(figures.len(), 8);
+        assert_eq!(figures[0], rect);
+        assert!(matches!(figures[1], Figure::Path(PathShape::Polygon, ref v) if v.len() == 4));
+        assert_eq!(
+            figures[2],
+            Figure::Shape(Shape::Rectangle, Point::new(11, 12), Point::new(7, 16))
+        );
+    }
+}
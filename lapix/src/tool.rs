@@ -1,3 +1,4 @@
+use crate::PathShape;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 
@@ -13,6 +14,13 @@ pub enum Tool {
     Move,
     Rectangle,
     Ellipse,
+    Polyline,
+    Polygon,
+    Bezier,
+    MagicWand,
+    Lasso,
+    PolygonLasso,
+    EllipseSelection,
 }
 
 impl Display for Tool {
@@ -27,8 +35,44 @@ impl Display for Tool {
             Self::Move => "move",
             Self::Rectangle => "rectangle",
             Self::Ellipse => "ellipse",
+            Self::Polyline => "polyline",
+            Self::Polygon => "polygon",
+            Self::Bezier => "bezier",
+            Self::MagicWand => "magic wand",
+            Self::Lasso => "lasso",
+            Self::PolygonLasso => "polygon lasso",
+            Self::EllipseSelection => "ellipse selection",
         };
 
         f.write_str(st)
     }
 }
+
+impl Tool {
+    /// Whether this tool draws with the [`Brush`] (its size and shape)
+    ///
+    /// [`Brush`]: crate::Brush
+    pub fn uses_brush(&self) -> bool {
+        matches!(
+            self,
+            Self::Brush
+                | Self::Eraser
+                | Self::Line
+                | Self::Rectangle
+                | Self::Ellipse
+                | Self::Polyline
+                | Self::Polygon
+                | Self::Bezier
+        )
+    }
+
+    /// The shape this tool draws through a path of points, if any
+    pub fn path_shape(&self) -> Option<PathShape> {
+        match self {
+            Self::Polyline => Some(PathShape::Polyline),
+            Self::Polygon => Some(PathShape::Polygon),
+            Self::Bezier => Some(PathShape::Bezier),
+            _ => None,
+        }
+    }
+}
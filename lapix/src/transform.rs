@@ -21,37 +21,24 @@ impl Transform {
     }
 
     fn silhouette<IMG: Bitmap>(image: &mut IMG) {
-        for i in 0..image.width() {
-            for j in 0..image.height() {
-                let p = (i, j).into();
-                let color = image.pixel(p);
-                if color.a > 127 {
-                    image.set_pixel(p, color::BLACK);
-                }
-            }
-        }
+        image.map_pixels(|color| if color.a > 127 { color::BLACK } else { color });
     }
 
     fn apply_palette<IMG: Bitmap>(image: &mut IMG, palette: &[Color]) {
-        for i in 0..image.width() {
-            for j in 0..image.height() {
-                let p = (i, j).into();
-                let color = image.pixel(p);
-
-                let mut min_dist = f32::MAX;
-                let mut min_index = 0;
-                for (i, palette_color) in palette.iter().enumerate() {
-                    let colorf: ColorF32 = (*palette_color).into();
-                    let dist = colorf.dist(&color.into());
-
-                    if dist < min_dist {
-                        min_dist = dist;
-                        min_index = i;
-                    }
+        image.map_pixels(|color| {
+            let mut min_dist = f32::MAX;
+            let mut min_index = 0;
+            for (i, palette_color) in palette.iter().enumerate() {
+                let colorf: ColorF32 = (*palette_color).into();
+                let dist = colorf.dist(&color.into());
+
+                if dist < min_dist {
+                    min_dist = dist;
+                    min_index = i;
                 }
-
-                image.set_pixel(p, palette[min_index]);
             }
-        }
+
+            palette[min_index]
+        });
     }
 }
@@ -1,12 +1,16 @@
 use lapix::TestImage;
 
 use lapix::color::{BLACK, TRANSPARENT};
-use lapix::{Color, Event, Point, Size, State};
+use lapix::{
+    Bitmap, Brush, BrushShape, BucketOptions, CanvasEffect, Color, Event, HistoryLimits, Mask,
+    Point, Rect, Selection, ShapeConstraints, ShapeOptions, ShapeStyle, Size, State, Symmetry,
+    SymmetryMode, Tool,
+};
 
 #[test]
 fn empty_canvas() {
     let side = 10;
-    let state = State::<TestImage>::new(Size::new(side, side), None, None);
+    let state = State::<TestImage>::new(Size::new(side, side));
 
     for i in 0..side {
         for j in 0..side {
@@ -18,7 +22,7 @@ fn empty_canvas() {
 #[test]
 fn draw_line() {
     let side = 10;
-    let mut state = State::<TestImage>::new(Size::new(side, side), None, None);
+    let mut state = State::<TestImage>::new(Size::new(side, side));
     state.execute(Event::LineStart(Point::new(0, 0))).unwrap();
     state
         .execute(Event::LineEnd(Point::new(side - 1, side - 1)))
@@ -36,7 +40,7 @@ fn draw_line() {
 #[test]
 fn draw_red_line() {
     let side = 10;
-    let mut state = State::<TestImage>::new(Size::new(side, side), None, None);
+    let mut state = State::<TestImage>::new(Size::new(side, side));
     let red = Color::new(255, 0, 0, 255);
     state.execute(Event::SetMainColor(red)).unwrap();
     state.execute(Event::LineStart(Point::new(0, 0))).unwrap();
@@ -56,7 +60,7 @@ fn draw_red_line() {
 #[test]
 fn draw_line_then_clear_canvas() {
     let side = 10;
-    let mut state = State::<TestImage>::new(Size::new(side, side), None, None);
+    let mut state = State::<TestImage>::new(Size::new(side, side));
     state.execute(Event::LineStart(Point::new(0, 0))).unwrap();
     state
         .execute(Event::LineEnd(Point::new(side - 1, side - 1)))
@@ -73,7 +77,7 @@ fn draw_line_then_clear_canvas() {
 #[test]
 fn bucket() {
     let side = 10;
-    let mut state = State::<TestImage>::new(Size::new(side, side), None, None);
+    let mut state = State::<TestImage>::new(Size::new(side, side));
     state.execute(Event::Bucket(Point::new(0, 0))).unwrap();
 
     for i in 0..side {
@@ -86,7 +90,7 @@ fn bucket() {
 #[test]
 fn bucket_then_erase() {
     let side = 10;
-    let mut state = State::<TestImage>::new(Size::new(side, side), None, None);
+    let mut state = State::<TestImage>::new(Size::new(side, side));
     state.execute(Event::Bucket(Point::new(0, 0))).unwrap();
     state.execute(Event::EraseStart).unwrap();
     state.execute(Event::Erase(Point::new(0, 0))).unwrap();
@@ -102,3 +106,982 @@ fn bucket_then_erase() {
         }
     }
 }
+
+#[test]
+fn undo_then_redo_line() {
+    let side = 10;
+    let mut state = State::<TestImage>::new(Size::new(side, side));
+    state.execute(Event::LineStart(Point::new(0, 0))).unwrap();
+    state
+        .execute(Event::LineEnd(Point::new(side - 1, side - 1)))
+        .unwrap();
+    state.execute(Event::Undo).unwrap();
+
+    for i in 0..side {
+        for j in 0..side {
+            assert_eq!(state.canvas().pixel(Point::new(i, j)), TRANSPARENT);
+        }
+    }
+
+    state.execute(Event::Redo).unwrap();
+
+    for i in 0..side {
+        for j in 0..side {
+            let color = if i == j { BLACK } else { TRANSPARENT };
+            assert_eq!(state.canvas().pixel(Point::new(i, j)), color);
+        }
+    }
+}
+
+#[test]
+fn new_action_clears_redo() {
+    let side = 10;
+    let mut state = State::<TestImage>::new(Size::new(side, side));
+    state.execute(Event::Bucket(Point::new(0, 0))).unwrap();
+    state.execute(Event::Undo).unwrap();
+    state.execute(Event::LineStart(Point::new(0, 0))).unwrap();
+    state.execute(Event::LineEnd(Point::new(0, 0))).unwrap();
+    state.execute(Event::Redo).unwrap();
+
+    assert_eq!(state.canvas().pixel(Point::new(0, 0)), BLACK);
+    assert_eq!(state.canvas().pixel(Point::new(1, 1)), TRANSPARENT);
+}
+
+#[test]
+fn undo_then_redo_layers() {
+    let mut state = State::<TestImage>::new(Size::new(2, 2));
+    state.execute(Event::NewLayerAbove).unwrap();
+    state.execute(Event::NewLayerAbove).unwrap();
+    state.execute(Event::DeleteLayer(0)).unwrap();
+    assert_eq!(state.layers().count(), 2);
+
+    state.execute(Event::Undo).unwrap();
+    state.execute(Event::Undo).unwrap();
+    assert_eq!(state.layers().count(), 2);
+
+    state.execute(Event::Redo).unwrap();
+    state.execute(Event::Redo).unwrap();
+    assert_eq!(state.layers().count(), 2);
+
+    state.execute(Event::Undo).unwrap();
+    assert_eq!(state.layers().count(), 3);
+}
+
+#[test]
+fn undo_layer_properties() {
+    let mut state = State::<TestImage>::new(Size::new(2, 2));
+    state.execute(Event::NewLayerAbove).unwrap();
+    state.execute(Event::SwitchLayer(1)).unwrap();
+    state.execute(Event::Bucket(Point::new(0, 0))).unwrap();
+    state
+        .execute(Event::ChangeLayerVisibility(1, false))
+        .unwrap();
+    state.execute(Event::ChangeLayerOpacity(0, 100)).unwrap();
+    state.execute(Event::MoveLayerDown(1)).unwrap();
+
+    assert_eq!(state.layers().canvas_at(0).pixel(Point::ZERO), BLACK);
+    assert_eq!(state.layers().get(1).opacity(), 100);
+    assert!(!state.layers().get(0).visible());
+
+    state.execute(Event::Undo).unwrap();
+    assert_eq!(state.layers().canvas_at(1).pixel(Point::ZERO), BLACK);
+
+    state.execute(Event::Undo).unwrap();
+    assert_eq!(state.layers().get(0).opacity(), 255);
+
+    state.execute(Event::Undo).unwrap();
+    assert!(state.layers().get(1).visible());
+}
+
+#[test]
+fn typed_opacity_is_one_action() {
+    let mut state = State::<TestImage>::new(Size::new(4, 4));
+    state.execute(Event::NewLayerAbove).unwrap();
+    for alpha in [1, 12, 128] {
+        state.execute(Event::ChangeLayerOpacity(1, alpha)).unwrap();
+    }
+    assert_eq!(state.history().len(), 2);
+    assert_eq!(state.layers().get(1).opacity(), 128);
+
+    // Changing another layer, or after an undo, is a new action
+    state.execute(Event::ChangeLayerOpacity(0, 50)).unwrap();
+    state.execute(Event::Undo).unwrap();
+    state.execute(Event::ChangeLayerOpacity(0, 60)).unwrap();
+    assert_eq!(state.history().len(), 3);
+
+    state.execute(Event::Undo).unwrap();
+    state.execute(Event::Undo).unwrap();
+    assert_eq!(state.layers().get(1).opacity(), 255);
+    assert_eq!(state.layers().count(), 2);
+}
+
+#[test]
+fn typed_opacity_is_one_action_in_a_full_history() {
+    let mut state = State::<TestImage>::new(Size::new(4, 4));
+    state
+        .execute(Event::SetHistoryLimits(HistoryLimits {
+            max_steps: Some(3),
+            max_bytes: None,
+        }))
+        .unwrap();
+    for x in 0..4 {
+        state.execute(Event::BrushStart).unwrap();
+        state.execute(Event::BrushStroke(Point::new(x, 0))).unwrap();
+        state.execute(Event::BrushEnd).unwrap();
+    }
+
+    for alpha in [1, 12, 128] {
+        state.execute(Event::ChangeLayerOpacity(0, alpha)).unwrap();
+    }
+    assert_eq!(state.history().len(), 3);
+
+    state.execute(Event::Undo).unwrap();
+    assert_eq!(state.layers().get(0).opacity(), 255);
+    assert_eq!(state.canvas().pixel(Point::new(3, 0)), BLACK);
+}
+
+#[test]
+fn undo_palette_and_spritesheet() {
+    let mut state = State::<TestImage>::new(Size::new(4, 4));
+    let color = Color::new(1, 2, 3, 255);
+    let n_colors = state.palette().len();
+    state.execute(Event::AddToPalette(color)).unwrap();
+    state
+        .execute(Event::SetSpritesheet(Size::new(2, 2)))
+        .unwrap();
+
+    state.execute(Event::Undo).unwrap();
+    assert_eq!(state.spritesheet(), Size::new(1, 1));
+    assert!(state.palette().contains(&color));
+
+    state.execute(Event::Undo).unwrap();
+    assert!(!state.palette().contains(&color));
+    assert_eq!(state.palette().len(), n_colors);
+
+    state.execute(Event::Redo).unwrap();
+    state.execute(Event::Redo).unwrap();
+    assert!(state.palette().contains(&color));
+    assert_eq!(state.spritesheet(), Size::new(2, 2));
+}
+
+#[test]
+fn new_layer_below() {
+    let mut state = State::<TestImage>::new(Size::new(2, 2));
+    state.execute(Event::Bucket(Point::new(0, 0))).unwrap();
+    state.execute(Event::NewLayerBelow).unwrap();
+
+    assert_eq!(state.layers().count(), 2);
+    assert_eq!(state.layers().active_index(), 0);
+    assert_eq!(state.layers().canvas_at(0).pixel(Point::ZERO), TRANSPARENT);
+    assert_eq!(state.layers().canvas_at(1).pixel(Point::ZERO), BLACK);
+
+    state.execute(Event::Undo).unwrap();
+    assert_eq!(state.layers().count(), 1);
+    assert_eq!(state.layers().canvas_at(0).pixel(Point::ZERO), BLACK);
+}
+
+#[test]
+fn undo_move_selection() {
+    let mut state = State::<TestImage>::new(Size::new(4, 4));
+    state.execute(Event::Bucket(Point::new(0, 0))).unwrap();
+    state
+        .execute(Event::StartSelection(Point::new(0, 0)))
+        .unwrap();
+    state
+        .execute(Event::EndSelection(Point::new(1, 1)))
+        .unwrap();
+    state.execute(Event::MoveStart(Point::new(0, 0))).unwrap();
+    state.execute(Event::MoveEnd(Point::new(2, 2))).unwrap();
+    state.execute(Event::FlipHorizontal).unwrap();
+
+    assert_eq!(state.selection(), Some(&Selection::FreeImage));
+    assert_eq!(state.canvas().pixel(Point::new(0, 0)), TRANSPARENT);
+    assert_eq!(state.free_image().unwrap().rect, Rect::new(2, 2, 2, 2));
+
+    state.execute(Event::Undo).unwrap();
+    state.execute(Event::Undo).unwrap();
+    assert!(state.free_image().is_none());
+    assert_eq!(
+        state.selection(),
+        Some(&Selection::Canvas(Mask::from_rect(Rect::new(0, 0, 2, 2))))
+    );
+    assert_eq!(state.canvas().pixel(Point::new(0, 0)), BLACK);
+
+    state.execute(Event::Redo).unwrap();
+    assert_eq!(state.free_image().unwrap().rect, Rect::new(2, 2, 2, 2));
+    assert_eq!(state.canvas().pixel(Point::new(0, 0)), TRANSPARENT);
+
+    state.execute(Event::ClearSelection).unwrap();
+    assert!(state.free_image().is_none());
+    assert_eq!(state.canvas().pixel(Point::new(2, 2)), BLACK);
+
+    state.execute(Event::Undo).unwrap();
+    assert_eq!(state.free_image().unwrap().rect, Rect::new(2, 2, 2, 2));
+    assert_eq!(state.canvas().pixel(Point::new(2, 2)), BLACK);
+}
+
+#[test]
+fn history_labels_and_jumps() {
+    let mut state = State::<TestImage>::new(Size::new(4, 4));
+    state.execute(Event::BrushStart).unwrap();
+    state.execute(Event::BrushStroke(Point::new(0, 0))).unwrap();
+    state.execute(Event::BrushEnd).unwrap();
+    state.execute(Event::Bucket(Point::new(3, 3))).unwrap();
+    state.execute(Event::NewLayerAbove).unwrap();
+    state.execute(Event::DeleteLayer(0)).unwrap();
+
+    let labels: Vec<_> = state.history().labels().collect();
+    assert_eq!(
+        labels,
+        vec![
+            "Brush stroke",
+            "Bucket fill",
+            "New layer 2",
+            "Delete layer 1"
+        ]
+    );
+    assert_eq!(state.history().position(), 4);
+
+    state.execute(Event::GoToHistory(1)).unwrap();
+    assert_eq!(state.history().position(), 1);
+    assert_eq!(state.history().len(), 4);
+    assert_eq!(state.layers().count(), 1);
+    assert_eq!(state.canvas().pixel(Point::new(0, 0)), BLACK);
+    assert_eq!(state.canvas().pixel(Point::new(3, 3)), TRANSPARENT);
+
+    state.execute(Event::GoToHistory(3)).unwrap();
+    assert_eq!(state.history().position(), 3);
+    assert_eq!(state.layers().count(), 2);
+    assert_eq!(state.canvas().pixel(Point::new(3, 3)), BLACK);
+
+    state.execute(Event::GoToHistory(0)).unwrap();
+    assert_eq!(state.canvas().pixel(Point::new(0, 0)), TRANSPARENT);
+    assert!(!state.history().can_undo());
+    assert!(state.history().can_redo());
+}
+
+#[test]
+fn history_step_limit() {
+    let mut state = State::<TestImage>::new(Size::new(4, 4));
+    state
+        .execute(Event::SetHistoryLimits(HistoryLimits {
+            max_steps: Some(2),
+            max_bytes: None,
+        }))
+        .unwrap();
+
+    for x in 0..4 {
+        state.execute(Event::BrushStart).unwrap();
+        state.execute(Event::BrushStroke(Point::new(x, 0))).unwrap();
+        state.execute(Event::BrushEnd).unwrap();
+    }
+    assert_eq!(state.history().len(), 2);
+
+    state.execute(Event::GoToHistory(0)).unwrap();
+    assert_eq!(state.canvas().pixel(Point::new(1, 0)), BLACK);
+    assert_eq!(state.canvas().pixel(Point::new(2, 0)), TRANSPARENT);
+    assert_eq!(state.canvas().pixel(Point::new(3, 0)), TRANSPARENT);
+}
+
+#[test]
+fn history_memory_limit() {
+    let mut state = State::<TestImage>::new(Size::new(8, 8));
+    state.execute(Event::Bucket(Point::new(0, 0))).unwrap();
+    let one_fill = state.history().memory_size();
+
+    state
+        .execute(Event::SetHistoryLimits(HistoryLimits {
+            max_steps: None,
+            max_bytes: Some(one_fill * 2),
+        }))
+        .unwrap();
+
+    let red = Color::new(255, 0, 0, 255);
+    state.execute(Event::SetMainColor(red)).unwrap();
+    state.execute(Event::Bucket(Point::new(0, 0))).unwrap();
+    state.execute(Event::SetMainColor(BLACK)).unwrap();
+    state.execute(Event::Bucket(Point::new(0, 0))).unwrap();
+
+    assert_eq!(state.history().len(), 2);
+    assert!(state.history().memory_size() <= one_fill * 2);
+
+    // The newest action is kept even if it alone exceeds the limit
+    state
+        .execute(Event::SetHistoryLimits(HistoryLimits {
+            max_steps: None,
+            max_bytes: Some(0),
+        }))
+        .unwrap();
+    assert_eq!(state.history().len(), 1);
+}
+
+#[test]
+fn history_memory_limit_ignores_redo() {
+    let mut state = State::<TestImage>::new(Size::new(8, 8));
+    let colors = [BLACK, Color::new(255, 0, 0, 255)];
+    for color in colors.iter().cycle().take(4) {
+        state.execute(Event::SetMainColor(*color)).unwrap();
+        state.execute(Event::Bucket(Point::new(0, 0))).unwrap();
+    }
+    let one_fill = state.history().memory_size() / 4;
+
+    state.execute(Event::Undo).unwrap();
+    state.execute(Event::Undo).unwrap();
+    state
+        .execute(Event::SetHistoryLimits(HistoryLimits {
+            max_steps: None,
+            max_bytes: Some(one_fill * 2),
+        }))
+        .unwrap();
+
+    assert_eq!(state.history().position(), 2);
+    assert_eq!(state.history().len(), 4);
+}
+
+#[test]
+fn failed_import_leaves_no_action_open() {
+    let mut state = State::<TestImage>::new(Size::new(4, 4));
+    state.execute(Event::BrushStart).unwrap();
+    state.execute(Event::BrushStroke(Point::new(0, 0))).unwrap();
+    let path = std::path::PathBuf::from("does/not/exist.png");
+    assert!(state.execute(Event::OpenFile(path)).is_err());
+    state.execute(Event::BrushStroke(Point::new(1, 0))).unwrap();
+    state.execute(Event::BrushEnd).unwrap();
+
+    assert!(state
+        .history()
+        .labels()
+        .all(|label| label != "Import image"));
+    state.execute(Event::Undo).unwrap();
+    assert_eq!(state.canvas().pixel(Point::new(0, 0)), BLACK);
+    assert_eq!(state.canvas().pixel(Point::new(1, 0)), TRANSPARENT);
+}
+
+#[test]
+fn history_limits_are_kept_when_loading() {
+    let limits = HistoryLimits {
+        max_steps: Some(2),
+        max_bytes: None,
+    };
+    let mut state = State::<TestImage>::new(Size::new(4, 4));
+    state.execute(Event::SetHistoryLimits(limits)).unwrap();
+    let bytes = state.project_bytes().unwrap();
+    state.load_project_bytes(&bytes).unwrap();
+    assert_eq!(state.history().limits(), limits);
+
+    for x in 0..4 {
+        state.execute(Event::BrushStart).unwrap();
+        state.execute(Event::BrushStroke(Point::new(x, 0))).unwrap();
+        state.execute(Event::BrushEnd).unwrap();
+    }
+    assert_eq!(state.history().len(), 2);
+}
+
+#[test]
+fn bucket_fill_is_stored_compactly() {
+    let side = 32;
+    let mut state = State::<TestImage>::new(Size::new(side, side));
+    state.execute(Event::Bucket(Point::new(0, 0))).unwrap();
+
+    // One run per row instead of one entry per pixel
+    assert!(state.history().memory_size() < (side * side) as usize);
+}
+
+#[test]
+fn exact_mode_runs_repeated_events() {
+    let side = 4;
+    let restarted_line = |exact| {
+        let mut state = State::<TestImage>::new(Size::new(side, side));
+        state.set_exact(exact);
+        state.execute(Event::LineStart(Point::new(0, 0))).unwrap();
+        state.execute(Event::LineStart(Point::new(0, 3))).unwrap();
+        state.execute(Event::LineEnd(Point::new(3, 3))).unwrap();
+        state
+    };
+
+    // The second start is ignored by default...
+    let state = restarted_line(false);
+    assert_eq!(state.canvas().pixel(Point::new(1, 1)), BLACK);
+    assert_eq!(state.canvas().pixel(Point::new(1, 3)), TRANSPARENT);
+
+    // ...but not in exact mode
+    let state = restarted_line(true);
+    assert_eq!(state.canvas().pixel(Point::new(1, 1)), TRANSPARENT);
+    assert_eq!(state.canvas().pixel(Point::new(1, 3)), BLACK);
+}
+
+#[test]
+fn record_and_replay_with_offset() {
+    let mut state = State::<TestImage>::new(Size::new(8, 8));
+    state.start_recording();
+    state.execute(Event::BrushStart).unwrap();
+    state.execute(Event::BrushStroke(Point::new(0, 0))).unwrap();
+    state.execute(Event::BrushStroke(Point::new(1, 0))).unwrap();
+    state.execute(Event::BrushEnd).unwrap();
+    state.execute(Event::LineStart(Point::new(0, 2))).unwrap();
+    state.execute(Event::LineEnd(Point::new(2, 2))).unwrap();
+    let events = state.stop_recording();
+    assert!(!state.is_recording());
+    assert_eq!(events.len(), 6);
+
+    state.replay("Macro", &events, Point::new(4, 4)).unwrap();
+    assert_eq!(state.canvas().pixel(Point::new(5, 4)), BLACK);
+    assert_eq!(state.canvas().pixel(Point::new(6, 6)), BLACK);
+    assert_eq!(state.history().len(), 3);
+    assert_eq!(state.history().labels().last(), Some("Macro"));
+
+    // The whole replay is undone at once
+    state.execute(Event::Undo).unwrap();
+    assert_eq!(state.canvas().pixel(Point::new(5, 4)), TRANSPARENT);
+    assert_eq!(state.canvas().pixel(Point::new(6, 6)), TRANSPARENT);
+    assert_eq!(state.canvas().pixel(Point::new(1, 0)), BLACK);
+}
+
+#[test]
+fn record_and_replay_undo() {
+    let mut state = State::<TestImage>::new(Size::new(8, 8));
+    state.start_recording();
+    state.execute(Event::LineStart(Point::new(0, 0))).unwrap();
+    state.execute(Event::LineEnd(Point::new(3, 0))).unwrap();
+    state.execute(Event::Bucket(Point::new(0, 4))).unwrap();
+    state.execute(Event::Undo).unwrap();
+    let events = state.stop_recording();
+    assert_eq!(events.last(), Some(&Event::Undo));
+
+    state.execute(Event::Undo).unwrap();
+    state.replay("Macro", &events, Point::ZERO).unwrap();
+    assert_eq!(state.canvas().pixel(Point::new(3, 0)), BLACK);
+    // The undone fill is not replayed
+    assert_eq!(state.canvas().pixel(Point::new(0, 4)), TRANSPARENT);
+    assert_eq!(state.history().len(), 1);
+}
+
+#[test]
+fn replay_only_undoes_its_own_actions() {
+    let mut state = State::<TestImage>::new(Size::new(8, 8));
+    state.execute(Event::Bucket(Point::new(0, 0))).unwrap();
+
+    let red = Color::new(255, 0, 0, 255);
+    let events = [
+        Event::SetTool(Tool::Eraser),
+        Event::Undo,
+        Event::SetMainColor(red),
+        Event::LineStart(Point::new(0, 0)),
+        Event::LineEnd(Point::new(7, 0)),
+        Event::Undo,
+        Event::Redo,
+    ];
+    state.replay("Macro", &events, Point::ZERO).unwrap();
+
+    assert_eq!(state.canvas().pixel(Point::new(3, 0)), red);
+    assert_eq!(state.canvas().pixel(Point::new(3, 3)), BLACK);
+    assert_eq!(state.selected_tool(), Tool::Brush);
+    assert_eq!(state.history().len(), 2);
+}
+
+#[test]
+fn effects_report_changed_area() {
+    let mut state = State::<TestImage>::new(Size::new(10, 10));
+    state.execute(Event::NewLayerAbove).unwrap();
+    state.execute(Event::SwitchLayer(1)).unwrap();
+
+    state.execute(Event::BrushStart).unwrap();
+    let fx = state.execute(Event::BrushStroke(Point::new(2, 3))).unwrap();
+    assert_eq!(fx, CanvasEffect::Update(1, Rect::new(2, 3, 1, 1)));
+    let fx = state.execute(Event::BrushStroke(Point::new(6, 1))).unwrap();
+    assert_eq!(fx, CanvasEffect::Update(1, Rect::new(3, 1, 4, 3)));
+    state.execute(Event::BrushEnd).unwrap();
+
+    let fx = state.execute(Event::SetMainColor(BLACK)).unwrap();
+    assert_eq!(fx, CanvasEffect::None);
+
+    let fx = state.execute(Event::Undo).unwrap();
+    assert_eq!(fx, CanvasEffect::Update(1, Rect::new(2, 1, 5, 3)));
+    let fx = state.execute(Event::NewLayerAbove).unwrap();
+    assert_eq!(fx, CanvasEffect::Layer);
+}
+
+#[test]
+fn bucket_samples_merged_layers() {
+    let mut state = State::<TestImage>::new(Size::new(5, 5));
+    state.execute(Event::RectStart(Point::new(1, 1))).unwrap();
+    state.execute(Event::RectEnd(Point::new(3, 3))).unwrap();
+    state.execute(Event::NewLayerAbove).unwrap();
+    state.execute(Event::SwitchLayer(1)).unwrap();
+
+    let red = Color::new(255, 0, 0, 255);
+    let options = BucketOptions {
+        sample_merged: true,
+        ..Default::default()
+    };
+    state.execute(Event::SetMainColor(red)).unwrap();
+    state.execute(Event::SetBucketOptions(options)).unwrap();
+    state.execute(Event::Bucket(Point::new(2, 2))).unwrap();
+
+    let layers = state.layers();
+    assert_eq!(layers.canvas_at(1).pixel(Point::new(2, 2)), red);
+    assert_eq!(layers.canvas_at(1).pixel(Point::new(1, 1)), TRANSPARENT);
+    assert_eq!(layers.canvas_at(1).pixel(Point::new(0, 0)), TRANSPARENT);
+    assert_eq!(layers.canvas_at(0).pixel(Point::new(2, 2)), TRANSPARENT);
+}
+
+#[test]
+fn brush_size_is_shared_by_eraser() {
+    let mut state = State::<TestImage>::new(Size::new(10, 10));
+    let brush = Brush {
+        size: 3,
+        shape: BrushShape::Square,
+        ..Default::default()
+    };
+    state.execute(Event::SetBrush(brush)).unwrap();
+
+    state.execute(Event::BrushStart).unwrap();
+    state.execute(Event::BrushStroke(Point::new(2, 2))).unwrap();
+    state.execute(Event::BrushStroke(Point::new(6, 2))).unwrap();
+    state.execute(Event::BrushEnd).unwrap();
+
+    let painted = |state: &State<TestImage>| {
+        (0..10)
+            .flat_map(|x| (0..10).map(move |y| Point::new(x, y)))
+            .filter(|p| state.canvas().pixel(*p) == BLACK)
+            .count()
+    };
+    assert_eq!(painted(&state), 7 * 3);
+    assert_eq!(state.canvas().pixel(Point::new(1, 1)), BLACK);
+    assert_eq!(state.canvas().pixel(Point::new(7, 3)), BLACK);
+
+    state.execute(Event::EraseStart).unwrap();
+    state.execute(Event::Erase(Point::new(4, 2))).unwrap();
+    state.execute(Event::EraseEnd).unwrap();
+    assert_eq!(painted(&state), 7 * 3 - 9);
+}
+
+#[test]
+fn pixel_perfect_stroke_is_undone_at_once() {
+    let mut state = State::<TestImage>::new(Size::new(4, 4));
+    let brush = Brush {
+        pixel_perfect: true,
+        ..Default::default()
+    };
+    state.execute(Event::SetBrush(brush)).unwrap();
+
+    state.execute(Event::BrushStart).unwrap();
+    state.execute(Event::BrushStroke(Point::new(0, 0))).unwrap();
+    state.execute(Event::BrushStroke(Point::new(1, 0))).unwrap();
+    state.execute(Event::BrushStroke(Point::new(1, 1))).unwrap();
+    state.execute(Event::BrushStroke(Point::new(2, 1))).unwrap();
+    state.execute(Event::BrushEnd).unwrap();
+
+    assert_eq!(state.canvas().pixel(Point::new(0, 0)), BLACK);
+    assert_eq!(state.canvas().pixel(Point::new(1, 0)), TRANSPARENT);
+    assert_eq!(state.canvas().pixel(Point::new(1, 1)), BLACK);
+    assert_eq!(state.canvas().pixel(Point::new(2, 1)), BLACK);
+    assert_eq!(state.history().len(), 1);
+
+    state.execute(Event::Undo).unwrap();
+    for x in 0..3 {
+        for y in 0..2 {
+            assert_eq!(state.canvas().pixel(Point::new(x, y)), TRANSPARENT);
+        }
+    }
+
+    state.execute(Event::Redo).unwrap();
+    assert_eq!(state.canvas().pixel(Point::new(1, 0)), TRANSPARENT);
+    assert_eq!(state.canvas().pixel(Point::new(1, 1)), BLACK);
+}
+
+#[test]
+fn constrained_filled_rectangle() {
+    let mut state = State::<TestImage>::new(Size::new(10, 10));
+    let red = Color::new(255, 0, 0, 255);
+    let options = ShapeOptions {
+        style: ShapeStyle::FilledOutline,
+        fill_color: red,
+    };
+    state.execute(Event::SetShapeOptions(options)).unwrap();
+    state.set_shape_constraints(ShapeConstraints {
+        square: true,
+        from_center: true,
+    });
+
+    state.start_recording();
+    state.execute(Event::RectStart(Point::new(4, 4))).unwrap();
+    state.execute(Event::RectEnd(Point::new(6, 5))).unwrap();
+    let events = state.stop_recording();
+
+    // A 5x5 square around the first point
+    assert_eq!(state.canvas().pixel(Point::new(2, 2)), BLACK);
+    assert_eq!(state.canvas().pixel(Point::new(6, 6)), BLACK);
+    assert_eq!(state.canvas().pixel(Point::new(4, 4)), red);
+    assert_eq!(state.canvas().pixel(Point::new(7, 4)), TRANSPARENT);
+
+    // The macro draws the same square, whatever the constraints
+    assert_eq!(
+        events,
+        vec![
+            Event::RectStart(Point::new(2, 2)),
+            Event::RectEnd(Point::new(6, 6))
+        ]
+    );
+}
+
+#[test]
+fn snapped_line() {
+    let mut state = State::<TestImage>::new(Size::new(10, 10));
+    state.set_shape_constraints(ShapeConstraints {
+        square: true,
+        from_center: false,
+    });
+    state.execute(Event::LineStart(Point::new(0, 0))).unwrap();
+    state.execute(Event::LineEnd(Point::new(6, 2))).unwrap();
+
+    // A 2:1 line with 3 runs of 2 pixels
+    for p in [(0, 0), (1, 0), (2, 1), (3, 1), (4, 2), (5, 2)] {
+        assert_eq!(state.canvas().pixel(p.into()), BLACK);
+    }
+    assert_eq!(state.canvas().pixel(Point::new(6, 2)), TRANSPARENT);
+}
+
+#[test]
+fn polyline_click_by_click() {
+    let mut state = State::<TestImage>::new(Size::new(10, 10));
+    state.execute(Event::SetTool(Tool::Polyline)).unwrap();
+    state.execute(Event::PathPoint(Point::new(0, 0))).unwrap();
+    state.execute(Event::PathPoint(Point::new(4, 0))).unwrap();
+    state.execute(Event::PathPoint(Point::new(4, 0))).unwrap();
+    state.execute(Event::PathPoint(Point::new(4, 3))).unwrap();
+    state.update_free_image(Point::new(8, 3)).unwrap();
+
+    // Only the preview shows the path (with the mouse as its next point)
+    assert_eq!(state.path().len(), 3);
+    assert!(state.free_image().is_some());
+    assert_eq!(state.canvas().pixel(Point::new(2, 0)), TRANSPARENT);
+
+    state.execute(Event::PathEnd).unwrap();
+
+    assert!(state.path().is_empty());
+    for p in [(0, 0), (2, 0), (4, 0), (4, 2), (4, 3)] {
+        assert_eq!(state.canvas().pixel(p.into()), BLACK);
+    }
+    assert_eq!(state.canvas().pixel(Point::new(6, 3)), TRANSPARENT);
+    assert_eq!(state.history().labels().last(), Some("Polyline"));
+
+    state.execute(Event::Undo).unwrap();
+    assert_eq!(state.canvas().pixel(Point::new(4, 2)), TRANSPARENT);
+}
+
+#[test]
+fn filled_polygon() {
+    let mut state = State::<TestImage>::new(Size::new(10, 10));
+    let red = Color::new(255, 0, 0, 255);
+    let options = ShapeOptions {
+        style: ShapeStyle::FilledOutline,
+        fill_color: red,
+    };
+    state.execute(Event::SetTool(Tool::Polygon)).unwrap();
+    state.execute(Event::SetShapeOptions(options)).unwrap();
+    for p in [(0, 0), (8, 0), (0, 8)] {
+        state.execute(Event::PathPoint(p.into())).unwrap();
+    }
+    state.execute(Event::PathEnd).unwrap();
+
+    assert_eq!(state.canvas().pixel(Point::new(4, 4)), BLACK);
+    assert_eq!(state.canvas().pixel(Point::new(0, 4)), BLACK);
+    assert_eq!(state.canvas().pixel(Point::new(2, 2)), red);
+    assert_eq!(state.canvas().pixel(Point::new(6, 6)), TRANSPARENT);
+}
+
+#[test]
+fn bezier_with_dragged_control_points() {
+    let mut state = State::<TestImage>::new(Size::new(10, 10));
+    state.execute(Event::SetTool(Tool::Bezier)).unwrap();
+    state.execute(Event::PathPoint(Point::new(0, 0))).unwrap();
+    state.execute(Event::PathPoint(Point::new(9, 0))).unwrap();
+
+    // The ends get two control points between them
+    assert_eq!(
+        state.path(),
+        [(0, 0), (3, 0), (6, 0), (9, 0)].map(Point::from)
+    );
+
+    state
+        .execute(Event::MovePathPoint(1, Point::new(0, 9)))
+        .unwrap();
+    state
+        .execute(Event::MovePathPoint(2, Point::new(9, 9)))
+        .unwrap();
+    state.execute(Event::PathEnd).unwrap();
+
+    assert_eq!(state.canvas().pixel(Point::new(0, 0)), BLACK);
+    assert_eq!(state.canvas().pixel(Point::new(9, 0)), BLACK);
+    assert_eq!(state.canvas().pixel(Point::new(4, 0)), TRANSPARENT);
+    assert!((0..10).any(|x| state.canvas().pixel(Point::new(x, 7)) == BLACK));
+    assert_eq!(state.history().labels().last(), Some("Bezier curve"));
+}
+
+#[test]
+fn other_events_cancel_the_path() {
+    let mut state = State::<TestImage>::new(Size::new(10, 10));
+    state.execute(Event::SetTool(Tool::Polygon)).unwrap();
+    state.execute(Event::PathPoint(Point::new(0, 0))).unwrap();
+    state.execute(Event::PathPoint(Point::new(5, 5))).unwrap();
+    state.update_free_image(Point::new(0, 5)).unwrap();
+    state
+        .execute(Event::SetMainColor(Color::new(255, 0, 0, 255)))
+        .unwrap();
+
+    // Changing a setting keeps the path, but not its old preview
+    assert_eq!(state.path().len(), 2);
+    assert!(state.free_image().is_none());
+
+    state.execute(Event::SetTool(Tool::Brush)).unwrap();
+    state.execute(Event::PathEnd).unwrap();
+
+    assert!(state.path().is_empty());
+    assert_eq!(state.canvas().pixel(Point::new(0, 0)), TRANSPARENT);
+}
+
+#[test]
+fn mirrored_stroke_is_one_action() {
+    let mut state = State::<TestImage>::new(Size::new(10, 10));
+    let symmetry = Symmetry::centered(SymmetryMode::Both, state.canvas().size());
+    state.execute(Event::SetSymmetry(symmetry)).unwrap();
+    state.execute(Event::BrushStart).unwrap();
+    state.execute(Event::BrushStroke(Point::new(1, 1))).unwrap();
+    state.execute(Event::BrushStroke(Point::new(1, 3))).unwrap();
+    state.execute(Event::BrushEnd).unwrap();
+
+    for p in [(1, 2), (8, 2), (1, 7), (8, 7)] {
+        assert_eq!(state.canvas().pixel(p.into()), BLACK);
+    }
+
+    state.execute(Event::Undo).unwrap();
+    for p in [(1, 2), (8, 2), (1, 7), (8, 7)] {
+        assert_eq!(state.canvas().pixel(p.into()), TRANSPARENT);
+    }
+}
+
+#[test]
+fn mirrored_bucket() {
+    let mut state = State::<TestImage>::new(Size::new(10, 10));
+    let red = Color::new(255, 0, 0, 255);
+    let symmetry = Symmetry::centered(SymmetryMode::Horizontal, state.canvas().size());
+    state.execute(Event::SetSymmetry(symmetry)).unwrap();
+    state.execute(Event::LineStart(Point::new(3, 0))).unwrap();
+    state.execute(Event::LineEnd(Point::new(3, 9))).unwrap();
+    state.execute(Event::SetMainColor(red)).unwrap();
+    state.execute(Event::Bucket(Point::new(0, 0))).unwrap();
+
+    assert_eq!(state.canvas().pixel(Point::new(6, 5)), BLACK);
+    assert_eq!(state.canvas().pixel(Point::new(2, 5)), red);
+    assert_eq!(state.canvas().pixel(Point::new(7, 5)), red);
+    assert_eq!(state.canvas().pixel(Point::new(4, 5)), TRANSPARENT);
+
+    // The mirrored fill must not spread from the pixels the first one painted
+    let mut state = State::<TestImage>::new(Size::new(6, 1));
+    let gray = Color::new(30, 30, 30, 255);
+    state.execute(Event::SetMainColor(gray)).unwrap();
+    state.execute(Event::BrushStart).unwrap();
+    state.execute(Event::BrushStroke(Point::ZERO)).unwrap();
+    state.execute(Event::BrushEnd).unwrap();
+
+    let options = BucketOptions {
+        tolerance: 20,
+        ..Default::default()
+    };
+    state.execute(Event::SetBucketOptions(options)).unwrap();
+    let symmetry = Symmetry {
+        mode: SymmetryMode::Horizontal,
+        axis: Point::new(3., 0.),
+    };
+    state.execute(Event::SetSymmetry(symmetry)).unwrap();
+    state.execute(Event::SetMainColor(BLACK)).unwrap();
+    state.execute(Event::Bucket(Point::new(1, 0))).unwrap();
+
+    assert_eq!(state.canvas().pixel(Point::new(0, 0)), gray);
+    for x in 1..6 {
+        assert_eq!(state.canvas().pixel(Point::new(x, 0)), BLACK);
+    }
+}
+
+#[test]
+fn radial_line() {
+    let mut state = State::<TestImage>::new(Size::new(12, 12));
+    let symmetry = Symmetry::centered(SymmetryMode::Radial(4), state.canvas().size());
+    state.execute(Event::SetSymmetry(symmetry)).unwrap();
+    state.execute(Event::SetTool(Tool::Line)).unwrap();
+    state.execute(Event::LineStart(Point::new(6, 1))).unwrap();
+    state.update_free_image(Point::new(9, 1)).unwrap();
+
+    // The preview shows every copy
+    let preview = state.free_image().unwrap().rect;
+    assert_eq!(preview, Rect::new(1, 1, 10, 10));
+
+    state.execute(Event::LineEnd(Point::new(9, 1))).unwrap();
+
+    for x in 6..=9 {
+        for p in symmetry.images(Point::new(x, 1)) {
+            assert_eq!(state.canvas().pixel(p), BLACK);
+        }
+    }
+    assert_eq!(state.history().labels().last(), Some("Line"));
+}
+
+#[test]
+fn tiled_stroke_wraps_around() {
+    let mut state = State::<TestImage>::new(Size::new(10, 10));
+    state.execute(Event::SetTileMode(true)).unwrap();
+    state.execute(Event::BrushStart).unwrap();
+    state.execute(Event::BrushStroke(Point::new(8, 5))).unwrap();
+    state
+        .execute(Event::BrushStroke(Point::new(12, 5)))
+        .unwrap();
+    state.execute(Event::BrushEnd).unwrap();
+
+    for x in [8, 9, 0, 1, 2] {
+        assert_eq!(state.canvas().pixel(Point::new(x, 5)), BLACK);
+    }
+    assert_eq!(state.canvas().pixel(Point::new(3, 5)), TRANSPARENT);
+}
+
+#[test]
+fn tiled_rectangle_wraps_around() {
+    let mut state = State::<TestImage>::new(Size::new(10, 10));
+    state.execute(Event::SetTileMode(true)).unwrap();
+    state.execute(Event::SetTool(Tool::Rectangle)).unwrap();
+    state.execute(Event::RectStart(Point::new(-2, -2))).unwrap();
+    state.execute(Event::RectEnd(Point::new(1, 1))).unwrap();
+
+    // The corners are split among the four corners of the canvas
+    for p in [(8, 8), (1, 8), (8, 1), (1, 1), (9, 8), (0, 1)] {
+        assert_eq!(state.canvas().pixel(p.into()), BLACK);
+    }
+    assert_eq!(state.canvas().pixel(Point::new(9, 9)), TRANSPARENT);
+}
+
+#[test]
+fn tiled_bucket_fills_across_the_edges() {
+    let mut state = State::<TestImage>::new(Size::new(10, 10));
+    let red = Color::new(255, 0, 0, 255);
+    state.execute(Event::LineStart(Point::new(3, 0))).unwrap();
+    state.execute(Event::LineEnd(Point::new(3, 9))).unwrap();
+    state.execute(Event::SetMainColor(red)).unwrap();
+    state.execute(Event::SetTileMode(true)).unwrap();
+    state.execute(Event::Bucket(Point::new(-1, 0))).unwrap();
+
+    assert_eq!(state.canvas().pixel(Point::new(0, 5)), red);
+    assert_eq!(state.canvas().pixel(Point::new(9, 5)), red);
+    assert_eq!(state.canvas().pixel(Point::new(3, 5)), BLACK);
+
+    // Without tile mode, the line splits the canvas in two
+    state.execute(Event::Undo).unwrap();
+    state.execute(Event::SetTileMode(false)).unwrap();
+    state.execute(Event::Bucket(Point::new(0, 0))).unwrap();
+
+    assert_eq!(state.canvas().pixel(Point::new(0, 5)), red);
+    assert_eq!(state.canvas().pixel(Point::new(9, 5)), TRANSPARENT);
+}
+
+#[test]
+fn magic_wand_selects_an_area_of_one_color() {
+    let mut state = State::<TestImage>::new(Size::new(6, 6));
+    state.execute(Event::LineStart(Point::new(3, 0))).unwrap();
+    state.execute(Event::LineEnd(Point::new(3, 5))).unwrap();
+    state.execute(Event::SetTool(Tool::MagicWand)).unwrap();
+    state.execute(Event::MagicWand(Point::new(0, 0))).unwrap();
+
+    let Some(Selection::Canvas(mask)) = state.selection() else {
+        panic!("nothing selected");
+    };
+    assert_eq!(mask.bounds(), Rect::new(0, 0, 3, 6));
+    assert!(mask.is_rect());
+
+    // Deleting the selection leaves the rest of the canvas untouched
+    state.execute(Event::SetTool(Tool::Selection)).unwrap();
+    state.execute(Event::MagicWand(Point::new(3, 2))).unwrap();
+    state.execute(Event::DeleteSelection).unwrap();
+    assert_eq!(state.canvas().pixel(Point::new(3, 2)), TRANSPARENT);
+    state.execute(Event::Undo).unwrap();
+    assert_eq!(state.canvas().pixel(Point::new(3, 2)), BLACK);
+}
+
+#[test]
+fn lasso_selection() {
+    let mut state = State::<TestImage>::new(Size::new(8, 8));
+    state.execute(Event::Bucket(Point::ZERO)).unwrap();
+    state.execute(Event::SetTool(Tool::Lasso)).unwrap();
+    for p in [(1, 1), (6, 1), (1, 6)] {
+        state.execute(Event::PathPoint(p.into())).unwrap();
+    }
+    state.execute(Event::PathEnd).unwrap();
+
+    let Some(Selection::Canvas(mask)) = state.selection().cloned() else {
+        panic!("nothing selected");
+    };
+    assert_eq!(mask.bounds(), Rect::new(1, 1, 6, 6));
+    assert!(mask.contains(Point::new(2, 2)));
+    assert!(!mask.contains(Point::new(6, 6)));
+    // Nothing is drawn
+    assert_eq!(state.history().len(), 1);
+
+    // Only the selected pixels are cut from the canvas and moved
+    state.execute(Event::MoveStart(Point::new(1, 1))).unwrap();
+    state.execute(Event::MoveEnd(Point::new(0, 1))).unwrap();
+    assert_eq!(state.canvas().pixel(Point::new(2, 2)), TRANSPARENT);
+    assert_eq!(state.canvas().pixel(Point::new(6, 6)), BLACK);
+    state.execute(Event::ClearSelection).unwrap();
+    assert_eq!(state.canvas().pixel(Point::new(1, 2)), BLACK);
+    assert_eq!(state.canvas().pixel(Point::new(0, 2)), BLACK);
+    assert_eq!(state.canvas().pixel(Point::new(6, 1)), TRANSPARENT);
+    assert_eq!(state.canvas().pixel(Point::new(6, 6)), BLACK);
+}
+
+#[test]
+fn ellipse_selection() {
+    let mut state = State::<TestImage>::new(Size::new(8, 8));
+    state.execute(Event::Bucket(Point::ZERO)).unwrap();
+    state
+        .execute(Event::SetTool(Tool::EllipseSelection))
+        .unwrap();
+    state
+        .execute(Event::StartSelection(Point::new(0, 0)))
+        .unwrap();
+    state
+        .execute(Event::EndSelection(Point::new(7, 7)))
+        .unwrap();
+    state.execute(Event::Copy).unwrap();
+    state.execute(Event::DeleteSelection).unwrap();
+
+    // The corners are outside the ellipse
+    assert_eq!(state.canvas().pixel(Point::new(0, 0)), BLACK);
+    assert_eq!(state.canvas().pixel(Point::new(4, 4)), TRANSPARENT);
+
+    state.execute(Event::Paste(Point::ZERO)).unwrap();
+    let image = &state.free_image().unwrap().texture;
+    assert_eq!(image.pixel(Point::new(0, 0)), TRANSPARENT);
+    assert_eq!(image.pixel(Point::new(4, 4)), BLACK);
+}
+
+#[test]
+fn bad_layer_indices_are_errors() {
+    let mut state = State::<TestImage>::new(Size::new(4, 4));
+    state.execute(Event::NewLayerAbove).unwrap();
+
+    for event in [
+        Event::SwitchLayer(2),
+        Event::ChangeLayerVisibility(2, false),
+        Event::ChangeLayerOpacity(2, 10),
+        Event::DeleteLayer(2),
+        Event::MoveLayerDown(0),
+        Event::MoveLayerUp(1),
+        Event::SetSpritesheet(Size::new(0, 1)),
+        Event::SetSpritesheet(Size::new(3, 1)),
+    ] {
+        assert!(state.execute(event).is_err());
+    }
+    assert_eq!(state.history().len(), 1);
+
+    state.execute(Event::DeleteLayer(1)).unwrap();
+    assert!(state.execute(Event::DeleteLayer(0)).is_err());
+    assert_eq!(state.layers().count(), 1);
+}
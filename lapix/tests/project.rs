@@ -0,0 +1,225 @@
+use lapix::TestImage;
+
+use lapix::color::{BLACK, TRANSPARENT};
+use lapix::{Bitmap, Color, Error, Event, FnStorage, MemoryStorage, Point, Size, State};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+const RED: Color = Color::new(255, 0, 0, 255);
+const GREEN: Color = Color::new(0, 255, 0, 255);
+
+fn fixture(name: &str) -> Vec<u8> {
+    let path = format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+    std::fs::read(path).unwrap()
+}
+
+fn load(bytes: &[u8]) -> lapix::Result<State<TestImage>> {
+    let mut state = State::<TestImage>::new(Size::new(1, 1));
+    state.load_project_bytes(bytes)?;
+
+    Ok(state)
+}
+
+/// The project saved in the fixtures: three 8x6 layers, the first with a black
+/// line at the top, the second filled with red at half opacity (and active),
+/// the third empty and hidden
+fn draw_fixture_project() -> State<TestImage> {
+    let mut state = State::<TestImage>::new(Size::new(8, 6));
+    state.execute(Event::LineStart(Point::new(0, 0))).unwrap();
+    state.execute(Event::LineEnd(Point::new(7, 0))).unwrap();
+    state.execute(Event::NewLayerAbove).unwrap();
+    state.execute(Event::SwitchLayer(1)).unwrap();
+    state.execute(Event::SetMainColor(RED)).unwrap();
+    state.execute(Event::Bucket(Point::new(0, 5))).unwrap();
+    state.execute(Event::ChangeLayerOpacity(1, 128)).unwrap();
+    state.execute(Event::NewLayerAbove).unwrap();
+    state
+        .execute(Event::ChangeLayerVisibility(2, false))
+        .unwrap();
+    state
+        .execute(Event::SetSpritesheet(Size::new(2, 1)))
+        .unwrap();
+    state.execute(Event::AddToPalette(GREEN)).unwrap();
+    state.execute(Event::SetMainColor(GREEN)).unwrap();
+    state.execute(Event::SwitchLayer(1)).unwrap();
+
+    state
+}
+
+fn assert_fixture_project<IMG>(state: &State<IMG>)
+where
+    IMG: Bitmap + Serialize + for<'de> Deserialize<'de>,
+{
+    let layers = state.layers();
+    assert_eq!(layers.count(), 3);
+    assert_eq!(layers.active_index(), 1);
+    assert_eq!(layers.canvas_at(0).size(), Size::new(8, 6));
+
+    assert_eq!(layers.canvas_at(0).pixel(Point::new(7, 0)), BLACK);
+    assert_eq!(layers.canvas_at(0).pixel(Point::new(7, 1)), TRANSPARENT);
+    assert_eq!(layers.canvas_at(1).pixel(Point::new(3, 3)), RED);
+    assert_eq!(layers.canvas_at(2).pixel(Point::new(3, 3)), TRANSPARENT);
+
+    assert!(layers.get(0).visible());
+    assert_eq!(layers.get(1).opacity(), 128);
+    assert!(!layers.get(2).visible());
+
+    assert_eq!(state.spritesheet(), Size::new(2, 1));
+    assert!(state.palette().contains(&GREEN));
+    assert_eq!(state.main_color(), GREEN);
+}
+
+#[test]
+fn round_trip() {
+    let state = draw_fixture_project();
+    let loaded = load(&state.project_bytes().unwrap()).unwrap();
+
+    assert_fixture_project(&loaded);
+    assert_eq!(loaded.palette(), state.palette());
+}
+
+#[test]
+fn load_v1_fixture() {
+    let state = load(&fixture("v1.tarsila")).unwrap();
+    assert_fixture_project(&state);
+}
+
+#[cfg(feature = "rgba-bitmap")]
+#[test]
+fn load_v1_fixture_headless() {
+    let mut state = State::<lapix::RgbaBitmap>::new(Size::new(1, 1));
+    state.load_project_bytes(&fixture("v1.tarsila")).unwrap();
+    assert_fixture_project(&state);
+
+    let loaded = load(&state.project_bytes().unwrap()).unwrap();
+    assert_fixture_project(&loaded);
+}
+
+#[test]
+fn load_legacy_fixture() {
+    let state = load(&fixture("legacy_0.1.2.tarsila")).unwrap();
+    assert_fixture_project(&state);
+}
+
+#[test]
+fn saved_bytes_match_v1_fixture() {
+    // If this fails, the format changed: bump the format version, keep the
+    // old fixture and add a migration for it
+    let state = draw_fixture_project();
+    assert_eq!(state.project_bytes().unwrap(), fixture("v1.tarsila"));
+}
+
+#[test]
+fn not_a_project() {
+    let result = load(b"definitely not a project file");
+    assert!(matches!(result, Err(Error::InvalidProjectFile)));
+
+    let result = load(&[]);
+    assert!(matches!(result, Err(Error::InvalidProjectFile)));
+}
+
+#[test]
+fn newer_version() {
+    let mut bytes = fixture("v1.tarsila");
+    bytes[5..9].copy_from_slice(&99u32.to_le_bytes());
+
+    let result = load(&bytes);
+    assert!(matches!(result, Err(Error::UnsupportedProjectVersion(99))));
+}
+
+#[test]
+fn truncated_files_fail_without_panicking() {
+    for name in ["v1.tarsila", "legacy_0.1.2.tarsila"] {
+        let bytes = fixture(name);
+
+        for len in 0..bytes.len() {
+            assert!(load(&bytes[..len]).is_err(), "{name} truncated at {len}");
+        }
+    }
+}
+
+#[test]
+fn corrupt_length_fails_without_panicking() {
+    let mut bytes = fixture("v1.tarsila");
+    // The number of layers, right after the header and the canvas size
+    bytes[17..25].copy_from_slice(&u64::MAX.to_le_bytes());
+
+    assert!(load(&bytes).is_err());
+}
+
+#[test]
+fn invalid_spritesheet() {
+    // The spritesheet comes right before the main color, at the end
+    for cols in [0, 3] {
+        let mut bytes = fixture("v1.tarsila");
+        let i = bytes.len() - 6;
+        bytes[i] = cols;
+
+        assert!(matches!(load(&bytes), Err(Error::CorruptProject(_))));
+    }
+}
+
+#[test]
+fn failed_load_keeps_state() {
+    let mut state = draw_fixture_project();
+    assert!(state.load_project_bytes(b"tarsila 0.1.2 garbage").is_err());
+
+    assert_fixture_project(&state);
+}
+
+#[test]
+fn save_and_load_through_storage() {
+    let storage = MemoryStorage::new();
+    let path = PathBuf::from("sprite.tarsila");
+
+    let mut state = draw_fixture_project();
+    state.set_storage(storage.clone());
+    state.execute(Event::SaveProject(path.clone())).unwrap();
+    assert_eq!(storage.get(&path), Some(fixture("v1.tarsila")));
+
+    let mut loaded = State::<TestImage>::new(Size::new(1, 1));
+    loaded.set_storage(storage);
+    loaded.execute(Event::LoadProject(path)).unwrap();
+    assert_fixture_project(&loaded);
+}
+
+#[test]
+fn load_missing_file() {
+    let mut state = State::<TestImage>::new(Size::new(2, 2));
+    state.set_storage(MemoryStorage::new());
+
+    let result = state.execute(Event::LoadProject("missing.tarsila".into()));
+    assert!(matches!(result, Err(Error::IoError(_))));
+    assert_eq!(state.canvas().size(), Size::new(2, 2));
+}
+
+#[test]
+fn storage_errors_are_returned() {
+    let mut state = draw_fixture_project();
+    state.set_storage(FnStorage::new(
+        |_| Ok(b"not a project".to_vec()),
+        |_, _| Err(Error::IoError(std::io::ErrorKind::PermissionDenied.into())),
+    ));
+
+    let result = state.execute(Event::SaveProject("a.tarsila".into()));
+    assert!(matches!(result, Err(Error::IoError(_))));
+
+    let result = state.execute(Event::LoadProject("a.tarsila".into()));
+    assert!(matches!(result, Err(Error::InvalidProjectFile)));
+    assert_fixture_project(&state);
+}
+
+#[test]
+fn load_image() {
+    let mut state = draw_fixture_project();
+    let png = state.layers().canvas_at(0).inner().png_bytes().unwrap();
+
+    state.load_image_bytes(&png).unwrap();
+    assert_eq!(state.layers().count(), 1);
+    assert_eq!(state.canvas().size(), Size::new(8, 6));
+    assert_eq!(state.canvas().pixel(Point::new(7, 0)), BLACK);
+    assert_eq!(state.history().position(), 0);
+
+    assert!(state.load_image_bytes(b"not an image").is_err());
+    assert_eq!(state.canvas().pixel(Point::new(7, 0)), BLACK);
+}
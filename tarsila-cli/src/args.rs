@@ -0,0 +1,241 @@
+use crate::{Error, Result};
+use lapix::{parse_size, Size};
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: tarsila-cli <COMMAND> [OPTIONS]
+
+Commands:
+  export    Export a project to an image
+  run       Run a script of events and write the result
+
+Options:
+  -h, --help       Print this help, or the help of a command
+  -V, --version    Print the version";
+
+pub const EXPORT_USAGE: &str = "\
+Usage: tarsila-cli export [OPTIONS] PROJECT OUTPUT
+
+Flatten the layers of PROJECT (a .tarsila file) and write them to OUTPUT. The
+format is given by the extension of OUTPUT (e.g. .png), unless --format is used.
+
+Options:
+  --format FORMAT    Image format, e.g. png, bmp, tga, jpg
+  --scale N          Scale the image N times (default 1)
+  --layers           Write each layer to its own file (OUTPUT_layerI), instead
+                     of flattening them
+  --frames           Write each frame of the spritesheet to its own file
+                     (OUTPUT_frameI)
+  -h, --help         Print this help";
+
+pub const RUN_USAGE: &str = "\
+Usage: tarsila-cli run [OPTIONS] SCRIPT OUTPUT
+
+Run the events of SCRIPT, a list of events in JSON (or RON, if the extension is
+.ron), on a new blank canvas and write the result to OUTPUT: a project if the
+extension is .tarsila, or else an image. Every event that fails is reported,
+and in that case nothing is written.
+
+Options:
+  --size WxH    Size of the canvas (default 64x64)
+  --dedup       Ignore repeated events, as the editor does, instead of running
+                every event exactly as written
+  -h, --help    Print this help";
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Help,
+    Version,
+    Export(ExportArgs),
+    Run(RunArgs),
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ExportArgs {
+    pub help: bool,
+    pub project: PathBuf,
+    pub output: PathBuf,
+    pub format: Option<String>,
+    pub scale: u32,
+    pub layers: bool,
+    pub frames: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RunArgs {
+    pub help: bool,
+    pub script: PathBuf,
+    pub output: PathBuf,
+    pub size: Size<i32>,
+    pub dedup: bool,
+}
+
+impl Default for RunArgs {
+    fn default() -> Self {
+        Self {
+            help: false,
+            script: PathBuf::new(),
+            output: PathBuf::new(),
+            size: Size::new(64, 64),
+            dedup: false,
+        }
+    }
+}
+
+impl Command {
+    /// Parse the arguments, not including the name of the program
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self> {
+        let mut args = args.into_iter();
+
+        match args.next().as_deref() {
+            Some("-h" | "--help") => Ok(Self::Help),
+            Some("-V" | "--version") => Ok(Self::Version),
+            Some("export") => Ok(Self::Export(ExportArgs::parse(args)?)),
+            Some("run") => Ok(Self::Run(RunArgs::parse(args)?)),
+            Some(command) => Err(Error::Usage(format!("unknown command {command}"))),
+            None => Err(Error::Usage("missing command".to_owned())),
+        }
+    }
+}
+
+impl ExportArgs {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self> {
+        let mut parsed = Self {
+            scale: 1,
+            ..Default::default()
+        };
+        let mut paths = Vec::new();
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| Error::Usage(format!("{name} needs a value")))
+            };
+
+            match arg.as_str() {
+                "-h" | "--help" => parsed.help = true,
+                "--format" => parsed.format = Some(value("--format")?),
+                "--scale" => {
+                    let scale = value("--scale")?;
+                    parsed.scale = match scale.parse() {
+                        Ok(n) if n > 0 => n,
+                        _ => return Err(Error::Usage(format!("invalid scale {scale}"))),
+                    };
+                }
+                "--layers" => parsed.layers = true,
+                "--frames" => parsed.frames = true,
+                _ if arg.starts_with('-') => {
+                    return Err(Error::Usage(format!("unknown option {arg}")))
+                }
+                _ => paths.push(PathBuf::from(arg)),
+            }
+        }
+
+        if parsed.help {
+            return Ok(parsed);
+        }
+
+        match <[PathBuf; 2]>::try_from(paths) {
+            Ok([project, output]) => {
+                parsed.project = project;
+                parsed.output = output;
+            }
+            Err(_) => return Err(Error::Usage("expected a PROJECT and an OUTPUT".to_owned())),
+        }
+
+        Ok(parsed)
+    }
+}
+
+impl RunArgs {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self> {
+        let mut parsed = Self::default();
+        let mut paths = Vec::new();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => parsed.help = true,
+                "--size" => {
+                    let size = args
+                        .next()
+                        .ok_or_else(|| Error::Usage("--size needs a value".to_owned()))?;
+                    parsed.size = parse_size(&size, 1).ok_or_else(|| {
+                        Error::Usage(format!("invalid size {size}, expected e.g. 64x32"))
+                    })?;
+                }
+                "--dedup" => parsed.dedup = true,
+                _ if arg.starts_with('-') => {
+                    return Err(Error::Usage(format!("unknown option {arg}")))
+                }
+                _ => paths.push(PathBuf::from(arg)),
+            }
+        }
+
+        if parsed.help {
+            return Ok(parsed);
+        }
+
+        match <[PathBuf; 2]>::try_from(paths) {
+            Ok([script, output]) => {
+                parsed.script = script;
+                parsed.output = output;
+            }
+            Err(_) => return Err(Error::Usage("expected a SCRIPT and an OUTPUT".to_owned())),
+        }
+
+        Ok(parsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command> {
+        Command::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn export() {
+        let command = parse(&["export", "a.tarsila", "--scale", "4", "a.png", "--frames"]);
+
+        assert_eq!(
+            command.unwrap(),
+            Command::Export(ExportArgs {
+                project: "a.tarsila".into(),
+                output: "a.png".into(),
+                scale: 4,
+                frames: true,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn run() {
+        let command = parse(&["run", "--size", "16x8", "a.json", "a.tarsila", "--dedup"]);
+
+        assert_eq!(
+            command.unwrap(),
+            Command::Run(RunArgs {
+                script: "a.json".into(),
+                output: "a.tarsila".into(),
+                size: Size::new(16, 8),
+                dedup: true,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_args() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["import"]).is_err());
+        assert!(parse(&["export", "a.tarsila"]).is_err());
+        assert!(parse(&["export", "a.tarsila", "a.png", "b.png"]).is_err());
+        assert!(parse(&["export", "a.tarsila", "a.png", "--scale", "0"]).is_err());
+        assert!(parse(&["export", "a.tarsila", "a.png", "--nope"]).is_err());
+        assert!(parse(&["run", "a.json"]).is_err());
+        assert!(parse(&["run", "a.json", "a.png", "--size", "0x8"]).is_err());
+    }
+}
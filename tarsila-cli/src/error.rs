@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{0}")]
+    Usage(String),
+    #[error("failed to read {}: {source}", path.display())]
+    Read { path: PathBuf, source: lapix::Error },
+    #[error("failed to read {}: {message}", path.display())]
+    Script { path: PathBuf, message: String },
+    #[error("failed to write {}: {source}", path.display())]
+    Write {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    #[error("failed to write {}: {source}", path.display())]
+    WriteProject { path: PathBuf, source: lapix::Error },
+    #[error("{failed} of {total} events failed")]
+    Events { failed: usize, total: usize },
+}
+
+impl Error {
+    /// The exit code of the program when it fails with this error
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Usage(_) => 2,
+            Self::Read { .. } | Self::Script { .. } => 3,
+            Self::Write { .. } | Self::WriteProject { .. } => 4,
+            Self::Events { .. } => 5,
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use crate::args::ExportArgs;
+use crate::{Error, Result};
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use lapix::{RgbaBitmap, Size, State};
+use std::path::{Path, PathBuf};
+
+/// Export a project as described by the arguments, returning the paths of the
+/// files written
+pub fn export(args: &ExportArgs) -> Result<Vec<PathBuf>> {
+    let format = output_format(&args.output, args.format.as_deref())?;
+    let state = load_project(&args.project)?;
+    let layers = state.layers();
+
+    let mut images: Vec<(String, RgbaImage)> = if args.layers {
+        (0..layers.count())
+            .map(|i| (format!("_layer{i}"), layers.canvas_at(i).inner().0.clone()))
+            .collect()
+    } else {
+        vec![(String::new(), layers.blended().0)]
+    };
+
+    if args.frames {
+        images = images
+            .into_iter()
+            .flat_map(|(suffix, img)| {
+                frames(&img, state.spritesheet())
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(i, frame)| (format!("{suffix}_frame{i}"), frame))
+            })
+            .collect();
+    }
+
+    let mut written = Vec::new();
+
+    for (suffix, img) in images {
+        let img = scale(&img, args.scale);
+        let path = output_path(&args.output, &suffix);
+        save(img, &path, format)?;
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+fn load_project(path: &Path) -> Result<State<RgbaBitmap>> {
+    let read_error = |source| Error::Read {
+        path: path.to_owned(),
+        source,
+    };
+
+    let bytes = std::fs::read(path).map_err(|e| read_error(e.into()))?;
+    let mut state = State::<RgbaBitmap>::new(Size::new(1, 1));
+    state.load_project_bytes(&bytes).map_err(read_error)?;
+
+    Ok(state)
+}
+
+/// The format given with `--format`, or else by the extension of the output
+pub fn output_format(output: &Path, format: Option<&str>) -> Result<ImageFormat> {
+    let format = match format {
+        Some(name) => ImageFormat::from_extension(name),
+        None => ImageFormat::from_path(output).ok(),
+    };
+
+    match format {
+        Some(format) if format.writing_enabled() => Ok(format),
+        Some(format) => Err(Error::Usage(format!(
+            "cannot write images in the {format:?} format"
+        ))),
+        None => Err(Error::Usage(format!(
+            "unknown image format for {}, use --format",
+            output.display()
+        ))),
+    }
+}
+
+/// Split an image in the frames of a spritesheet with a number of columns and
+/// rows, in reading order
+fn frames(img: &RgbaImage, spritesheet: Size<u8>) -> Vec<RgbaImage> {
+    let (cols, rows) = (spritesheet.x as u32, spritesheet.y as u32);
+    let (w, h) = (img.width() / cols, img.height() / rows);
+
+    (0..rows)
+        .flat_map(|row| (0..cols).map(move |col| (col, row)))
+        .map(|(col, row)| imageops::crop_imm(img, col * w, row * h, w, h).to_image())
+        .collect()
+}
+
+fn scale(img: &RgbaImage, factor: u32) -> RgbaImage {
+    if factor == 1 {
+        return img.clone();
+    }
+
+    imageops::resize(
+        img,
+        img.width() * factor,
+        img.height() * factor,
+        FilterType::Nearest,
+    )
+}
+
+/// Add a suffix to the name of the output file, before the extension
+fn output_path(output: &Path, suffix: &str) -> PathBuf {
+    if suffix.is_empty() {
+        return output.to_owned();
+    }
+
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match output.extension() {
+        Some(ext) => format!("{stem}{suffix}.{}", ext.to_string_lossy()),
+        None => format!("{stem}{suffix}"),
+    };
+
+    output.with_file_name(name)
+}
+
+pub fn save(img: RgbaImage, path: &Path, format: ImageFormat) -> Result<()> {
+    // Some formats (e.g. JPEG) have no transparency
+    let img = match format {
+        ImageFormat::Jpeg | ImageFormat::Pnm => {
+            DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(img).into_rgb8())
+        }
+        _ => DynamicImage::ImageRgba8(img),
+    };
+
+    img.save_with_format(path, format)
+        .map_err(|source| Error::Write {
+            path: path.to_owned(),
+            source,
+        })
+}
@@ -0,0 +1,47 @@
+//! Command-line tools for Tarsila projects, for when there is no window, e.g.
+//! in build pipelines
+
+mod args;
+mod error;
+mod export;
+mod run;
+
+use args::{Command, EXPORT_USAGE, RUN_USAGE, USAGE};
+use error::{Error, Result};
+use std::process::ExitCode;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("ERROR: {e}");
+            if let Error::Usage(_) = e {
+                eprintln!("Run `tarsila-cli --help` for more information");
+            }
+
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+fn run() -> Result<()> {
+    match Command::parse(std::env::args().skip(1))? {
+        Command::Help => println!("{USAGE}"),
+        Command::Version => println!("tarsila-cli {VERSION}"),
+        Command::Export(args) if args.help => println!("{EXPORT_USAGE}"),
+        Command::Export(args) => {
+            for path in export::export(&args)? {
+                println!("{}", path.display());
+            }
+        }
+        Command::Run(args) if args.help => println!("{RUN_USAGE}"),
+        Command::Run(args) => {
+            run::run(&args)?;
+            println!("{}", args.output.display());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use crate::args::RunArgs;
+use crate::export;
+use crate::{Error, Result};
+use lapix::{Event, RgbaBitmap, State};
+use std::path::Path;
+
+/// Run a script of events as described by the arguments and write the result
+/// to the output. Every event that fails is reported, and in that case nothing
+/// is written.
+pub fn run(args: &RunArgs) -> Result<()> {
+    // Check the output before running anything
+    let project = args.output.extension().is_some_and(|ext| ext == "tarsila");
+    let format = if project {
+        None
+    } else {
+        Some(export::output_format(&args.output, None)?)
+    };
+
+    let events = read_script(&args.script)?;
+    let mut state = State::<RgbaBitmap>::new(args.size);
+    state.set_exact(!args.dedup);
+
+    let mut failed = 0;
+    for (i, event) in events.iter().enumerate() {
+        if let Err(e) = state.execute(event.clone()) {
+            eprintln!("event {i} ({event:?}) failed: {e}");
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        return Err(Error::Events {
+            failed,
+            total: events.len(),
+        });
+    }
+
+    match format {
+        Some(format) => export::save(state.layers().blended().0, &args.output, format),
+        None => save_project(&state, &args.output),
+    }
+}
+
+/// Read a list of events, in RON if the file has that extension, or else in
+/// JSON
+fn read_script(path: &Path) -> Result<Vec<Event>> {
+    let script_error = |message: String| Error::Script {
+        path: path.to_owned(),
+        message,
+    };
+
+    let text = std::fs::read_to_string(path).map_err(|e| script_error(e.to_string()))?;
+
+    if path.extension().is_some_and(|ext| ext == "ron") {
+        ron::from_str(&text).map_err(|e| script_error(e.to_string()))
+    } else {
+        serde_json::from_str(&text).map_err(|e| script_error(e.to_string()))
+    }
+}
+
+fn save_project(state: &State<RgbaBitmap>, path: &Path) -> Result<()> {
+    let write_error = |source| Error::WriteProject {
+        path: path.to_owned(),
+        source,
+    };
+
+    let bytes = state.project_bytes().map_err(write_error)?;
+    std::fs::write(path, bytes).map_err(|e| write_error(e.into()))
+}
@@ -0,0 +1,44 @@
+//! Helpers shared by the tests that run the `tarsila-cli` binary
+
+use image::RgbaImage;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A directory for the files written by a test, removed when it is dropped
+/// (at the end of the test)
+pub struct OutDir(PathBuf);
+
+impl Deref for OutDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for OutDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Create an empty output directory for a test
+pub fn out_dir(name: &str) -> OutDir {
+    let dir = std::env::temp_dir().join(format!("tarsila-cli-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    OutDir(dir)
+}
+
+pub fn tarsila_cli(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_tarsila-cli"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+pub fn open(path: &Path) -> RgbaImage {
+    image::open(path).unwrap().into_rgba8()
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use common::{open, out_dir, tarsila_cli};
+use std::path::{Path, PathBuf};
+
+/// A project with three 8x6 layers (the third hidden) and a spritesheet of two
+/// columns
+fn fixture() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../lapix/tests/fixtures/v1.tarsila")
+}
+
+#[test]
+fn export_flattened() {
+    let dir = out_dir("flattened");
+    let out = dir.join("sprite.png");
+
+    let output = tarsila_cli(&["export", fixture().to_str().unwrap(), out.to_str().unwrap()]);
+    assert!(output.status.success());
+
+    let img = open(&out);
+    assert_eq!(img.dimensions(), (8, 6));
+    // The black line of the first layer, under the red of the second one
+    assert_eq!(img.get_pixel(0, 0).0[3], 255);
+    assert_ne!(img.get_pixel(0, 0), img.get_pixel(0, 5));
+}
+
+#[test]
+fn export_scaled() {
+    let dir = out_dir("scaled");
+    let out = dir.join("sprite.bmp");
+    let project = fixture();
+
+    let output = tarsila_cli(&[
+        "export",
+        project.to_str().unwrap(),
+        out.to_str().unwrap(),
+        "--scale",
+        "3",
+    ]);
+    assert!(output.status.success());
+
+    let img = open(&out);
+    assert_eq!(img.dimensions(), (24, 18));
+    assert_eq!(img.get_pixel(0, 0), img.get_pixel(2, 2));
+}
+
+#[test]
+fn export_layers_and_frames() {
+    let dir = out_dir("layers");
+    let out = dir.join("sprite.png");
+    let project = fixture();
+
+    let output = tarsila_cli(&[
+        "export",
+        "--layers",
+        "--frames",
+        project.to_str().unwrap(),
+        out.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+
+    for layer in 0..3 {
+        for frame in 0..2 {
+            let path = dir.join(format!("sprite_layer{layer}_frame{frame}.png"));
+            assert_eq!(open(&path).dimensions(), (4, 6), "{}", path.display());
+        }
+    }
+
+    // The second layer is filled with red
+    let red = open(&dir.join("sprite_layer1_frame1.png"));
+    assert_eq!(red.get_pixel(3, 3).0, [255, 0, 0, 255]);
+}
+
+#[test]
+fn exit_codes() {
+    let dir = out_dir("exit_codes");
+    let project = fixture();
+    let project = project.to_str().unwrap();
+    let out = dir.join("sprite.png");
+    let out = out.to_str().unwrap();
+    let missing_dir = dir.join("missing/sprite.png");
+
+    let code = |args: &[&str]| tarsila_cli(args).status.code();
+
+    assert_eq!(code(&["--help"]), Some(0));
+    assert_eq!(code(&["export", project]), Some(2));
+    assert_eq!(code(&["export", project, "sprite.unknown"]), Some(2));
+    assert_eq!(code(&["export", "missing.tarsila", out]), Some(3));
+    assert_eq!(code(&["export", out, out]), Some(3));
+
+    // A spritesheet that does not split the canvas in frames
+    let mut bytes = std::fs::read(fixture()).unwrap();
+    let i = bytes.len() - 6;
+    bytes[i] = 0;
+    let bad_project = dir.join("bad.tarsila");
+    std::fs::write(&bad_project, bytes).unwrap();
+    let bad_project = bad_project.to_str().unwrap();
+    assert_eq!(code(&["export", bad_project, out, "--frames"]), Some(3));
+    assert_eq!(
+        code(&["export", project, missing_dir.to_str().unwrap()]),
+        Some(4)
+    );
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use common::{open, out_dir, tarsila_cli};
+use image::Rgba;
+use std::path::{Path, PathBuf};
+
+const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+const TRANSPARENT: Rgba<u8> = Rgba([0, 0, 0, 0]);
+
+fn script(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/scripts")
+        .join(name)
+}
+
+#[test]
+fn run_exactly_as_written() {
+    let dir = out_dir("run-exact");
+    let out = dir.join("line.png");
+    let script = script("restarted_line.ron");
+
+    let output = tarsila_cli(&[
+        "run",
+        script.to_str().unwrap(),
+        out.to_str().unwrap(),
+        "--size",
+        "8x4",
+    ]);
+    assert!(output.status.success());
+
+    let img = open(&out);
+    assert_eq!(img.dimensions(), (8, 4));
+    assert_eq!(*img.get_pixel(4, 3), RED);
+    assert_eq!(*img.get_pixel(0, 0), TRANSPARENT);
+}
+
+#[test]
+fn run_with_dedup() {
+    let dir = out_dir("run-dedup");
+    let out = dir.join("line.png");
+    let script = script("restarted_line.ron");
+
+    let output = tarsila_cli(&[
+        "run",
+        script.to_str().unwrap(),
+        out.to_str().unwrap(),
+        "--size",
+        "8x4",
+        "--dedup",
+    ]);
+    assert!(output.status.success());
+
+    let img = open(&out);
+    assert_eq!(*img.get_pixel(0, 0), RED);
+    assert_eq!(*img.get_pixel(4, 3), TRANSPARENT);
+}
+
+#[test]
+fn run_to_project() {
+    let dir = out_dir("run-project");
+    let project = dir.join("bucket.tarsila");
+    let png = dir.join("bucket.png");
+    let script = script("bucket.json");
+
+    let output = tarsila_cli(&["run", script.to_str().unwrap(), project.to_str().unwrap()]);
+    assert!(output.status.success());
+
+    let output = tarsila_cli(&[
+        "export",
+        project.to_str().unwrap(),
+        png.to_str().unwrap(),
+        "--layers",
+    ]);
+    assert!(output.status.success());
+
+    let filled = open(&dir.join("bucket_layer0.png"));
+    assert_eq!(filled.dimensions(), (64, 64));
+    assert_eq!(*filled.get_pixel(63, 63), Rgba([0, 0, 0, 255]));
+    assert_eq!(
+        *open(&dir.join("bucket_layer1.png")).get_pixel(0, 0),
+        TRANSPARENT
+    );
+}
+
+#[test]
+fn failed_events_are_reported() {
+    let dir = out_dir("run-failed");
+    let out = dir.join("line.png");
+    let script = script("line_not_started.json");
+
+    let output = tarsila_cli(&["run", script.to_str().unwrap(), out.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(5));
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("event 1 (LineEnd"));
+    assert!(stderr.contains("1 of 2 events failed"));
+    assert!(!out.exists());
+}
+
+#[test]
+fn bad_indices_are_reported() {
+    let dir = out_dir("run-bad-indices");
+    let out = dir.join("out.png");
+    let script = script("bad_indices.json");
+
+    let output = tarsila_cli(&["run", script.to_str().unwrap(), out.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(5));
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("event 0 (MoveLayerDown(0)) failed"));
+    assert!(stderr.contains("event 1 (DeleteLayer(3)) failed"));
+    assert!(stderr.contains("event 2 (ChangeLayerOpacity(4, 10)) failed"));
+    assert!(stderr.contains("event 3 (SetSpritesheet("));
+    assert!(stderr.contains("4 of 4 events failed"));
+    assert!(!out.exists());
+}
@@ -0,0 +1,307 @@
+use crate::wrapped_image::WrappedImage;
+use crate::Result;
+use directories::ProjectDirs;
+use lapix::State;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Each running instance autosaves to its own subdirectory of this one, named
+/// after its process ID
+const SESSIONS_DIR: &str = "sessions";
+/// Holds the process ID of the instance while it is running. If it is found
+/// and that process is gone, the session did not end cleanly.
+const SESSION_FILE: &str = "session.lock";
+const TMP_FILE: &str = "autosave.tmp";
+/// The latest autosave of a crashed session, moved aside so that autosaving
+/// does not rotate it away before it is restored
+const RECOVERY_FILE: &str = "recovered.tarsila";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutosaveConfig {
+    pub enabled: bool,
+    /// Time between autosaves, in seconds
+    pub interval_secs: u64,
+    /// How many autosaves to keep, the oldest ones are deleted
+    pub backups: usize,
+}
+
+impl Default for AutosaveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 120,
+            backups: 5,
+        }
+    }
+}
+
+/// Periodically saves the project to the state directory (e.g.
+/// `~/.local/state/tarsila/sessions/<pid>`), keeping a few older autosaves
+/// around
+pub struct Autosave {
+    config: AutosaveConfig,
+    /// The directory of this session
+    dir: Option<PathBuf>,
+    last_save: Instant,
+    last_hash: Option<u64>,
+}
+
+impl Default for Autosave {
+    fn default() -> Self {
+        Self::new(AutosaveConfig::default())
+    }
+}
+
+impl Autosave {
+    pub fn new(config: AutosaveConfig) -> Self {
+        let dir = ProjectDirs::from("", "", "tarsila").map(|dirs| {
+            let state = dirs.state_dir().unwrap_or(dirs.data_local_dir());
+            state
+                .join(SESSIONS_DIR)
+                .join(std::process::id().to_string())
+        });
+
+        Self {
+            config,
+            dir,
+            last_save: Instant::now(),
+            last_hash: None,
+        }
+    }
+
+    pub fn config(&self) -> AutosaveConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: AutosaveConfig) {
+        self.config = config;
+    }
+
+    /// Start the session, taking over the directory of a previous one that is
+    /// no longer running, so that its autosaves are kept. Sessions that did not
+    /// end cleanly are taken over first, and their latest autosave is
+    /// returned, so that it can be restored. Sessions of other running
+    /// instances are left alone.
+    pub fn start(&mut self) -> Result<Option<PathBuf>> {
+        let Some(dir) = &self.dir else {
+            return Ok(None);
+        };
+        let Some(sessions_dir) = dir.parent() else {
+            return Ok(None);
+        };
+
+        std::fs::create_dir_all(sessions_dir)?;
+
+        let mut crashed = Vec::new();
+        let mut finished = Vec::new();
+
+        for entry in std::fs::read_dir(sessions_dir)? {
+            let path = entry?.path();
+
+            if !path.is_dir() {
+                continue;
+            }
+
+            match session_status(&path) {
+                SessionStatus::Running => (),
+                SessionStatus::Crashed => crashed.push(path),
+                SessionStatus::Finished => finished.push(path),
+            }
+        }
+
+        // Only one crashed session is restored at a time, the others are
+        // offered the next time tarsila starts. One left by an older process
+        // with the same ID goes first, as its directory is the one reused.
+        crashed.sort_by_key(|path| (path == dir, last_modified(path)));
+        finished.sort_by_key(|path| (path == dir, last_modified(path)));
+        let recovered = crashed.pop();
+        let previous = recovered.clone().or_else(|| finished.pop());
+
+        // The session is set up before touching the others, which another
+        // instance starting at the same time may be taking over too
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(dir.join(SESSION_FILE), std::process::id().to_string())?;
+
+        for path in finished.iter().filter(|path| *path != dir) {
+            if let Err(e) = std::fs::remove_dir_all(path) {
+                eprintln!("ERROR: Failed to remove {}: {e}", path.display());
+            }
+        }
+
+        if let Some(previous) = previous.filter(|previous| previous != dir) {
+            if let Err(e) = take_over(&previous, dir) {
+                eprintln!("ERROR: Failed to take over {}: {e}", previous.display());
+            }
+        }
+
+        let latest = dir.join(backup_name(1));
+
+        if recovered.is_none() || !latest.exists() {
+            return Ok(None);
+        }
+
+        let recovery = dir.join(RECOVERY_FILE);
+        std::fs::rename(latest, &recovery)?;
+
+        Ok(Some(recovery))
+    }
+
+    /// Delete the autosave of a crashed session once the user chose whether to
+    /// restore it, so that it is not offered again
+    pub fn discard_recovery(&self) -> Result<()> {
+        if let Some(dir) = &self.dir {
+            let recovery = dir.join(RECOVERY_FILE);
+
+            if recovery.exists() {
+                std::fs::remove_file(recovery)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// End the session cleanly
+    pub fn finish(&self) -> Result<()> {
+        if let Some(dir) = &self.dir {
+            let session = dir.join(SESSION_FILE);
+
+            if session.exists() {
+                std::fs::remove_file(session)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Autosave if enough time has passed and the project changed since the
+    /// last autosave
+    pub fn update(&mut self, state: &State<WrappedImage>) -> Result<()> {
+        let interval = Duration::from_secs(self.config.interval_secs);
+
+        if !self.config.enabled || self.last_save.elapsed() < interval {
+            return Ok(());
+        }
+        self.last_save = Instant::now();
+
+        let Some(dir) = self.dir.clone() else {
+            return Ok(());
+        };
+
+        let bytes = state.project_bytes()?;
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        if self.last_hash == Some(hash) {
+            return Ok(());
+        }
+
+        // Write to a temporary file first so that a crash while saving does
+        // not leave a broken autosave
+        let tmp = dir.join(TMP_FILE);
+        std::fs::write(&tmp, bytes)?;
+        self.rotate(&dir)?;
+        std::fs::rename(tmp, dir.join(backup_name(1)))?;
+        self.last_hash = Some(hash);
+
+        Ok(())
+    }
+
+    /// Shift the autosaves, so that the newest is 2, the one before it is 3,
+    /// etc. and delete the ones beyond the number of backups
+    fn rotate(&self, dir: &Path) -> Result<()> {
+        let backups = self.config.backups.max(1);
+        let mut last = backups;
+
+        while dir.join(backup_name(last + 1)).exists() {
+            last += 1;
+        }
+
+        for index in (1..=last).rev() {
+            let path = dir.join(backup_name(index));
+
+            if !path.exists() {
+                continue;
+            }
+
+            if index >= backups {
+                std::fs::remove_file(path)?;
+            } else {
+                std::fs::rename(path, dir.join(backup_name(index + 1)))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+enum SessionStatus {
+    Running,
+    Crashed,
+    Finished,
+}
+
+fn session_status(dir: &Path) -> SessionStatus {
+    let Ok(text) = std::fs::read_to_string(dir.join(SESSION_FILE)) else {
+        return SessionStatus::Finished;
+    };
+
+    match text.trim().parse() {
+        // A lock with the ID of this process was left by an older process that
+        // had the same ID
+        Ok(pid) if pid != std::process::id() && is_running(pid) => SessionStatus::Running,
+        _ => SessionStatus::Crashed,
+    }
+}
+
+/// Whether a process with this ID is running. Where this cannot be checked,
+/// the process is assumed to have exited.
+fn is_running(pid: u32) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        Path::new("/proc").join(pid.to_string()).exists()
+    }
+
+    #[cfg(all(unix, not(target_os = "linux")))]
+    {
+        std::process::Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = pid;
+        false
+    }
+}
+
+/// Move the autosaves of a previous session into the directory of this one,
+/// removing the directory of the previous session
+fn take_over(previous: &Path, dir: &Path) -> Result<()> {
+    for entry in std::fs::read_dir(previous)? {
+        let entry = entry?;
+
+        if entry.file_name() != SESSION_FILE {
+            std::fs::rename(entry.path(), dir.join(entry.file_name()))?;
+        }
+    }
+
+    std::fs::remove_dir_all(previous)?;
+
+    Ok(())
+}
+
+fn last_modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn backup_name(index: usize) -> String {
+    format!("autosave.{index}.tarsila")
+}
@@ -0,0 +1,123 @@
+use crate::{Error, Result};
+use lapix::{parse_size, Size};
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: tarsila [OPTIONS] [FILE]
+
+Open FILE, either a project (.tarsila) or an image (e.g. .png), or start a new
+project if there is none.
+
+Options:
+  --new WxH            Start a new project with a canvas of W by H pixels
+  --palette FILE       Load the palette from an image
+  --spritesheet CxR    Split the canvas in C columns and R rows of frames
+  -h, --help           Print this help
+  -V, --version        Print the version";
+
+/// Command-line arguments of tarsila
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    pub file: Option<PathBuf>,
+    pub new: Option<Size<i32>>,
+    pub palette: Option<PathBuf>,
+    pub spritesheet: Option<Size<u8>>,
+    pub help: bool,
+    pub version: bool,
+}
+
+impl Args {
+    /// Parse the arguments, not including the name of the program
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| Error::InvalidArgument(format!("{name} needs a value")))
+            };
+
+            match arg.as_str() {
+                "-h" | "--help" => parsed.help = true,
+                "-V" | "--version" => parsed.version = true,
+                "--new" => {
+                    let size = value("--new")?;
+                    parsed.new = Some(parse_size(&size, 1).ok_or_else(|| invalid_size(&size))?);
+                }
+                "--palette" => parsed.palette = Some(value("--palette")?.into()),
+                "--spritesheet" => {
+                    let size = value("--spritesheet")?;
+                    let spritesheet = parse_size(&size, 1).ok_or_else(|| invalid_size(&size))?;
+                    parsed.spritesheet = Some(spritesheet);
+                }
+                _ if arg.starts_with('-') => {
+                    return Err(Error::InvalidArgument(format!("unknown option {arg}")))
+                }
+                _ if parsed.file.is_some() => {
+                    return Err(Error::InvalidArgument(format!("unexpected argument {arg}")))
+                }
+                _ => parsed.file = Some(arg.into()),
+            }
+        }
+
+        if parsed.file.is_some() && parsed.new.is_some() {
+            return Err(Error::InvalidArgument(
+                "--new cannot be used when opening a file".to_owned(),
+            ));
+        }
+
+        Ok(parsed)
+    }
+}
+
+fn invalid_size(s: &str) -> Error {
+    Error::InvalidArgument(format!("invalid size {s}, expected e.g. 64x32"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args> {
+        Args::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn file_and_options() {
+        let args = parse(&[
+            "--palette",
+            "pal.png",
+            "sprite.tarsila",
+            "--spritesheet",
+            "4x2",
+        ]);
+
+        assert_eq!(
+            args.unwrap(),
+            Args {
+                file: Some("sprite.tarsila".into()),
+                palette: Some("pal.png".into()),
+                spritesheet: Some(Size::new(4, 2)),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn new_canvas() {
+        let args = parse(&["--new", "32x16"]).unwrap();
+        assert_eq!(args.new, Some(Size::new(32, 16)));
+    }
+
+    #[test]
+    fn invalid_args() {
+        assert!(parse(&["--new", "32"]).is_err());
+        assert!(parse(&["--new", "0x16"]).is_err());
+        assert!(parse(&["--new"]).is_err());
+        assert!(parse(&["--spritesheet", "300x1"]).is_err());
+        assert!(parse(&["--nope"]).is_err());
+        assert!(parse(&["a.png", "b.png"]).is_err());
+        assert!(parse(&["--new", "8x8", "a.png"]).is_err());
+    }
+}
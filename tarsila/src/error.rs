@@ -1,10 +1,33 @@
+use crate::input::bindings::Conflict;
 use lapix::Error as LapixError;
+use std::path::PathBuf;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("Image error: {0}")]
+    #[error("{0}")]
     LapixError(#[from] LapixError),
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error(
+        "Failed to read settings (the file was backed up to {}): {0}",
+        .1.display()
+    )]
+    SettingsRead(toml::de::Error, PathBuf),
+    #[error("Failed to write settings: {0}")]
+    SettingsWrite(#[from] toml::ser::Error),
+    #[error("Failed to read key bindings: {0}")]
+    KeyBindingsRead(toml::de::Error),
+    #[error("Failed to write key bindings: {0}")]
+    KeyBindingsWrite(toml::ser::Error),
+    #[error("Failed to read macros: {0}")]
+    MacrosRead(toml::de::Error),
+    #[error("Failed to write macros: {0}")]
+    MacrosWrite(toml::ser::Error),
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("Key binding conflict: {0}")]
+    KeyBindingConflict(Conflict),
 }
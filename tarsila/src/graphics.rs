@@ -3,13 +3,22 @@ use crate::UiState;
 use egui_macroquad::macroquad::prelude::Color as MqColor;
 use egui_macroquad::macroquad::prelude::*;
 use lapix::graphics;
-use lapix::{Bitmap, FreeImage, Point, Position, Rect, Selection, Size};
+use lapix::{Bitmap, FreeImage, Point, Position, Size, Symmetry, SymmetryMode};
+use std::f32::consts::TAU;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 const DASHED_LINE_SEGMENT: f32 = 5.;
 const DASHED_LINE_ANIMATION_MS: u128 = 250;
 const SPRSHEET_LINE_THICKNESS: f32 = 1.;
 const SPRSHEET_LINE_COLOR: MqColor = BLACK;
+const SYMMETRY_AXIS_THICKNESS: f32 = 1.;
+const SYMMETRY_AXIS_COLOR: MqColor = MqColor::new(0.2, 0.4, 1., 0.8);
+/// Size in screen pixels of the handles of the control points of a Bezier
+/// curve
+pub const BEZIER_HANDLE_SIZE: f32 = 6.;
+
+/// A line between two points of the canvas
+pub type Segment = (Point<i32>, Point<i32>);
 
 #[derive(Debug, Copy, Clone)]
 pub struct DrawContext {
@@ -18,7 +27,22 @@ pub struct DrawContext {
     pub canvas_pos: Position<f32>,
     pub camera: Position<f32>,
     pub canvas_size: Size<f32>,
-    pub selection: Option<Selection>,
+}
+
+impl DrawContext {
+    /// The context to draw a copy of the canvas `i` canvases to the right and
+    /// `j` canvases below it, as shown in tile mode
+    pub fn tile(self, i: i32, j: i32) -> Self {
+        let offset = Position::new(
+            i as f32 * self.canvas_size.x * self.scale,
+            j as f32 * self.canvas_size.y * self.scale,
+        );
+
+        Self {
+            canvas_pos: self.canvas_pos + offset,
+            ..self
+        }
+    }
 }
 
 pub fn draw_texture_helper(texture: &Texture2D, p: Position<f32>, scale: f32) {
@@ -62,13 +86,6 @@ pub fn draw_animated_dashed_line(p1: Point<i32>, p2: Point<i32>) {
     }
 }
 
-pub fn draw_animated_dashed_rect(rect: Rect<i32>) {
-    draw_animated_dashed_line(rect.pos(), rect.top_right());
-    draw_animated_dashed_line(rect.pos(), rect.bottom_left());
-    draw_animated_dashed_line(rect.top_right(), rect.pos() + rect.size());
-    draw_animated_dashed_line(rect.bottom_left(), rect.pos() + rect.size());
-}
-
 pub fn draw_free_image(
     ctx: DrawContext,
     img: &FreeImage<WrappedImage>,
@@ -93,21 +110,87 @@ pub fn draw_free_image(
     egui_macroquad::macroquad::prelude::draw_texture_ex(free_image_tex, x, y, color.into(), params);
 }
 
-pub fn draw_selection(ctx: DrawContext, free_image: Option<&FreeImage<WrappedImage>>) {
-    let rect = match ctx.selection {
-        Some(Selection::FreeImage) => free_image.unwrap().rect,
-        Some(Selection::Canvas(rect)) => rect,
-        _ => return,
+/// Draw the outline of the selection, made of the segments along the edges of
+/// the selected pixels, moved by an offset (the position of the free image, if
+/// it is what is selected)
+pub fn draw_selection(ctx: DrawContext, outline: &[Segment], offset: Point<i32>) {
+    let p0 = ctx.canvas_pos - ctx.camera;
+    let to_screen = |p: Point<i32>| {
+        let p = p + offset;
+        Point::new(
+            (p0.x + p.x as f32 * ctx.scale) as i32,
+            (p0.y + p.y as f32 * ctx.scale) as i32,
+        )
     };
+    for (a, b) in outline {
+        draw_animated_dashed_line(to_screen(*a), to_screen(*b));
+    }
+}
 
+/// Draw the path of a lasso selection being made, closed by a line back to
+/// its first point
+pub fn draw_lasso(ctx: DrawContext, path: &[Point<i32>]) {
     let p0 = ctx.canvas_pos - ctx.camera;
-    let r = Rect {
-        x: (p0.x + rect.x as f32 * ctx.scale) as i32,
-        y: (p0.y + rect.y as f32 * ctx.scale) as i32,
-        w: (rect.w as f32 * ctx.scale) as i32,
-        h: (rect.h as f32 * ctx.scale) as i32,
+    let center = |p: Point<i32>| {
+        Point::new(
+            (p0.x + (p.x as f32 + 0.5) * ctx.scale) as i32,
+            (p0.y + (p.y as f32 + 0.5) * ctx.scale) as i32,
+        )
     };
-    draw_animated_dashed_rect(r);
+
+    let points: Vec<_> = path.iter().map(|p| center(*p)).collect();
+    for (a, b) in points.iter().zip(points.iter().cycle().skip(1)) {
+        draw_animated_dashed_line(*a, *b);
+    }
+}
+
+/// Draw the pixels of the canvas that the brush would paint, skipping the ones
+/// out of its bounds
+pub fn draw_brush_preview(ctx: DrawContext, points: &[Point<i32>], color: MqColor) {
+    let p0 = ctx.canvas_pos - ctx.camera;
+    let (w, h) = (ctx.canvas_size.x as i32, ctx.canvas_size.y as i32);
+
+    for q in points {
+        if q.x < 0 || q.y < 0 || q.x >= w || q.y >= h {
+            continue;
+        }
+
+        draw_rectangle(
+            p0.x + q.x as f32 * ctx.scale,
+            p0.y + q.y as f32 * ctx.scale,
+            ctx.scale,
+            ctx.scale,
+            color,
+        );
+    }
+}
+
+/// Draw the control points of a cubic Bezier curve (its ends and the two
+/// points between them) as handles, each control point connected to the
+/// nearest end
+pub fn draw_bezier_handles(ctx: DrawContext, points: &[Point<i32>]) {
+    let p0 = ctx.canvas_pos - ctx.camera;
+    let center = |p: Point<i32>| {
+        Vec2::new(
+            p0.x + (p.x as f32 + 0.5) * ctx.scale,
+            p0.y + (p.y as f32 + 0.5) * ctx.scale,
+        )
+    };
+
+    if let [start, c1, c2, end] = points {
+        for (a, b) in [(start, c1), (end, c2)] {
+            let (a, b) = (center(*a), center(*b));
+            draw_line(a.x, a.y, b.x, b.y, 1., DARKGRAY);
+        }
+    }
+
+    let half = BEZIER_HANDLE_SIZE / 2.;
+    for p in points {
+        let c = center(*p);
+        let (x, y) = (c.x - half, c.y - half);
+        draw_rectangle(x, y, BEZIER_HANDLE_SIZE, BEZIER_HANDLE_SIZE, WHITE);
+        draw_rectangle_lines(x, y, BEZIER_HANDLE_SIZE, BEZIER_HANDLE_SIZE, 1., BLACK);
+    }
 }
 
 pub fn draw_spritesheet_boundaries(ctx: DrawContext) {
@@ -131,7 +214,7 @@ pub fn draw_spritesheet_boundaries(ctx: DrawContext) {
     }
 }
 
-pub fn draw_canvas(state: &UiState) {
+pub fn draw_canvas(state: &UiState, ctx: DrawContext) {
     for i in 0..state.num_layers() {
         if !state.layer(i).visible() {
             continue;
@@ -139,8 +222,8 @@ pub fn draw_canvas(state: &UiState) {
 
         let texture = state.layer_tex(i);
         let size = Size::new(texture.width(), texture.height());
-        let p = state.canvas_pos() - state.camera();
-        let scale = state.zoom();
+        let p = ctx.canvas_pos - ctx.camera;
+        let scale = ctx.scale;
 
         let params = DrawTextureParams {
             dest_size: Some(Vec2 {
@@ -160,3 +243,47 @@ pub fn draw_canvas(state: &UiState) {
         );
     }
 }
+
+/// Draw the axes of the [`Symmetry`] over the canvas: the lines across which
+/// the drawing is mirrored, or the rays between the rotated copies
+pub fn draw_symmetry_axes(ctx: DrawContext, symmetry: Symmetry) {
+    let p0 = ctx.canvas_pos - ctx.camera;
+    let (w, h) = (ctx.canvas_size.x * ctx.scale, ctx.canvas_size.y * ctx.scale);
+    let axis = Vec2::new(
+        p0.x + symmetry.axis.x * ctx.scale,
+        p0.y + symmetry.axis.y * ctx.scale,
+    );
+    let line = |x1: f32, y1: f32, x2: f32, y2: f32| {
+        draw_line(x1, y1, x2, y2, SYMMETRY_AXIS_THICKNESS, SYMMETRY_AXIS_COLOR);
+    };
+
+    match symmetry.mode {
+        SymmetryMode::Off => (),
+        SymmetryMode::Horizontal => line(axis.x, p0.y, axis.x, p0.y + h),
+        SymmetryMode::Vertical => line(p0.x, axis.y, p0.x + w, axis.y),
+        SymmetryMode::Both => {
+            line(axis.x, p0.y, axis.x, p0.y + h);
+            line(p0.x, axis.y, p0.x + w, axis.y);
+        }
+        SymmetryMode::Radial(copies) => {
+            for i in 0..copies {
+                // From the center up, then around, until the edge of the canvas
+                let angle = TAU * i as f32 / copies as f32 - TAU / 4.;
+                let dir = Vec2::new(angle.cos(), angle.sin());
+                let reach = |d: f32, from: f32, start: f32, len: f32| {
+                    if d > f32::EPSILON {
+                        (start + len - from) / d
+                    } else if d < -f32::EPSILON {
+                        (start - from) / d
+                    } else {
+                        f32::INFINITY
+                    }
+                };
+                let t = reach(dir.x, axis.x, p0.x, w).min(reach(dir.y, axis.y, p0.y, h));
+                if t.is_finite() && t > 0. {
+                    line(axis.x, axis.y, axis.x + dir.x * t, axis.y + dir.y * t);
+                }
+            }
+        }
+    }
+}
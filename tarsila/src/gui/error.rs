@@ -0,0 +1,36 @@
+use egui_macroquad::egui;
+
+/// Shows the errors that happened until the user dismisses them
+pub struct ErrorWindow {
+    errors: Vec<String>,
+}
+
+impl ErrorWindow {
+    pub fn new() -> Self {
+        Self { errors: Vec::new() }
+    }
+
+    pub fn push(&mut self, error: String) {
+        if !self.errors.contains(&error) {
+            self.errors.push(error);
+        }
+    }
+
+    pub fn update(&mut self, egui_ctx: &egui::Context) {
+        if self.errors.is_empty() {
+            return;
+        }
+
+        egui::Window::new("Error")
+            .default_pos((200., 30.))
+            .collapsible(false)
+            .show(egui_ctx, |ui| {
+                for error in self.errors.iter() {
+                    ui.label(error);
+                }
+                if ui.button("Ok").clicked() {
+                    self.errors.clear();
+                }
+            });
+    }
+}
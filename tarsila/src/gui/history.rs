@@ -0,0 +1,62 @@
+use lapix::Event;
+use {crate::Effect, egui_macroquad::egui};
+
+pub struct HistoryPanel {
+    labels: Vec<String>,
+    position: usize,
+}
+
+impl HistoryPanel {
+    pub fn new() -> Self {
+        Self {
+            labels: Vec::new(),
+            position: 0,
+        }
+    }
+
+    pub fn sync(&mut self, labels: Vec<String>, position: usize) {
+        self.labels = labels;
+        self.position = position;
+    }
+
+    pub fn update(&mut self, egui_ctx: &egui::Context) -> Vec<Effect> {
+        let mut events = Vec::new();
+
+        egui::Window::new("History")
+            .default_open(false)
+            .show(egui_ctx, |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(200.)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        let entries = std::iter::once("Initial state")
+                            .chain(self.labels.iter().map(|label| label.as_str()));
+
+                        for (i, label) in entries.enumerate() {
+                            // Actions after the current position were undone
+                            // and can still be redone
+                            let text = if i > self.position {
+                                egui::RichText::new(label).weak()
+                            } else {
+                                egui::RichText::new(label)
+                            };
+                            let tooltip = if i > self.position {
+                                "redo up to this point"
+                            } else {
+                                "undo up to this point"
+                            };
+
+                            if ui
+                                .selectable_label(i == self.position, text)
+                                .on_hover_text(tooltip)
+                                .clicked()
+                            {
+                                events.push(Event::GoToHistory(i).into());
+                            }
+                        }
+                    });
+            });
+
+        events
+    }
+}
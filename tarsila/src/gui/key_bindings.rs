@@ -0,0 +1,137 @@
+use crate::input::bindings::{Binding, KeySpec};
+use crate::input::{Action, KeyBindings};
+use crate::{Effect, UiEvent};
+use egui_macroquad::egui;
+
+/// An action with the keys bound to it as they are being edited
+struct Row {
+    action: Action,
+    keys: String,
+    error: Option<String>,
+}
+
+impl Row {
+    fn new(binding: &Binding) -> Self {
+        let keys: Vec<_> = binding.keys.iter().map(|k| k.to_string()).collect();
+
+        Self {
+            action: binding.action.clone(),
+            keys: keys.join(", "),
+            error: None,
+        }
+    }
+
+    fn parse(&mut self) -> Option<Binding> {
+        let keys: Result<Vec<KeySpec>, _> = self
+            .keys
+            .split(',')
+            .map(str::trim)
+            .filter(|k| !k.is_empty())
+            .map(str::parse)
+            .collect();
+
+        match keys {
+            Ok(keys) => {
+                self.error = None;
+                Some(Binding {
+                    keys,
+                    action: self.action.clone(),
+                })
+            }
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+}
+
+pub struct KeyBindingsWindow {
+    /// The bindings being edited, if the window is open
+    rows: Option<Vec<Row>>,
+}
+
+impl KeyBindingsWindow {
+    pub fn new() -> Self {
+        Self { rows: None }
+    }
+
+    pub fn open(&mut self, key_bindings: &KeyBindings) {
+        self.rows = Some(key_bindings.bindings().iter().map(Row::new).collect());
+    }
+
+    pub fn update(&mut self, egui_ctx: &egui::Context) -> Vec<Effect> {
+        let mut events = Vec::new();
+
+        let Some(rows) = &mut self.rows else {
+            return events;
+        };
+
+        let mut close = false;
+        let mut reset = false;
+
+        // Parse all of them (instead of stopping at the first error) so that
+        // every row shows its error
+        let bindings: Vec<_> = rows.iter_mut().map(Row::parse).collect();
+        let draft = bindings
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .map(KeyBindings::from);
+
+        egui::Window::new("Key Bindings")
+            .default_pos((200., 30.))
+            .show(egui_ctx, |ui| {
+                ui.label("Separate the keys of a shortcut with +, e.g. Ctrl+Shift+Z");
+                ui.label("and different shortcuts with commas");
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .max_height(400.)
+                    .show(ui, |ui| {
+                        egui::Grid::new("key_bindings_grid").show(ui, |ui| {
+                            for row in rows.iter_mut() {
+                                ui.label(row.action.to_string());
+                                ui.text_edit_singleline(&mut row.keys);
+                                if let Some(error) = &row.error {
+                                    ui.colored_label(egui::Color32::DARK_RED, error);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    });
+
+                if let Some(draft) = &draft {
+                    for conflict in draft.conflicts() {
+                        ui.colored_label(egui::Color32::DARK_RED, conflict.to_string());
+                    }
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    let ok = ui.add_enabled(draft.is_some(), egui::Button::new("Ok"));
+                    if ok.clicked() {
+                        if let Some(draft) = &draft {
+                            events.push(UiEvent::UpdateKeyBindings(draft.clone()).into());
+                        }
+                        close = true;
+                    }
+                    if ui.button("Reset to defaults").clicked() {
+                        reset = true;
+                    }
+                    if ui.button("cancel").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        if reset {
+            self.rows = Some(KeyBindings::new().bindings().iter().map(Row::new).collect());
+        }
+
+        if close {
+            self.rows = None;
+        }
+
+        events
+    }
+}
@@ -37,11 +37,19 @@ impl LayersPanel {
         egui::Window::new("Layers")
             //    .default_pos((15., 410.))
             .show(egui_ctx, |ui| {
-                let btn = ui.button("+");
-                if btn.clicked() {
-                    events.push(Event::NewLayerAbove.into());
-                    events.push(Event::SwitchLayer(self.num_layers).into());
-                }
+                ui.horizontal(|ui| {
+                    let btn = ui.button("+").on_hover_text("new layer on top");
+                    if btn.clicked() {
+                        events.push(Event::NewLayerAbove.into());
+                        events.push(Event::SwitchLayer(self.num_layers).into());
+                    }
+                    let btn = ui
+                        .button("+ below")
+                        .on_hover_text("new layer below the active one");
+                    if btn.clicked() {
+                        events.push(Event::NewLayerBelow.into());
+                    }
+                });
 
                 ui.horizontal(|ui| {
                     ui.label("#");
@@ -0,0 +1,68 @@
+use crate::macros::Macro;
+use crate::{Effect, UiEvent};
+use egui_macroquad::egui;
+use lapix::Event;
+
+/// Asks for the name of a macro that was just recorded
+pub struct MacroWindow {
+    events: Option<Vec<Event>>,
+    name: String,
+}
+
+impl MacroWindow {
+    pub fn new() -> Self {
+        Self {
+            events: None,
+            name: String::new(),
+        }
+    }
+
+    pub fn open(&mut self, events: Vec<Event>) {
+        self.events = Some(events);
+        self.name.clear();
+    }
+
+    pub fn update(&mut self, egui_ctx: &egui::Context) -> Vec<Effect> {
+        let mut events = Vec::new();
+
+        let Some(recorded) = &self.events else {
+            return events;
+        };
+
+        let mut close = false;
+
+        egui::Window::new("Save Macro")
+            .default_pos((200., 30.))
+            .collapsible(false)
+            .show(egui_ctx, |ui| {
+                ui.label(format!("{} events recorded", recorded.len()));
+                ui.horizontal(|ui| {
+                    let label = ui.label("name:");
+                    ui.text_edit_singleline(&mut self.name)
+                        .labelled_by(label.id);
+                });
+
+                ui.horizontal(|ui| {
+                    let name = self.name.trim();
+                    let save = ui.add_enabled(!name.is_empty(), egui::Button::new("Save"));
+                    if save.clicked() {
+                        let new = Macro {
+                            name: name.to_owned(),
+                            events: recorded.clone(),
+                        };
+                        events.push(UiEvent::SaveMacro(new).into());
+                        close = true;
+                    }
+                    if ui.button("Discard").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        if close {
+            self.events = None;
+        }
+
+        events
+    }
+}
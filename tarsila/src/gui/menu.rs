@@ -1,5 +1,9 @@
 use std::{path::PathBuf, sync::Arc};
 use {
+    super::key_bindings::KeyBindingsWindow,
+    crate::autosave::AutosaveConfig,
+    crate::input::{Action, KeyBindings},
+    crate::settings::Settings,
     crate::{Effect, UiEvent},
     egui_macroquad::egui,
 };
@@ -9,13 +13,25 @@ use {
 };
 
 pub struct MenuBar {
-    last_file: Option<PathBuf>,
     show_resize_window: bool,
     show_spritesheet_window: bool,
     show_confirm_exit_window: bool,
     show_confirm_new_window: bool,
+    show_autosave_window: bool,
+    show_preferences_window: bool,
     canvas_size: Size<i32>,
     spritesheet: Size<u8>,
+    settings: Settings,
+    key_bindings: KeyBindings,
+    macros: Vec<String>,
+    recording: bool,
+    tile_mode: bool,
+    key_bindings_window: KeyBindingsWindow,
+    /// Autosave settings being edited in the autosave window
+    autosave_draft: Option<AutosaveConfig>,
+    /// Settings being edited in the preferences window. Only the ones shown in
+    /// it are applied, so that it does not undo changes made elsewhere
+    preferences_draft: Option<Settings>,
     canvas_size_str: Option<(String, String)>,
     spritesheet_str: Option<(String, String)>,
     file_dialog: FileDialog,
@@ -32,13 +48,22 @@ enum FileOp {
 impl MenuBar {
     pub fn new() -> Self {
         Self {
-            last_file: None,
             show_resize_window: false,
             show_spritesheet_window: false,
             show_confirm_exit_window: false,
             show_confirm_new_window: false,
+            show_autosave_window: false,
+            show_preferences_window: false,
             canvas_size: Size::ZERO,
             spritesheet: (1, 1).into(),
+            settings: Settings::default(),
+            key_bindings: KeyBindings::default(),
+            macros: Vec::new(),
+            recording: false,
+            tile_mode: false,
+            key_bindings_window: KeyBindingsWindow::new(),
+            autosave_draft: None,
+            preferences_draft: None,
             canvas_size_str: None,
             spritesheet_str: None,
             file_dialog: FileDialog::new()
@@ -57,9 +82,39 @@ impl MenuBar {
         }
     }
 
-    pub fn sync(&mut self, canvas_size: Size<i32>, spritesheet: Size<u8>) {
+    pub fn show_resize_window(&mut self) {
+        self.show_resize_window = true;
+    }
+
+    pub fn show_spritesheet_window(&mut self) {
+        self.show_spritesheet_window = true;
+    }
+
+    pub fn sync(
+        &mut self,
+        canvas_size: Size<i32>,
+        spritesheet: Size<u8>,
+        settings: Settings,
+        key_bindings: KeyBindings,
+        macros: Vec<String>,
+        recording: bool,
+        tile_mode: bool,
+    ) {
         self.canvas_size = canvas_size;
         self.spritesheet = spritesheet;
+        self.settings = settings;
+        self.key_bindings = key_bindings;
+        self.macros = macros;
+        self.recording = recording;
+        self.tile_mode = tile_mode;
+    }
+
+    /// The label of a menu item, with the first shortcut of its action
+    fn label(&self, text: &str, action: &Action) -> String {
+        match self.key_bindings.keys(action).first() {
+            Some(keys) => format!("{text} ({keys})"),
+            None => text.to_owned(),
+        }
     }
 
     pub fn update(&mut self, egui_ctx: &egui::Context) -> Vec<Effect> {
@@ -68,23 +123,23 @@ impl MenuBar {
         events.append(&mut self.update_spritesheet_window(egui_ctx));
         events.append(&mut self.update_confirm_exit_window(egui_ctx));
         events.append(&mut self.update_confirm_new_window(egui_ctx));
+        events.append(&mut self.update_autosave_window(egui_ctx));
+        events.append(&mut self.update_preferences_window(egui_ctx));
+        events.append(&mut self.key_bindings_window.update(egui_ctx));
         self.file_dialog.update(egui_ctx);
         if let Some(path) = self.file_dialog.take_picked() {
+            let mut settings = self.settings.clone();
             match self.file_op {
-                FileOp::SaveProject => {
-                    self.last_file = Some(path.clone());
-                    events.push(Event::SaveProject(path).into());
-                }
-                FileOp::LoadProject => {
-                    self.last_file = Some(path.clone());
-                    events.push(Event::LoadProject(path).into());
-                }
-                FileOp::ExportImage => {
-                    self.last_file = Some(path.clone());
-                    events.push(Event::Save(path).into());
-                }
+                FileOp::SaveProject => settings.used_project(&path),
+                // The project is added to the recent ones once it is loaded
+                _ => settings.used_file(&path),
+            }
+            events.push(UiEvent::UpdateSettings(settings).into());
+            match self.file_op {
+                FileOp::SaveProject => events.push(Event::SaveProject(path).into()),
+                FileOp::LoadProject => events.push(UiEvent::LoadProject(path).into()),
+                FileOp::ExportImage => events.push(Event::Save(path).into()),
                 FileOp::ImportImage => {
-                    self.last_file = Some(path.clone());
                     events.push(Event::OpenFile(path).into());
                     events.push(Event::SetTool(Tool::Move).into());
                 }
@@ -93,6 +148,22 @@ impl MenuBar {
         events
     }
 
+    fn open_file_dialog(&mut self, op: FileOp, filter: &str) {
+        let save = matches!(op, FileOp::SaveProject | FileOp::ExportImage);
+        self.file_op = op;
+        self.file_dialog.config_mut().default_file_filter = Some(filter.into());
+
+        if let Some(dir) = &self.settings.last_dir {
+            self.file_dialog.config_mut().initial_directory = dir.clone();
+        }
+
+        if save {
+            self.file_dialog.save_file();
+        } else {
+            self.file_dialog.pick_file();
+        }
+    }
+
     fn update_menu(&mut self, egui_ctx: &egui::Context) -> Vec<Effect> {
         let mut events = Vec::new();
 
@@ -105,52 +176,54 @@ impl MenuBar {
                     }
                     if ui.button("Save Project").clicked() {
                         ui.close_menu();
-                        self.file_op = FileOp::SaveProject;
-                        self.file_dialog.config_mut().default_file_filter =
-                            Some("Tarsila Files".into());
-
-                        if let Some(dir) = self.last_file.as_ref().and_then(|p| p.parent()) {
-                            self.file_dialog.config_mut().initial_directory = dir.to_path_buf();
-                        }
-                        self.file_dialog.save_file();
+                        self.open_file_dialog(FileOp::SaveProject, "Tarsila Files");
                     }
                     if ui.button("Load Project").clicked() {
                         ui.close_menu();
-                        self.file_op = FileOp::LoadProject;
-                        self.file_dialog.config_mut().default_file_filter =
-                            Some("Tarsila Files".into());
-                        if let Some(dir) = self.last_file.as_ref().and_then(|p| p.parent()) {
-                            self.file_dialog.config_mut().initial_directory = dir.to_path_buf();
-                        }
-                        self.file_dialog.pick_file();
+                        self.open_file_dialog(FileOp::LoadProject, "Tarsila Files");
                     }
+                    ui.add_enabled_ui(!self.settings.recent_projects.is_empty(), |ui| {
+                        ui.menu_button("Open Recent", |ui| {
+                            if let Some(path) = self.update_recent_menu(ui) {
+                                events.push(UiEvent::LoadProject(path).into());
+                            }
+                        });
+                    });
                     if ui.button("Export Image").clicked() {
                         ui.close_menu();
-                        self.file_op = FileOp::ExportImage;
-                        self.file_dialog.config_mut().default_file_filter =
-                            Some("Image Files".into());
-
-                        if let Some(dir) = self.last_file.as_ref().and_then(|p| p.parent()) {
-                            self.file_dialog.config_mut().initial_directory = dir.to_path_buf();
-                        }
-                        self.file_dialog.save_file();
+                        self.open_file_dialog(FileOp::ExportImage, "Image Files");
                     }
                     if ui.button("Import Image").clicked() {
                         ui.close_menu();
-                        self.file_op = FileOp::ImportImage;
-                        self.file_dialog.config_mut().default_file_filter =
-                            Some("Image Files".into());
-
-                        if let Some(dir) = self.last_file.as_ref().and_then(|p| p.parent()) {
-                            self.file_dialog.config_mut().initial_directory = dir.to_path_buf();
-                        }
-                        self.file_dialog.pick_file();
+                        self.open_file_dialog(FileOp::ImportImage, "Image Files");
+                    }
+                    if ui.button("Autosave").clicked() {
+                        self.show_autosave_window = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Preferences").clicked() {
+                        self.show_preferences_window = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Key Bindings").clicked() {
+                        self.key_bindings_window.open(&self.key_bindings);
+                        ui.close_menu();
                     }
                     if ui.button("Exit").clicked() {
                         self.show_confirm_exit_window = true;
                         ui.close_menu();
                     }
                 });
+                ui.menu_button("Edit", |ui| {
+                    if ui.button(self.label("Undo", &Action::Undo)).clicked() {
+                        events.push(Event::Undo.into());
+                        ui.close_menu();
+                    }
+                    if ui.button(self.label("Redo", &Action::Redo)).clicked() {
+                        events.push(Event::Redo.into());
+                        ui.close_menu();
+                    }
+                });
                 ui.menu_button("View", |ui| {
                     if ui.button("Zoom in").clicked() {
                         events.push(Effect::UiEvent(UiEvent::ZoomIn));
@@ -168,6 +241,25 @@ impl MenuBar {
                         events.push(Effect::UiEvent(UiEvent::SetZoom100));
                         ui.close_menu();
                     }
+                    let mut tile_mode = self.tile_mode;
+                    let label = self.label("Tile mode", &Action::ToggleTileMode);
+                    if ui.checkbox(&mut tile_mode, label).changed() {
+                        events.push(Event::SetTileMode(tile_mode).into());
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    let mut panels = self.settings.panels;
+                    ui.checkbox(&mut panels.toolbox, "Toolbox");
+                    ui.checkbox(&mut panels.palette, "Palette");
+                    ui.checkbox(&mut panels.layers, "Layers");
+                    ui.checkbox(&mut panels.preview, "Preview");
+                    ui.checkbox(&mut panels.history, "History");
+                    ui.checkbox(&mut panels.status_bar, "Status bar");
+                    if panels != self.settings.panels {
+                        let mut settings = self.settings.clone();
+                        settings.panels = panels;
+                        events.push(UiEvent::UpdateSettings(settings).into());
+                    }
                 });
                 ui.menu_button("Canvas", |ui| {
                     /*
@@ -190,6 +282,36 @@ impl MenuBar {
                         events.push(Event::ClearCanvas.into());
                     }
                 });
+                ui.menu_button("Macros", |ui| {
+                    if self.recording {
+                        if ui.button("Stop Recording").clicked() {
+                            events.push(UiEvent::StopRecording.into());
+                            ui.close_menu();
+                        }
+                    } else if ui.button("Start Recording").clicked() {
+                        events.push(UiEvent::StartRecording.into());
+                        ui.close_menu();
+                    }
+                    if !self.macros.is_empty() {
+                        ui.separator();
+                    }
+                    for (i, name) in self.macros.iter().enumerate() {
+                        ui.menu_button(name, |ui| {
+                            if ui.button("Replay").clicked() {
+                                events.push(UiEvent::ReplayMacro(i).into());
+                                ui.close_menu();
+                            }
+                            if ui.button("Place (click on the canvas)").clicked() {
+                                events.push(UiEvent::PlaceMacro(i).into());
+                                ui.close_menu();
+                            }
+                            if ui.button("Delete").clicked() {
+                                events.push(UiEvent::DeleteMacro(i).into());
+                                ui.close_menu();
+                            }
+                        });
+                    }
+                });
                 ui.menu_button("Transform", |ui| {
                     if ui.button("Silhouete").clicked() {
                         ui.close_menu();
@@ -313,6 +435,123 @@ impl MenuBar {
         events
     }
 
+    fn update_autosave_window(&mut self, egui_ctx: &egui::Context) -> Vec<Effect> {
+        let mut events = Vec::new();
+
+        if !self.show_autosave_window {
+            return events;
+        }
+
+        let config = self.autosave_draft.get_or_insert(self.settings.autosave);
+
+        egui::Window::new("Autosave")
+            .default_pos((200., 30.))
+            .show(egui_ctx, |ui| {
+                ui.checkbox(&mut config.enabled, "enabled");
+                ui.horizontal(|ui| {
+                    ui.label("interval (s):");
+                    ui.add(egui::DragValue::new(&mut config.interval_secs).range(10..=3600));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("backups:");
+                    ui.add(egui::DragValue::new(&mut config.backups).range(1..=50));
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Ok").clicked() {
+                        let mut settings = self.settings.clone();
+                        settings.autosave = *config;
+                        events.push(UiEvent::UpdateSettings(settings).into());
+                        self.show_autosave_window = false;
+                    }
+                    if ui.button("cancel").clicked() {
+                        self.show_autosave_window = false;
+                    }
+                });
+            });
+
+        if !self.show_autosave_window {
+            self.autosave_draft = None;
+        }
+
+        events
+    }
+
+    fn update_preferences_window(&mut self, egui_ctx: &egui::Context) -> Vec<Effect> {
+        let mut events = Vec::new();
+
+        if !self.show_preferences_window {
+            return events;
+        }
+
+        let draft = self.preferences_draft.get_or_insert(self.settings.clone());
+
+        egui::Window::new("Preferences")
+            .default_pos((200., 30.))
+            .show(egui_ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("new canvas size:");
+                    ui.add(egui::DragValue::new(&mut draft.canvas_size.x).range(1..=4096));
+                    ui.label("x");
+                    ui.add(egui::DragValue::new(&mut draft.canvas_size.y).range(1..=4096));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("default zoom:");
+                    ui.add(
+                        egui::DragValue::new(&mut draft.zoom)
+                            .range(1.0..=64.0)
+                            .speed(0.1),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("camera speed:");
+                    ui.add(
+                        egui::DragValue::new(&mut draft.camera_speed)
+                            .range(1.0..=100.0)
+                            .speed(0.5),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Ok").clicked() {
+                        let mut settings = self.settings.clone();
+                        settings.canvas_size = draft.canvas_size;
+                        settings.zoom = draft.zoom;
+                        settings.camera_speed = draft.camera_speed;
+                        events.push(UiEvent::UpdateSettings(settings).into());
+                        self.show_preferences_window = false;
+                    }
+                    if ui.button("cancel").clicked() {
+                        self.show_preferences_window = false;
+                    }
+                });
+            });
+
+        if !self.show_preferences_window {
+            self.preferences_draft = None;
+        }
+
+        events
+    }
+
+    /// List the recent projects, returning the one that was clicked
+    fn update_recent_menu(&self, ui: &mut egui::Ui) -> Option<PathBuf> {
+        let mut picked = None;
+
+        for path in &self.settings.recent_projects {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.display().to_string());
+
+            let button = ui.button(name).on_hover_text(path.display().to_string());
+            if button.clicked() {
+                picked = Some(path.clone());
+                ui.close_menu();
+            }
+        }
+
+        picked
+    }
+
     fn update_confirm_exit_window(&mut self, egui_ctx: &egui::Context) -> Vec<Effect> {
         let mut events = Vec::new();
 
@@ -1,27 +1,44 @@
 use egui_macroquad::macroquad::prelude::*;
-use lapix::{Position, Size, Tool};
+use lapix::{Brush, BucketOptions, Event, Position, ShapeOptions, Size, Symmetry, Tool};
+use std::path::PathBuf;
 use {
+    crate::input::KeyBindings,
+    crate::settings::{PanelVisibility, Settings},
     crate::{Effect, UiEvent, UiState},
     egui_macroquad::{egui, EguiMqInteg},
 };
 
+mod error;
+mod history;
+mod key_bindings;
 mod layers;
+mod macros;
 mod menu;
 mod palette;
 mod preview;
+mod recovery;
 mod status;
 mod toolbar;
 
+use error::ErrorWindow;
+use history::HistoryPanel;
 use layers::LayersPanel;
+use macros::MacroWindow;
 use menu::MenuBar;
 use palette::Palette;
 use preview::Preview;
+use recovery::RecoveryWindow;
 use status::StatusBar;
 use toolbar::Toolbar;
 
 #[derive(Debug, Clone)]
 pub struct GuiSyncParams {
     pub main_color: [u8; 4],
+    pub brush: Brush,
+    pub shape_options: ShapeOptions,
+    pub bucket_options: BucketOptions,
+    pub symmetry: Symmetry,
+    pub tile_mode: bool,
     pub num_layers: usize,
     pub active_layer: usize,
     pub layers_vis: Vec<bool>,
@@ -35,15 +52,26 @@ pub struct GuiSyncParams {
     pub spritesheet: Size<u8>,
     pub zoom: f32,
     pub fps: f32,
+    pub history: Vec<String>,
+    pub history_position: usize,
+    pub settings: Settings,
+    pub key_bindings: KeyBindings,
+    pub macros: Vec<String>,
+    pub recording: bool,
 }
 
 pub struct Gui {
     toolbar: Toolbar,
     layers_panel: LayersPanel,
+    history_panel: HistoryPanel,
+    error_window: ErrorWindow,
+    recovery_window: RecoveryWindow,
+    macro_window: MacroWindow,
     preview: Preview,
     palette: Palette,
     status_bar: StatusBar,
     menu: MenuBar,
+    panels: PanelVisibility,
     mouse_on_canvas: bool,
     selected_tool: Tool,
     pub egui_mq: EguiMqInteg,
@@ -54,10 +82,15 @@ impl Gui {
         Self {
             toolbar: Toolbar::new(),
             layers_panel: LayersPanel::new(),
+            history_panel: HistoryPanel::new(),
+            error_window: ErrorWindow::new(),
+            recovery_window: RecoveryWindow::new(),
+            macro_window: MacroWindow::new(),
             preview: Preview::new(),
             palette: Palette::new(),
             status_bar: StatusBar::new(),
             menu: MenuBar::new(),
+            panels: PanelVisibility::default(),
             mouse_on_canvas: false,
             selected_tool: Tool::Brush,
             egui_mq: EguiMqInteg::new(),
@@ -66,8 +99,16 @@ impl Gui {
 
     pub fn sync(&mut self, params: GuiSyncParams) {
         self.mouse_on_canvas = params.is_on_canvas;
-
-        self.toolbar.sync(params.main_color);
+        self.panels = params.settings.panels;
+
+        self.toolbar.sync(
+            params.main_color,
+            params.brush.clone(),
+            params.bucket_options,
+            params.shape_options,
+            params.symmetry,
+            params.canvas_size,
+        );
         self.selected_tool = params.selected_tool;
         self.layers_panel.sync(
             params.num_layers,
@@ -82,10 +123,41 @@ impl Gui {
             params.layers_alpha.clone(),
         );
         self.palette.sync(params.palette.clone());
-        self.menu.sync(params.canvas_size, params.spritesheet);
+        self.history_panel
+            .sync(params.history.clone(), params.history_position);
+        self.menu.sync(
+            params.canvas_size,
+            params.spritesheet,
+            params.settings.clone(),
+            params.key_bindings.clone(),
+            params.macros.clone(),
+            params.recording,
+            params.tile_mode,
+        );
         self.status_bar.sync(params);
     }
 
+    pub fn show_error(&mut self, error: String) {
+        self.error_window.push(error);
+    }
+
+    pub fn offer_recovery(&mut self, autosave: PathBuf) {
+        self.recovery_window.offer(autosave);
+    }
+
+    pub fn show_resize_window(&mut self) {
+        self.menu.show_resize_window();
+    }
+
+    pub fn show_spritesheet_window(&mut self) {
+        self.menu.show_spritesheet_window();
+    }
+
+    /// Ask for the name of a macro that was just recorded
+    pub fn name_macro(&mut self, events: Vec<Event>) {
+        self.macro_window.open(events);
+    }
+
     pub fn update(&mut self) -> Vec<Effect> {
         let mut events = Vec::new();
 
@@ -116,20 +188,42 @@ impl Gui {
             visuals.override_text_color = text_color;
             egui_ctx.set_visuals(visuals);
 
-            let mut palette_events = self.palette.update(egui_ctx);
-            events.append(&mut palette_events);
+            if self.panels.palette {
+                let mut palette_events = self.palette.update(egui_ctx);
+                events.append(&mut palette_events);
+            }
 
-            let mut toolbar_events = self.toolbar.update(egui_ctx, self.selected_tool);
-            events.append(&mut toolbar_events);
+            if self.panels.toolbox {
+                let mut toolbar_events = self.toolbar.update(egui_ctx, self.selected_tool);
+                events.append(&mut toolbar_events);
+            }
 
-            let mut layers_events = self.layers_panel.update(egui_ctx);
-            events.append(&mut layers_events);
+            if self.panels.layers {
+                let mut layers_events = self.layers_panel.update(egui_ctx);
+                events.append(&mut layers_events);
+            }
+
+            if self.panels.history {
+                let mut history_events = self.history_panel.update(egui_ctx);
+                events.append(&mut history_events);
+            }
 
             let mut menu_events = self.menu.update(egui_ctx);
             events.append(&mut menu_events);
 
-            self.preview.update(egui_ctx);
-            self.status_bar.update(egui_ctx);
+            let mut recovery_events = self.recovery_window.update(egui_ctx);
+            events.append(&mut recovery_events);
+
+            let mut macro_events = self.macro_window.update(egui_ctx);
+            events.append(&mut macro_events);
+
+            if self.panels.preview {
+                self.preview.update(egui_ctx);
+            }
+            if self.panels.status_bar {
+                self.status_bar.update(egui_ctx);
+            }
+            self.error_window.update(egui_ctx);
 
             let mut canvas_panel_events = self.update_canvas_panel(egui_ctx);
             events.append(&mut canvas_panel_events);
@@ -144,7 +238,9 @@ impl Gui {
     }
 
     pub fn draw_preview(&self, state: &UiState) {
-        self.preview.draw(state);
+        if self.panels.preview {
+            self.preview.draw(state);
+        }
     }
 
     fn update_canvas_panel(&mut self, egui_ctx: &egui::Context) -> Vec<Effect> {
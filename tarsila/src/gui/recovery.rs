@@ -0,0 +1,52 @@
+use lapix::Event;
+use std::path::PathBuf;
+use {
+    crate::{Effect, UiEvent},
+    egui_macroquad::egui,
+};
+
+/// Offers to restore the latest autosave after tarsila did not exit cleanly
+pub struct RecoveryWindow {
+    autosave: Option<PathBuf>,
+}
+
+impl RecoveryWindow {
+    pub fn new() -> Self {
+        Self { autosave: None }
+    }
+
+    pub fn offer(&mut self, autosave: PathBuf) {
+        self.autosave = Some(autosave);
+    }
+
+    pub fn update(&mut self, egui_ctx: &egui::Context) -> Vec<Effect> {
+        let mut events = Vec::new();
+
+        let Some(path) = self.autosave.clone() else {
+            return events;
+        };
+
+        egui::Window::new("Recover Project")
+            .default_pos((200., 30.))
+            .collapsible(false)
+            .show(egui_ctx, |ui| {
+                ui.label(
+                    "Tarsila did not exit properly last time. \
+                    Do you want to restore the latest autosave?",
+                );
+                ui.horizontal(|ui| {
+                    if ui.button("Restore").clicked() {
+                        events.push(Event::LoadProject(path).into());
+                        events.push(UiEvent::DiscardRecovery.into());
+                        self.autosave = None;
+                    }
+                    if ui.button("Discard").clicked() {
+                        events.push(UiEvent::DiscardRecovery.into());
+                        self.autosave = None;
+                    }
+                });
+            });
+
+        events
+    }
+}
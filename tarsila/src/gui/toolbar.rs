@@ -1,29 +1,46 @@
 use egui_macroquad::egui::Color32;
 use egui_macroquad::macroquad::prelude::*;
-use lapix::{Event, Size, Tool};
+use lapix::{
+    Brush, BrushShape, BucketOptions, Event, ShapeOptions, ShapeStyle, Size, Symmetry,
+    SymmetryMode, Tool,
+};
 use std::collections::HashMap;
 use {
-    crate::{util, Effect, Resources},
+    crate::{util, Effect, Resources, UiEvent},
     egui_macroquad::egui,
 };
 
 const TOOL_BTN_IMG_SIZE: Size<usize> = Size { x: 16, y: 16 };
-const TOOLS: [Tool; 9] = [
+const DEFAULT_RADIAL_COPIES: u8 = 6;
+const TOOLS: [Tool; 16] = [
     Tool::Brush,
     Tool::Bucket,
     Tool::Eraser,
     Tool::Eyedropper,
     Tool::Line,
     Tool::Selection,
+    Tool::EllipseSelection,
+    Tool::Lasso,
+    Tool::PolygonLasso,
+    Tool::MagicWand,
     Tool::Move,
     Tool::Rectangle,
     Tool::Ellipse,
+    Tool::Polyline,
+    Tool::Polygon,
+    Tool::Bezier,
 ];
 
 pub struct Toolbar {
     tools: HashMap<Tool, ToolButton>,
     brush: [u8; 3],
     brush_alpha: String,
+    /// Size and shape of the brush (`brush` is its color)
+    footprint: Brush,
+    bucket: BucketOptions,
+    shape: ShapeOptions,
+    symmetry: Symmetry,
+    canvas_size: Size<i32>,
 }
 
 impl Toolbar {
@@ -32,12 +49,30 @@ impl Toolbar {
             tools: TOOLS.iter().map(|t| (*t, ToolButton::new(*t))).collect(),
             brush: [0, 0, 0],
             brush_alpha: "255".to_owned(),
+            footprint: Brush::default(),
+            bucket: BucketOptions::default(),
+            shape: ShapeOptions::default(),
+            symmetry: Symmetry::default(),
+            canvas_size: Size::ZERO,
         }
     }
 
-    pub fn sync(&mut self, main_color: [u8; 4]) {
+    pub fn sync(
+        &mut self,
+        main_color: [u8; 4],
+        footprint: Brush,
+        bucket: BucketOptions,
+        shape: ShapeOptions,
+        symmetry: Symmetry,
+        canvas_size: Size<i32>,
+    ) {
         self.brush = util::rgba_to_rgb_u8(main_color);
         self.brush_alpha = main_color[3].to_string();
+        self.footprint = footprint;
+        self.bucket = bucket;
+        self.shape = shape;
+        self.symmetry = symmetry;
+        self.canvas_size = canvas_size;
     }
 
     pub fn get_mut(&mut self, tool: Tool) -> Option<&mut ToolButton> {
@@ -86,10 +121,180 @@ impl Toolbar {
                         }
                     }
                 });
+
+                if selected_tool.uses_brush() {
+                    ui.separator();
+                    events.extend(self.brush_settings(ui, selected_tool));
+                }
+
+                if matches!(
+                    selected_tool,
+                    Tool::Rectangle | Tool::Ellipse | Tool::Polygon
+                ) {
+                    ui.separator();
+                    if let Some(options) = self.shape_options(ui) {
+                        events.push(Event::SetShapeOptions(options).into());
+                    }
+                }
+
+                // The magic wand selects the area the bucket would fill
+                if matches!(selected_tool, Tool::Bucket | Tool::MagicWand) {
+                    ui.separator();
+                    if let Some(options) = self.bucket_options(ui) {
+                        events.push(Event::SetBucketOptions(options).into());
+                    }
+                }
+
+                if selected_tool.uses_brush() || selected_tool == Tool::Bucket {
+                    ui.separator();
+                    if let Some(symmetry) = self.symmetry_settings(ui) {
+                        events.push(Event::SetSymmetry(symmetry).into());
+                    }
+                }
             });
 
         events
     }
+
+    /// Show the size and shape of the brush, returning the effects of
+    /// changing them
+    fn brush_settings(&mut self, ui: &mut egui::Ui, selected_tool: Tool) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        let mut brush = self.footprint.clone();
+        let is_stamp = matches!(brush.shape, BrushShape::Stamp(_));
+
+        let size_label = if matches!(
+            selected_tool,
+            Tool::Rectangle | Tool::Ellipse | Tool::Polygon
+        ) {
+            "thickness"
+        } else {
+            "size"
+        };
+        ui.add_enabled(
+            !is_stamp,
+            egui::Slider::new(&mut brush.size, 1..=32).text(size_label),
+        );
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut brush.shape, BrushShape::Square, "square");
+            ui.selectable_value(&mut brush.shape, BrushShape::Circle, "circle");
+            if ui
+                .selectable_label(is_stamp, "stamp")
+                .on_hover_text("use the selection as the brush")
+                .clicked()
+            {
+                effects.push(UiEvent::StampFromSelection.into());
+            }
+        });
+        if matches!(selected_tool, Tool::Brush | Tool::Eraser) {
+            ui.checkbox(&mut brush.pixel_perfect, "pixel perfect")
+                .on_hover_text("remove the doubled corners of strokes");
+        }
+
+        if brush != self.footprint {
+            effects.push(Event::SetBrush(brush).into());
+        }
+
+        effects
+    }
+
+    /// Show the options of the tools that draw shapes, returning them if they
+    /// changed
+    fn shape_options(&mut self, ui: &mut egui::Ui) -> Option<ShapeOptions> {
+        let mut options = self.shape;
+        let c = options.fill_color;
+        let mut fill = Color32::from_rgba_unmultiplied(c.r, c.g, c.b, c.a);
+
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut options.style, ShapeStyle::Outline, "outline");
+            ui.selectable_value(&mut options.style, ShapeStyle::Filled, "filled");
+            ui.selectable_value(&mut options.style, ShapeStyle::FilledOutline, "both");
+        });
+        ui.horizontal(|ui| {
+            ui.label("fill:");
+            egui::color_picker::color_edit_button_srgba(
+                ui,
+                &mut fill,
+                egui::color_picker::Alpha::OnlyBlend,
+            );
+        });
+        options.fill_color = fill.to_srgba_unmultiplied().into();
+
+        (options != self.shape).then_some(options)
+    }
+
+    /// Show the symmetry setting of the tools that draw, returning it if it
+    /// changed
+    fn symmetry_settings(&mut self, ui: &mut egui::Ui) -> Option<Symmetry> {
+        let mut symmetry = self.symmetry;
+        let radial = matches!(symmetry.mode, SymmetryMode::Radial(_));
+
+        ui.label("symmetry");
+        ui.horizontal_wrapped(|ui| {
+            ui.set_max_width(160.);
+            ui.selectable_value(&mut symmetry.mode, SymmetryMode::Off, "off");
+            ui.selectable_value(&mut symmetry.mode, SymmetryMode::Horizontal, "horizontal")
+                .on_hover_text("mirror from left to right");
+            ui.selectable_value(&mut symmetry.mode, SymmetryMode::Vertical, "vertical")
+                .on_hover_text("mirror from top to bottom");
+            ui.selectable_value(&mut symmetry.mode, SymmetryMode::Both, "both");
+            if ui
+                .selectable_label(radial, "radial")
+                .on_hover_text("rotate copies around the center")
+                .clicked()
+                && !radial
+            {
+                symmetry.mode = SymmetryMode::Radial(DEFAULT_RADIAL_COPIES);
+            }
+        });
+
+        if let SymmetryMode::Radial(copies) = &mut symmetry.mode {
+            ui.add(egui::Slider::new(copies, 2..=16).text("copies"));
+        }
+
+        let (x_axis, y_axis) = match symmetry.mode {
+            SymmetryMode::Off => (false, false),
+            SymmetryMode::Horizontal => (true, false),
+            SymmetryMode::Vertical => (false, true),
+            SymmetryMode::Both | SymmetryMode::Radial(_) => (true, true),
+        };
+        let size = self.canvas_size;
+        if x_axis {
+            ui.add(
+                egui::Slider::new(&mut symmetry.axis.x, 0.0..=size.x as f32)
+                    .step_by(0.5)
+                    .text("axis x"),
+            );
+        }
+        if y_axis {
+            ui.add(
+                egui::Slider::new(&mut symmetry.axis.y, 0.0..=size.y as f32)
+                    .step_by(0.5)
+                    .text("axis y"),
+            );
+        }
+        if (x_axis || y_axis) && ui.button("center axes").clicked() {
+            symmetry = Symmetry::centered(symmetry.mode, size);
+        }
+
+        (symmetry != self.symmetry).then_some(symmetry)
+    }
+
+    /// Show the options of the bucket tool, returning them if they changed
+    fn bucket_options(&mut self, ui: &mut egui::Ui) -> Option<BucketOptions> {
+        let mut options = self.bucket;
+
+        ui.add(egui::Slider::new(&mut options.tolerance, 0..=100).text("tolerance"))
+            .on_hover_text("how different (in %) a color can be and still be filled");
+        ui.checkbox(&mut options.global, "replace all")
+            .on_hover_text("fill every pixel of the color in the layer, connected or not");
+        ui.checkbox(&mut options.diagonal, "diagonal")
+            .on_hover_text("pixels touching only by a corner are connected too");
+        ui.checkbox(&mut options.sample_merged, "sample all layers")
+            .on_hover_text("find the area to fill in the visible image, not only the active layer");
+
+        (options != self.bucket).then_some(options)
+    }
 }
 
 pub struct ToolButton {
@@ -149,9 +354,16 @@ impl ToolButton {
             Tool::Eyedropper => "eyedropper tool (I)",
             Tool::Line => "line tool (L)",
             Tool::Selection => "selection tool (S)",
+            Tool::EllipseSelection => "ellipse selection tool",
+            Tool::Lasso => "lasso tool (Q)",
+            Tool::PolygonLasso => "polygon lasso tool",
+            Tool::MagicWand => "magic wand tool (W)",
             Tool::Move => "move tool (M)",
             Tool::Rectangle => "rectangle tool (R)",
             Tool::Ellipse => "ellipse tool (O)",
+            Tool::Polyline => "polyline tool (P)",
+            Tool::Polygon => "polygon tool",
+            Tool::Bezier => "bezier curve tool",
         }
     }
 }
@@ -0,0 +1,176 @@
+use super::InputEvent;
+use crate::mouse::CursorType;
+use crate::{Effect, UiEvent};
+use lapix::{Color, Direction, Event, Point, Size, Tool, Transform};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Something that can be bound to keys. Covers the [`Event`]s and
+/// [`UiEvent`]s that make sense as shortcuts: the ones that need a position on
+/// the canvas (e.g. [`Event::Bucket`]) are sent by the tools started with
+/// [`Action::ToolStart`], and the ones that need a file path by the dialogs in
+/// the menu. The layer actions that take no index act on the active layer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Action {
+    // lapix events
+    SetTool(Tool),
+    SetMainColor(Color),
+    ClearCanvas,
+    ResizeCanvas(Size<i32>),
+    NewLayerAbove,
+    NewLayerBelow,
+    SwitchLayer(usize),
+    SetSpritesheet(Size<u8>),
+    ClearSelection,
+    DeleteSelection,
+    Copy,
+    FlipHorizontal,
+    FlipVertical,
+    ApplyTransform(Transform),
+    Undo,
+    Redo,
+    /// Finish the polyline, polygon or Bezier curve being drawn
+    FinishPath,
+    // UI events
+    ZoomIn,
+    ZoomOut,
+    ResetZoom,
+    SetZoom100,
+    ZoomAdd(f32),
+    ZoomMul(f32),
+    MoveCamera(Direction),
+    /// Move the camera along with the mouse
+    MoveCameraWithMouse,
+    Paste,
+    NewProject,
+    Exit,
+    ToolStart,
+    ToolStroke,
+    ToolEnd,
+    ToggleCrosshair,
+    ToggleTileMode,
+    /// Show or hide the active layer
+    ToggleLayerVisibility,
+    /// Move the active layer above the next one
+    MoveLayerUp,
+    /// Move the active layer below the previous one
+    MoveLayerDown,
+    ShowResizeWindow,
+    ShowSpritesheetWindow,
+    /// Block the canvas and show the pan cursor
+    StartPan,
+    /// Undo [`Action::StartPan`]
+    EndPan,
+}
+
+impl Action {
+    /// The effects of this action, given the input events that triggered it
+    pub fn effects(&self, events: &[InputEvent]) -> Vec<Effect> {
+        let event = |e: Event| vec![Effect::Event(e)];
+        let ui_event = |e: UiEvent| vec![Effect::UiEvent(e)];
+
+        match self {
+            Self::SetTool(tool) => event(Event::SetTool(*tool)),
+            Self::SetMainColor(color) => event(Event::SetMainColor(*color)),
+            Self::ClearCanvas => event(Event::ClearCanvas),
+            Self::ResizeCanvas(size) => event(Event::ResizeCanvas(*size)),
+            Self::NewLayerAbove => event(Event::NewLayerAbove),
+            Self::NewLayerBelow => event(Event::NewLayerBelow),
+            Self::SwitchLayer(i) => event(Event::SwitchLayer(*i)),
+            Self::SetSpritesheet(size) => event(Event::SetSpritesheet(*size)),
+            Self::ClearSelection => event(Event::ClearSelection),
+            Self::DeleteSelection => event(Event::DeleteSelection),
+            Self::Copy => event(Event::Copy),
+            Self::FlipHorizontal => event(Event::FlipHorizontal),
+            Self::FlipVertical => event(Event::FlipVertical),
+            Self::ApplyTransform(t) => event(Event::ApplyTransform(t.clone())),
+            Self::Undo => event(Event::Undo),
+            Self::Redo => event(Event::Redo),
+            Self::FinishPath => event(Event::PathEnd),
+            Self::ZoomIn => ui_event(UiEvent::ZoomIn),
+            Self::ZoomOut => ui_event(UiEvent::ZoomOut),
+            Self::ResetZoom => ui_event(UiEvent::ResetZoom),
+            Self::SetZoom100 => ui_event(UiEvent::SetZoom100),
+            Self::ZoomAdd(z) => ui_event(UiEvent::ZoomAdd(*z)),
+            Self::ZoomMul(z) => ui_event(UiEvent::ZoomMul(*z)),
+            Self::MoveCamera(direction) => ui_event(UiEvent::MoveCamera(*direction)),
+            Self::MoveCameraWithMouse => {
+                for event in events {
+                    if let InputEvent::MouseRealMove(delta) = event {
+                        return ui_event(UiEvent::MoveCameraExact(Point::ZERO - *delta));
+                    }
+                }
+
+                Vec::new()
+            }
+            Self::Paste => ui_event(UiEvent::Paste),
+            Self::NewProject => ui_event(UiEvent::NewProject),
+            Self::Exit => ui_event(UiEvent::Exit),
+            Self::ToolStart => ui_event(UiEvent::ToolStart),
+            Self::ToolStroke => ui_event(UiEvent::ToolStroke),
+            Self::ToolEnd => ui_event(UiEvent::ToolEnd),
+            Self::ToggleCrosshair => ui_event(UiEvent::ToggleCursor(CursorType::Cross)),
+            Self::ToggleTileMode => ui_event(UiEvent::ToggleTileMode),
+            Self::ToggleLayerVisibility => ui_event(UiEvent::ToggleLayerVisibility),
+            Self::MoveLayerUp => ui_event(UiEvent::MoveLayerUp),
+            Self::MoveLayerDown => ui_event(UiEvent::MoveLayerDown),
+            Self::ShowResizeWindow => ui_event(UiEvent::ShowResizeWindow),
+            Self::ShowSpritesheetWindow => ui_event(UiEvent::ShowSpritesheetWindow),
+            Self::StartPan => vec![
+                Effect::UiEvent(UiEvent::BlockCanvas),
+                Effect::UiEvent(UiEvent::SetCursor(CursorType::Pan)),
+            ],
+            Self::EndPan => vec![
+                Effect::UiEvent(UiEvent::UnblockCanvas),
+                Effect::UiEvent(UiEvent::SetPreviousCursor),
+            ],
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SetTool(tool) => write!(f, "Tool: {tool:?}"),
+            Self::SetMainColor(c) => write!(f, "Color: #{:02x}{:02x}{:02x}", c.r, c.g, c.b),
+            Self::ClearCanvas => f.write_str("Erase canvas"),
+            Self::ResizeCanvas(size) => write!(f, "Resize canvas to {}x{}", size.x, size.y),
+            Self::NewLayerAbove => f.write_str("New layer above"),
+            Self::NewLayerBelow => f.write_str("New layer below"),
+            Self::SwitchLayer(i) => write!(f, "Switch to layer {}", i + 1),
+            Self::SetSpritesheet(size) => write!(f, "Spritesheet: {}x{}", size.x, size.y),
+            Self::ClearSelection => f.write_str("Clear selection"),
+            Self::DeleteSelection => f.write_str("Delete selection"),
+            Self::Copy => f.write_str("Copy"),
+            Self::FlipHorizontal => f.write_str("Flip horizontally"),
+            Self::FlipVertical => f.write_str("Flip vertically"),
+            Self::ApplyTransform(t) => write!(f, "Transform: {t:?}"),
+            Self::Undo => f.write_str("Undo"),
+            Self::Redo => f.write_str("Redo"),
+            Self::FinishPath => f.write_str("Finish path"),
+            Self::ZoomIn => f.write_str("Zoom in"),
+            Self::ZoomOut => f.write_str("Zoom out"),
+            Self::ResetZoom => f.write_str("Reset zoom"),
+            Self::SetZoom100 => f.write_str("Zoom to 100%"),
+            Self::ZoomAdd(z) => write!(f, "Zoom {z:+}"),
+            Self::ZoomMul(z) => write!(f, "Zoom x{z}"),
+            Self::MoveCamera(direction) => write!(f, "Move camera {direction:?}"),
+            Self::MoveCameraWithMouse => f.write_str("Move camera with mouse"),
+            Self::Paste => f.write_str("Paste"),
+            Self::NewProject => f.write_str("New project"),
+            Self::Exit => f.write_str("Exit"),
+            Self::ToolStart => f.write_str("Start using tool"),
+            Self::ToolStroke => f.write_str("Keep using tool"),
+            Self::ToolEnd => f.write_str("Stop using tool"),
+            Self::ToggleCrosshair => f.write_str("Toggle crosshair cursor"),
+            Self::ToggleTileMode => f.write_str("Toggle tile mode"),
+            Self::ToggleLayerVisibility => f.write_str("Show/hide layer"),
+            Self::MoveLayerUp => f.write_str("Move layer up"),
+            Self::MoveLayerDown => f.write_str("Move layer down"),
+            Self::ShowResizeWindow => f.write_str("Resize canvas..."),
+            Self::ShowSpritesheetWindow => f.write_str("Change spritesheet..."),
+            Self::StartPan => f.write_str("Start panning"),
+            Self::EndPan => f.write_str("Stop panning"),
+        }
+    }
+}
@@ -1,13 +1,122 @@
-use super::{InputEvent, KeyboardKey, KeyboardModifier};
-use crate::mouse::CursorType;
-use crate::{Effect, UiEvent};
+use super::{Action, InputEvent, KeyboardKey, KeyboardModifier, MouseButton};
+use crate::settings::config_file;
+use crate::{Error, Result};
 use egui_macroquad::macroquad::prelude as mq;
-use lapix::{Direction, Event, Point, Tool};
+use lapix::{Direction, Tool};
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
-use std::fmt::Debug;
-use std::hash::Hash;
-
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+use std::fmt;
+use std::str::FromStr;
+
+const BINDINGS_FILE: &str = "keybindings.toml";
+
+/// Keys that can be bound, named as in [`mq::KeyCode`]
+const KEYS: &[mq::KeyCode] = {
+    use mq::KeyCode::*;
+    &[
+        A,
+        B,
+        C,
+        D,
+        E,
+        F,
+        G,
+        H,
+        I,
+        J,
+        K,
+        L,
+        M,
+        N,
+        O,
+        P,
+        Q,
+        R,
+        S,
+        T,
+        U,
+        V,
+        W,
+        X,
+        Y,
+        Z,
+        Key0,
+        Key1,
+        Key2,
+        Key3,
+        Key4,
+        Key5,
+        Key6,
+        Key7,
+        Key8,
+        Key9,
+        F1,
+        F2,
+        F3,
+        F4,
+        F5,
+        F6,
+        F7,
+        F8,
+        F9,
+        F10,
+        F11,
+        F12,
+        Space,
+        Apostrophe,
+        Comma,
+        Minus,
+        Period,
+        Slash,
+        Semicolon,
+        Equal,
+        LeftBracket,
+        Backslash,
+        RightBracket,
+        GraveAccent,
+        Escape,
+        Enter,
+        Tab,
+        Backspace,
+        Insert,
+        Delete,
+        Right,
+        Left,
+        Down,
+        Up,
+        PageUp,
+        PageDown,
+        Home,
+        End,
+        Kp0,
+        Kp1,
+        Kp2,
+        Kp3,
+        Kp4,
+        Kp5,
+        Kp6,
+        Kp7,
+        Kp8,
+        Kp9,
+        KpAdd,
+        KpSubtract,
+    ]
+};
+
+const MODIFIERS: [(KeyboardModifier, &str); 4] = [
+    (KeyboardModifier::Control, "Ctrl"),
+    (KeyboardModifier::Shift, "Shift"),
+    (KeyboardModifier::Alt, "Alt"),
+    (KeyboardModifier::Super, "Super"),
+];
+
+/// The input events that trigger an action. It is written as the names of the
+/// modifiers and keys separated by `+`, e.g. `Ctrl+Shift+Z`. A key or mouse
+/// button is triggered when pressed, unless followed by `:down` (while it is
+/// held down) or `:release`. `MouseMove` triggers on mouse movement, e.g.
+/// `Space:down+MouseLeft:down+MouseMove`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
 pub enum KeySpec {
     InputEvents(Vec<InputEvent>),
     FollowMouse(Vec<InputEvent>),
@@ -49,10 +158,16 @@ impl KeySpec {
 
     // Checks whether the two sets of events have the same keyboard modifier
     fn matches_mod(a: &[InputEvent], b: &[InputEvent]) -> bool {
-        let mut a = a.iter().filter(|e| matches!(e, InputEvent::KeyModifier(_)));
-        let mut b = b.iter().filter(|e| matches!(e, InputEvent::KeyModifier(_)));
-
-        a.all(|e| b.any(|e2| e2 == e)) && b.all(|e| a.any(|e2| e2 == e))
+        let a: Vec<_> = a
+            .iter()
+            .filter(|e| matches!(e, InputEvent::KeyModifier(_)))
+            .collect();
+        let b: Vec<_> = b
+            .iter()
+            .filter(|e| matches!(e, InputEvent::KeyModifier(_)))
+            .collect();
+
+        a.iter().all(|e| b.contains(e)) && b.iter().all(|e| a.contains(e))
     }
 
     fn is_subset(a: &[InputEvent], b: &[InputEvent]) -> bool {
@@ -60,111 +175,243 @@ impl KeySpec {
     }
 }
 
-type MouseAction = Box<dyn Fn(Point<i32>) -> Vec<Effect>>;
+/// Specs are equal when they are triggered by the same input events,
+/// regardless of order
+impl PartialEq for KeySpec {
+    fn eq(&self, other: &Self) -> bool {
+        let (a, b) = (self.iter(), other.iter());
 
-pub enum ActionSpec {
-    Fx(Vec<Effect>),
-    // TODO: by using a closure here, we cannot make this serializable, we
-    // should later just have an enum of possible functions. Problem is that we
-    // need to have the (Ui)Event parameterizable
-    MouseFn(MouseAction),
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+            && Self::is_subset(a, b)
+            && Self::is_subset(b, a)
+    }
 }
 
-impl From<Event> for ActionSpec {
-    fn from(e: Event) -> Self {
-        Self::Fx(vec![Effect::Event(e)])
+impl Eq for KeySpec {}
+
+impl fmt::Display for KeySpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let events = self.iter();
+        let mut names: Vec<String> = MODIFIERS
+            .iter()
+            .filter(|(m, _)| events.contains(&InputEvent::KeyModifier(m.clone())))
+            .map(|(_, name)| name.to_string())
+            .collect();
+
+        for event in events {
+            let name = match event {
+                InputEvent::KeyPress(k) => format!("{:?}", k.0),
+                InputEvent::KeyDown(k) => format!("{:?}:down", k.0),
+                InputEvent::KeyRelease(k) => format!("{:?}:release", k.0),
+                InputEvent::MouseButtonPress(b) => format!("Mouse{:?}", b.0),
+                InputEvent::MouseButtonDown(b) => format!("Mouse{:?}:down", b.0),
+                InputEvent::MouseButtonRelease(b) => format!("Mouse{:?}:release", b.0),
+                InputEvent::MouseScrollUp => "ScrollUp".to_owned(),
+                InputEvent::MouseScrollDown => "ScrollDown".to_owned(),
+                InputEvent::KeyModifier(_)
+                | InputEvent::MouseRealMove(_)
+                | InputEvent::MouseCanvasMove(_) => continue,
+            };
+            names.push(name);
+        }
+
+        if matches!(self, Self::FollowMouse(_)) {
+            names.push("MouseMove".to_owned());
+        }
+
+        f.write_str(&names.join("+"))
     }
 }
 
-impl From<UiEvent> for ActionSpec {
-    fn from(e: UiEvent) -> Self {
-        Self::Fx(vec![Effect::UiEvent(e)])
+impl FromStr for KeySpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut events = Vec::new();
+        let mut follow_mouse = false;
+
+        for token in s.split('+').map(str::trim) {
+            if let Some((m, _)) = MODIFIERS.iter().find(|(_, name)| *name == token) {
+                events.push(InputEvent::KeyModifier(m.clone()));
+                continue;
+            }
+
+            match token {
+                "ScrollUp" => events.push(InputEvent::MouseScrollUp),
+                "ScrollDown" => events.push(InputEvent::MouseScrollDown),
+                "MouseMove" => follow_mouse = true,
+                _ => events.push(parse_trigger(token)?),
+            }
+        }
+
+        if events.is_empty() {
+            return Err(format!("no keys in `{s}`"));
+        }
+
+        if follow_mouse {
+            Ok(Self::FollowMouse(events))
+        } else {
+            Ok(Self::InputEvents(events))
+        }
     }
 }
 
-impl Debug for ActionSpec {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        match self {
-            Self::Fx(vec) => f.write_fmt(format_args!("{:?}", vec)),
-            Self::MouseFn(_) => f.write_str("MouseFn(f)"),
-        }
+impl TryFrom<String> for KeySpec {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        s.parse()
     }
 }
 
-impl ActionSpec {
-    pub fn produce(&self, events: &[InputEvent]) -> Vec<Effect> {
-        match self {
-            Self::Fx(fx) => fx.clone(),
-            Self::MouseFn(f) => {
-                for event in events {
-                    if let InputEvent::MouseRealMove(delta) = event {
-                        return (f)(*delta);
-                    }
-                }
+impl From<KeySpec> for String {
+    fn from(spec: KeySpec) -> Self {
+        spec.to_string()
+    }
+}
 
-                eprintln!("ERROR: missing `MouseRealMove` event");
-                Vec::new()
-            }
-        }
+/// Parse a key or mouse button, with an optional `:down` or `:release`
+fn parse_trigger(token: &str) -> std::result::Result<InputEvent, String> {
+    let (name, trigger) = token.split_once(':').unwrap_or((token, "press"));
+
+    let button = match name {
+        "MouseLeft" => Some(MouseButton(mq::MouseButton::Left)),
+        "MouseRight" => Some(MouseButton(mq::MouseButton::Right)),
+        _ => None,
+    };
+
+    if let Some(button) = button {
+        return match trigger {
+            "press" => Ok(InputEvent::MouseButtonPress(button)),
+            "down" => Ok(InputEvent::MouseButtonDown(button)),
+            "release" => Ok(InputEvent::MouseButtonRelease(button)),
+            _ => Err(format!("unknown trigger `{trigger}` in `{token}`")),
+        };
+    }
+
+    let key = KEYS
+        .iter()
+        .find(|k| format!("{k:?}") == name)
+        .map(|k| KeyboardKey(*k))
+        .ok_or_else(|| format!("unknown key `{name}`"))?;
+
+    match trigger {
+        "press" => Ok(InputEvent::KeyPress(key)),
+        "down" => Ok(InputEvent::KeyDown(key)),
+        "release" => Ok(InputEvent::KeyRelease(key)),
+        _ => Err(format!("unknown trigger `{trigger}` in `{token}`")),
+    }
+}
+
+/// An action and all the keys bound to it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Binding {
+    pub keys: Vec<KeySpec>,
+    pub action: Action,
+}
+
+/// The same keys bound to two different actions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub keys: KeySpec,
+    pub actions: (Action, Action),
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is bound to both \"{}\" and \"{}\"",
+            self.keys, self.actions.0, self.actions.1
+        )
     }
 }
 
-#[derive(Debug)]
+/// The contents of the key bindings file
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BindingsFile {
+    #[serde(default)]
+    bindings: Vec<Binding>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct KeyBindings {
-    bindings: Vec<(KeySpec, ActionSpec)>,
+    bindings: Vec<Binding>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<Vec<Binding>> for KeyBindings {
+    fn from(bindings: Vec<Binding>) -> Self {
+        Self { bindings }
+    }
 }
 
 impl KeyBindings {
+    /// The default key bindings
     pub fn new() -> Self {
-        // TODO: bindings are very static, but sometimes we want to do something
-        // more dynamic. E.g. when mouse moves, we want camera to move to the
-        // same extent, not just in the same direction.
-        let bindings = vec![
+        let defaults: Vec<(KeySpec, Action)> = vec![
             // SET TOOLS
             (
                 InputEvent::KeyPress(mq::KeyCode::B.into()).into(),
-                Event::SetTool(Tool::Brush).into(),
+                Action::SetTool(Tool::Brush),
             ),
             (
                 InputEvent::KeyPress(mq::KeyCode::G.into()).into(),
-                Event::SetTool(Tool::Bucket).into(),
+                Action::SetTool(Tool::Bucket),
             ),
             (
                 InputEvent::KeyPress(mq::KeyCode::L.into()).into(),
-                Event::SetTool(Tool::Line).into(),
+                Action::SetTool(Tool::Line),
             ),
             (
                 InputEvent::KeyPress(mq::KeyCode::R.into()).into(),
-                Event::SetTool(Tool::Rectangle).into(),
+                Action::SetTool(Tool::Rectangle),
             ),
             (
                 InputEvent::KeyPress(mq::KeyCode::O.into()).into(),
-                Event::SetTool(Tool::Ellipse).into(),
+                Action::SetTool(Tool::Ellipse),
+            ),
+            (
+                InputEvent::KeyPress(mq::KeyCode::P.into()).into(),
+                Action::SetTool(Tool::Polyline),
             ),
             (
                 InputEvent::KeyPress(mq::KeyCode::I.into()).into(),
-                Event::SetTool(Tool::Eyedropper).into(),
+                Action::SetTool(Tool::Eyedropper),
             ),
             (
                 InputEvent::KeyPress(mq::KeyCode::E.into()).into(),
-                Event::SetTool(Tool::Eraser).into(),
+                Action::SetTool(Tool::Eraser),
             ),
             (
                 InputEvent::KeyPress(mq::KeyCode::S.into()).into(),
-                Event::SetTool(Tool::Selection).into(),
+                Action::SetTool(Tool::Selection),
+            ),
+            (
+                InputEvent::KeyPress(mq::KeyCode::Q.into()).into(),
+                Action::SetTool(Tool::Lasso),
+            ),
+            (
+                InputEvent::KeyPress(mq::KeyCode::W.into()).into(),
+                Action::SetTool(Tool::MagicWand),
             ),
             (
                 InputEvent::KeyPress(mq::KeyCode::M.into()).into(),
-                Event::SetTool(Tool::Move).into(),
+                Action::SetTool(Tool::Move),
             ),
             // FLIP
             (
                 InputEvent::KeyPress(mq::KeyCode::H.into()).into(),
-                Event::FlipHorizontal.into(),
+                Action::FlipHorizontal,
             ),
             (
                 InputEvent::KeyPress(mq::KeyCode::V.into()).into(),
-                Event::FlipVertical.into(),
+                Action::FlipVertical,
             ),
             // ZOOM
             (
@@ -173,7 +420,7 @@ impl KeyBindings {
                     InputEvent::KeyModifier(KeyboardModifier::Control),
                 ]
                 .into(),
-                UiEvent::ZoomAdd(0.25).into(),
+                Action::ZoomAdd(0.25),
             ),
             (
                 vec![
@@ -181,76 +428,75 @@ impl KeyBindings {
                     InputEvent::KeyModifier(KeyboardModifier::Control),
                 ]
                 .into(),
-                UiEvent::ZoomAdd(-0.25).into(),
+                Action::ZoomAdd(-0.25),
             ),
             (
                 InputEvent::KeyPress(mq::KeyCode::Minus.into()).into(),
-                UiEvent::ZoomOut.into(),
+                Action::ZoomOut,
             ),
             (
                 InputEvent::KeyPress(mq::KeyCode::Equal.into()).into(),
-                UiEvent::ZoomIn.into(),
+                Action::ZoomIn,
             ),
             // DRAWING
             (
                 InputEvent::MouseButtonPress(mq::MouseButton::Left.into()).into(),
-                UiEvent::ToolStart.into(),
+                Action::ToolStart,
             ),
             (
                 InputEvent::MouseButtonDown(mq::MouseButton::Left.into()).into(),
-                UiEvent::ToolStroke.into(),
+                Action::ToolStroke,
             ),
             (
                 InputEvent::MouseButtonRelease(mq::MouseButton::Left.into()).into(),
-                UiEvent::ToolEnd.into(),
+                Action::ToolEnd,
+            ),
+            (
+                InputEvent::KeyPress(mq::KeyCode::Enter.into()).into(),
+                Action::FinishPath,
             ),
             // CROSSHAIR CURSOR
             (
                 InputEvent::KeyPress(mq::KeyCode::C.into()).into(),
-                ActionSpec::Fx(vec![Effect::UiEvent(UiEvent::ToggleCursor(
-                    CursorType::Cross,
-                ))]),
+                Action::ToggleCrosshair,
+            ),
+            // TILE MODE
+            (
+                InputEvent::KeyPress(mq::KeyCode::T.into()).into(),
+                Action::ToggleTileMode,
             ),
             // PAN CURSOR + CANVAS BLOCK
             (
                 InputEvent::KeyPress(mq::KeyCode::Space.into()).into(),
-                ActionSpec::Fx(vec![
-                    Effect::UiEvent(UiEvent::BlockCanvas),
-                    Effect::UiEvent(UiEvent::SetCursor(CursorType::Pan)),
-                ]),
+                Action::StartPan,
             ),
             (
                 InputEvent::KeyRelease(mq::KeyCode::Space.into()).into(),
-                ActionSpec::Fx(vec![
-                    Effect::UiEvent(UiEvent::UnblockCanvas),
-                    Effect::UiEvent(UiEvent::SetPreviousCursor),
-                ]),
+                Action::EndPan,
             ),
             // PAN
             (
                 InputEvent::KeyDown(mq::KeyCode::Down.into()).into(),
-                UiEvent::MoveCamera(Direction::Down).into(),
+                Action::MoveCamera(Direction::Down),
             ),
             (
                 InputEvent::KeyDown(mq::KeyCode::Up.into()).into(),
-                UiEvent::MoveCamera(Direction::Up).into(),
+                Action::MoveCamera(Direction::Up),
             ),
             (
                 InputEvent::KeyDown(mq::KeyCode::Left.into()).into(),
-                UiEvent::MoveCamera(Direction::Left).into(),
+                Action::MoveCamera(Direction::Left),
             ),
             (
                 InputEvent::KeyDown(mq::KeyCode::Right.into()).into(),
-                UiEvent::MoveCamera(Direction::Right).into(),
+                Action::MoveCamera(Direction::Right),
             ),
             (
                 KeySpec::FollowMouse(vec![
                     InputEvent::KeyDown(mq::KeyCode::Space.into()),
                     InputEvent::MouseButtonDown(mq::MouseButton::Left.into()),
                 ]),
-                ActionSpec::MouseFn(Box::new(|p| {
-                    vec![Effect::UiEvent(UiEvent::MoveCameraExact(Point::ZERO - p))]
-                })),
+                Action::MoveCameraWithMouse,
             ),
             // UNDO
             (
@@ -259,7 +505,25 @@ impl KeyBindings {
                     InputEvent::KeyPress(mq::KeyCode::Z.into()),
                 ]
                 .into(),
-                Event::Undo.into(),
+                Action::Undo,
+            ),
+            // REDO
+            (
+                vec![
+                    InputEvent::KeyModifier(KeyboardModifier::Control),
+                    InputEvent::KeyPress(mq::KeyCode::Y.into()),
+                ]
+                .into(),
+                Action::Redo,
+            ),
+            (
+                vec![
+                    InputEvent::KeyModifier(KeyboardModifier::Control),
+                    InputEvent::KeyModifier(KeyboardModifier::Shift),
+                    InputEvent::KeyPress(mq::KeyCode::Z.into()),
+                ]
+                .into(),
+                Action::Redo,
             ),
             // COPY + PASTE
             (
@@ -268,7 +532,7 @@ impl KeyBindings {
                     InputEvent::KeyPress(mq::KeyCode::C.into()),
                 ]
                 .into(),
-                Event::Copy.into(),
+                Action::Copy,
             ),
             (
                 vec![
@@ -276,25 +540,177 @@ impl KeyBindings {
                     InputEvent::KeyPress(mq::KeyCode::V.into()),
                 ]
                 .into(),
-                UiEvent::Paste.into(),
+                Action::Paste,
             ),
             (
                 InputEvent::KeyPress(mq::KeyCode::Delete.into()).into(),
-                Event::DeleteSelection.into(),
+                Action::DeleteSelection,
+            ),
+            // LAYERS
+            (
+                vec![
+                    InputEvent::KeyModifier(KeyboardModifier::Control),
+                    InputEvent::KeyPress(mq::KeyCode::H.into()),
+                ]
+                .into(),
+                Action::ToggleLayerVisibility,
+            ),
+            (
+                InputEvent::KeyPress(mq::KeyCode::PageUp.into()).into(),
+                Action::MoveLayerUp,
+            ),
+            (
+                InputEvent::KeyPress(mq::KeyCode::PageDown.into()).into(),
+                Action::MoveLayerDown,
+            ),
+            // CANVAS
+            (
+                vec![
+                    InputEvent::KeyModifier(KeyboardModifier::Control),
+                    InputEvent::KeyPress(mq::KeyCode::R.into()),
+                ]
+                .into(),
+                Action::ShowResizeWindow,
+            ),
+            (
+                vec![
+                    InputEvent::KeyModifier(KeyboardModifier::Control),
+                    InputEvent::KeyModifier(KeyboardModifier::Shift),
+                    InputEvent::KeyPress(mq::KeyCode::S.into()),
+                ]
+                .into(),
+                Action::ShowSpritesheetWindow,
             ),
         ];
 
+        // Switch to the first 9 layers with the number keys
+        let number_keys = [
+            mq::KeyCode::Key1,
+            mq::KeyCode::Key2,
+            mq::KeyCode::Key3,
+            mq::KeyCode::Key4,
+            mq::KeyCode::Key5,
+            mq::KeyCode::Key6,
+            mq::KeyCode::Key7,
+            mq::KeyCode::Key8,
+            mq::KeyCode::Key9,
+        ];
+        let layer_keys = number_keys.into_iter().enumerate().map(|(i, key)| {
+            let keys: KeySpec = InputEvent::KeyPress(key.into()).into();
+            (keys, Action::SwitchLayer(i))
+        });
+
+        let mut bindings: Vec<Binding> = Vec::new();
+        for (keys, action) in defaults.into_iter().chain(layer_keys) {
+            match bindings.iter_mut().find(|b| b.action == action) {
+                Some(binding) => binding.keys.push(keys),
+                None => bindings.push(Binding {
+                    keys: vec![keys],
+                    action,
+                }),
+            }
+        }
+
         Self { bindings }
     }
 
-    pub fn iter(&self) -> std::slice::Iter<(KeySpec, ActionSpec)> {
-        self.bindings.iter()
+    /// Load the key bindings file from the config directory (e.g.
+    /// `~/.config/tarsila/keybindings.toml`). The actions listed there are bound
+    /// to the keys in the file instead of their default keys; the rest keep
+    /// their defaults.
+    pub fn load() -> Result<Self> {
+        let mut key_bindings = Self::new();
+
+        let Some(path) = config_file(BINDINGS_FILE) else {
+            return Ok(key_bindings);
+        };
+
+        if !path.exists() {
+            return Ok(key_bindings);
+        }
+
+        let text = std::fs::read_to_string(path)?;
+        let file: BindingsFile = toml::from_str(&text).map_err(Error::KeyBindingsRead)?;
+        key_bindings.apply(file.bindings);
+
+        Ok(key_bindings)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = config_file(BINDINGS_FILE) else {
+            return Ok(());
+        };
+
+        let file = BindingsFile {
+            bindings: self.bindings.clone(),
+        };
+        let text = toml::to_string_pretty(&file).map_err(Error::KeyBindingsWrite)?;
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, text)?;
+
+        Ok(())
+    }
+
+    /// Bind each of these actions to its keys, replacing the keys they were
+    /// bound to
+    pub fn apply(&mut self, bindings: Vec<Binding>) {
+        for binding in bindings {
+            match self
+                .bindings
+                .iter_mut()
+                .find(|b| b.action == binding.action)
+            {
+                Some(b) => b.keys = binding.keys,
+                None => self.bindings.push(binding),
+            }
+        }
+    }
+
+    pub fn bindings(&self) -> &[Binding] {
+        &self.bindings
+    }
+
+    /// The keys bound to an action
+    pub fn keys(&self, action: &Action) -> &[KeySpec] {
+        self.bindings
+            .iter()
+            .find(|b| b.action == *action)
+            .map(|b| b.keys.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&KeySpec, &Action)> {
+        self.bindings
+            .iter()
+            .flat_map(|b| b.keys.iter().map(move |keys| (keys, &b.action)))
+    }
+
+    /// Keys that are bound to more than one action
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let all: Vec<_> = self.iter().collect();
+        let mut conflicts = Vec::new();
+
+        for (i, (keys, action)) in all.iter().enumerate() {
+            for (other_keys, other_action) in &all[i + 1..] {
+                if action != other_action && keys == other_keys {
+                    conflicts.push(Conflict {
+                        keys: (*keys).clone(),
+                        actions: ((*action).clone(), (*other_action).clone()),
+                    });
+                }
+            }
+        }
+
+        conflicts
     }
 
     pub fn used_keys(&self) -> Vec<KeyboardKey> {
         let mut used_keys = HashSet::new();
 
-        for (keys, _) in &self.bindings {
+        for (keys, _) in self.iter() {
             for key in keys.iter() {
                 match &key {
                     InputEvent::KeyPress(k)
@@ -382,4 +798,113 @@ mod tests {
             InputEvent::MouseButtonDown(mq::MouseButton::Right.into())
         ]));
     }
+
+    #[test]
+    fn event_with_many_modifiers_matches() {
+        let spec: KeySpec = vec![
+            InputEvent::KeyModifier(KeyboardModifier::Control),
+            InputEvent::KeyModifier(KeyboardModifier::Shift),
+            InputEvent::KeyPress(mq::KeyCode::Z.into()),
+        ]
+        .into();
+
+        assert!(spec.matches(&vec![
+            InputEvent::KeyModifier(KeyboardModifier::Shift),
+            InputEvent::KeyModifier(KeyboardModifier::Control),
+            InputEvent::KeyPress(mq::KeyCode::Z.into()),
+        ]));
+
+        assert!(!spec.matches(&vec![
+            InputEvent::KeyModifier(KeyboardModifier::Control),
+            InputEvent::KeyPress(mq::KeyCode::Z.into()),
+        ]));
+    }
+
+    #[test]
+    fn key_spec_parses_and_displays() {
+        let spec: KeySpec = "Shift+Ctrl+Z".parse().unwrap();
+        let expected: KeySpec = vec![
+            InputEvent::KeyModifier(KeyboardModifier::Control),
+            InputEvent::KeyModifier(KeyboardModifier::Shift),
+            InputEvent::KeyPress(mq::KeyCode::Z.into()),
+        ]
+        .into();
+
+        assert_eq!(spec, expected);
+        assert_eq!(spec.to_string(), "Ctrl+Shift+Z");
+
+        let spec: KeySpec = "Space:down + MouseLeft:down + MouseMove".parse().unwrap();
+        assert_eq!(
+            spec,
+            KeySpec::FollowMouse(vec![
+                InputEvent::KeyDown(mq::KeyCode::Space.into()),
+                InputEvent::MouseButtonDown(mq::MouseButton::Left.into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn invalid_key_spec() {
+        assert!("Ctrl+Nope".parse::<KeySpec>().is_err());
+        assert!("A:sometimes".parse::<KeySpec>().is_err());
+        assert!("MouseMove".parse::<KeySpec>().is_err());
+    }
+
+    #[test]
+    fn default_bindings_round_trip() {
+        for (keys, action) in KeyBindings::new().iter() {
+            let parsed: KeySpec = keys.to_string().parse().unwrap();
+            assert_eq!(parsed, *keys, "{action}");
+        }
+    }
+
+    #[test]
+    fn default_bindings_have_no_conflicts() {
+        assert_eq!(KeyBindings::new().conflicts(), Vec::new());
+    }
+
+    #[test]
+    fn file_overrides_defaults() {
+        let text = r#"
+            [[bindings]]
+            keys = ["U"]
+            action = "Undo"
+
+            [[bindings]]
+            keys = []
+            action = "FlipVertical"
+
+            [[bindings]]
+            keys = ["Ctrl+ScrollUp"]
+            action = { ZoomAdd = 1.0 }
+        "#;
+        let file: BindingsFile = toml::from_str(text).unwrap();
+        let mut key_bindings = KeyBindings::new();
+        key_bindings.apply(file.bindings);
+
+        assert_eq!(key_bindings.keys(&Action::Undo), &["U".parse().unwrap()]);
+        assert!(key_bindings.keys(&Action::FlipVertical).is_empty());
+        assert_eq!(
+            key_bindings.keys(&Action::Redo),
+            KeyBindings::new().keys(&Action::Redo)
+        );
+
+        let conflicts = key_bindings.conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            conflicts[0].actions,
+            (Action::ZoomAdd(0.25), Action::ZoomAdd(1.0))
+        );
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let file = BindingsFile {
+            bindings: KeyBindings::new().bindings().to_vec(),
+        };
+        let text = toml::to_string_pretty(&file).unwrap();
+        let loaded: BindingsFile = toml::from_str(&text).unwrap();
+
+        assert_eq!(KeyBindings::from(loaded.bindings), KeyBindings::new());
+    }
 }
@@ -1,7 +1,7 @@
 use super::{InputEvent, InputMapper, KeyBindings, KeyboardKey, KeyboardModifier, MouseButton};
 use crate::Effect;
 use egui_macroquad::macroquad::prelude as mq;
-use lapix::Position;
+use lapix::{Position, ShapeConstraints};
 
 #[derive(Debug)]
 pub struct InputManager {
@@ -25,6 +25,10 @@ impl InputManager {
         }
     }
 
+    pub fn set_keys_to_track(&mut self, keys_to_track: Vec<KeyboardKey>) {
+        self.keys_to_track = keys_to_track;
+    }
+
     pub fn sync(&mut self, mouse_pos: Position<f32>, mouse_canvas_pos: Position<i32>) {
         self.prev_mouse_canvas = self.mouse_canvas;
         self.mouse_canvas = mouse_canvas_pos;
@@ -38,6 +42,17 @@ impl InputManager {
         self.mapper.map(key_bindings, input_events)
     }
 
+    /// Shift makes the shape being drawn a square (or circle) and snaps lines
+    /// to clean slopes, and Control draws shapes from their center
+    pub fn shape_constraints(&self) -> ShapeConstraints {
+        ShapeConstraints {
+            square: mq::is_key_down(mq::KeyCode::RightShift)
+                || mq::is_key_down(mq::KeyCode::LeftShift),
+            from_center: mq::is_key_down(mq::KeyCode::RightControl)
+                || mq::is_key_down(mq::KeyCode::LeftControl),
+        }
+    }
+
     fn get_input_events(&self) -> Vec<InputEvent> {
         let mut events = Vec::new();
 
@@ -10,7 +10,7 @@ impl InputMapper {
 
         for (keys, action) in key_bindings.iter() {
             if keys.matches(&input_events) {
-                let mut new_fx = action.produce(&input_events);
+                let mut new_fx = action.effects(&input_events);
                 fx.append(&mut new_fx);
             }
         }
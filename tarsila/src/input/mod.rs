@@ -1,7 +1,9 @@
+pub mod action;
 pub mod bindings;
 pub mod manager;
 pub mod mapper;
 
+pub use action::Action;
 pub use bindings::KeyBindings;
 pub use mapper::InputMapper;
 
@@ -0,0 +1,124 @@
+use crate::settings::config_file;
+use crate::{Error, Result};
+use lapix::{Event, Point};
+use serde::{Deserialize, Serialize};
+
+const MACROS_FILE: &str = "macros.toml";
+
+/// A named sequence of recorded events that can be replayed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Macro {
+    pub name: String,
+    pub events: Vec<Event>,
+}
+
+impl Macro {
+    /// The first point of the canvas where the macro does something. Replaying
+    /// it at a point moves everything by the distance from the origin to it.
+    pub fn origin(&self) -> Point<i32> {
+        self.events
+            .iter()
+            .find_map(|event| event.point())
+            .unwrap_or(Point::ZERO)
+    }
+}
+
+/// The saved macros, stored in the config directory (e.g.
+/// `~/.config/tarsila/macros.toml`)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Macros {
+    macros: Vec<Macro>,
+}
+
+impl Macros {
+    /// Load the macros file, or no macros if there is none
+    pub fn load() -> Result<Self> {
+        let Some(path) = config_file(MACROS_FILE) else {
+            return Ok(Self::default());
+        };
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let text = std::fs::read_to_string(path)?;
+        toml::from_str(&text).map_err(Error::MacrosRead)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = config_file(MACROS_FILE) else {
+            return Ok(());
+        };
+
+        let text = toml::to_string_pretty(self).map_err(Error::MacrosWrite)?;
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, text)?;
+
+        Ok(())
+    }
+
+    /// Add a macro, replacing the one with the same name, if any
+    pub fn add(&mut self, new: Macro) {
+        match self.macros.iter_mut().find(|m| m.name == new.name) {
+            Some(m) => *m = new,
+            None => self.macros.push(new),
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.macros.len() {
+            self.macros.remove(index);
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Macro> {
+        self.macros.get(index)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.macros.iter().map(|m| m.name.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lapix::Color;
+
+    #[test]
+    fn toml_round_trip() {
+        let mut macros = Macros::default();
+        macros.add(Macro {
+            name: "shading".to_owned(),
+            events: vec![
+                Event::SetMainColor(Color::new(10, 20, 30, 255)),
+                Event::BrushStart,
+                Event::BrushStroke(Point::new(3, 4)),
+                Event::BrushEnd,
+                Event::ChangeLayerVisibility(0, true),
+            ],
+        });
+
+        let text = toml::to_string_pretty(&macros).unwrap();
+        assert_eq!(toml::from_str::<Macros>(&text).unwrap(), macros);
+    }
+
+    #[test]
+    fn origin_and_replacing() {
+        let mut macros = Macros::default();
+        let mut new = Macro {
+            name: "dot".to_owned(),
+            events: vec![Event::NewLayerAbove, Event::Bucket(Point::new(2, 5))],
+        };
+        macros.add(new.clone());
+        assert_eq!(macros.get(0).unwrap().origin(), Point::new(2, 5));
+
+        new.events.pop();
+        macros.add(new);
+        assert_eq!(macros.names(), vec!["dot"]);
+        assert_eq!(macros.get(0).unwrap().origin(), Point::ZERO);
+    }
+}
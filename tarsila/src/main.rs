@@ -1,21 +1,29 @@
 use egui_macroquad::macroquad::{self, prelude::*};
 
+mod autosave;
 mod bg;
+mod cli;
 mod error;
 mod graphics;
 mod gui;
 mod input;
+mod macros;
 mod mouse;
-mod project;
 mod resource;
+mod settings;
 mod ui_state;
 mod util;
 mod wrapped_image;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-use error::Result;
+use cli::{Args, USAGE};
+use error::{Error, Result};
+use input::KeyBindings;
+use lapix::Event;
+use macros::Macros;
 use resource::Resources;
+use settings::Settings;
 use ui_state::{Effect, UiEvent, UiState, WINDOW_H, WINDOW_W};
 use util::*;
 
@@ -29,18 +37,84 @@ fn window_conf() -> Conf {
     }
 }
 
-#[macroquad::main(window_conf)]
-async fn main() {
-    let mut state = UiState::default();
+fn main() {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("ERROR: {e}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    if args.help {
+        println!("{USAGE}");
+        return;
+    }
+
+    if args.version {
+        println!("tarsila {VERSION}");
+        return;
+    }
+
+    macroquad::Window::from_config(window_conf(), run(args));
+}
+
+async fn run(args: Args) {
+    let (settings, settings_error) = match Settings::load() {
+        Ok(settings) => (settings, None),
+        Err(e) => (Settings::default(), Some(e)),
+    };
+    let (key_bindings, key_bindings_error) = match KeyBindings::load() {
+        Ok(key_bindings) => (key_bindings, None),
+        Err(e) => (KeyBindings::default(), Some(e)),
+    };
+    let (macros, macros_error) = match Macros::load() {
+        Ok(macros) => (macros, None),
+        Err(e) => (Macros::default(), Some(e)),
+    };
+    let conflicts = key_bindings.conflicts();
+    let canvas_size = args.new.unwrap_or(settings.canvas_size);
+    let mut state = UiState::new(canvas_size, settings, key_bindings, macros);
     let mut frame = 0;
 
+    let conflicts = conflicts.into_iter().map(Error::KeyBindingConflict);
+    for e in settings_error
+        .into_iter()
+        .chain(key_bindings_error)
+        .chain(macros_error)
+        .chain(conflicts)
+    {
+        eprintln!("ERROR: {e}");
+        state.show_error(e);
+    }
+
+    for e in open_args(&mut state, args) {
+        eprintln!("ERROR: {e}");
+        state.show_error(e);
+    }
+
+    // Closing the window goes through the same path as File > Exit, so that the
+    // session ends cleanly
+    prevent_quit();
+
+    if let Err(e) = state.start_session() {
+        eprintln!("ERROR: {e}");
+        state.show_error(e);
+    }
+
     loop {
+        if is_quit_requested() {
+            break;
+        }
+
         if let Err(e) = state.update(frame) {
             eprintln!("ERROR: {e}");
+            state.show_error(e);
         }
 
         if let Err(e) = state.draw() {
             eprintln!("ERROR: {e}");
+            state.show_error(e);
         }
 
         next_frame().await;
@@ -51,4 +125,28 @@ async fn main() {
 
         frame += 1;
     }
+
+    if let Err(e) = state.end_session() {
+        eprintln!("ERROR: {e}");
+    }
+}
+
+/// Open the file and apply the options given in the command line, returning
+/// the errors
+fn open_args(state: &mut UiState, args: Args) -> Vec<Error> {
+    let mut results = Vec::new();
+
+    if let Some(path) = args.file {
+        results.push(state.open_file(&path));
+    }
+
+    if let Some(path) = args.palette {
+        results.push(state.execute(Event::LoadPalette(path)));
+    }
+
+    if let Some(size) = args.spritesheet {
+        results.push(state.execute(Event::SetSpritesheet(size)));
+    }
+
+    results.into_iter().filter_map(|r| r.err()).collect()
 }
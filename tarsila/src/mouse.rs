@@ -64,9 +64,16 @@ impl CursorSet {
             (Tool::Eyedropper, (0., -16.).into()),
             (Tool::Line, (0., -16.).into()),
             (Tool::Selection, (0., 0.).into()),
+            (Tool::EllipseSelection, (0., -16.).into()),
+            (Tool::Lasso, (0., -16.).into()),
+            (Tool::PolygonLasso, (0., -16.).into()),
+            (Tool::MagicWand, (0., -16.).into()),
             (Tool::Move, (-8., -8.).into()),
             (Tool::Rectangle, (0., -16.).into()),
             (Tool::Ellipse, (0., -16.).into()),
+            (Tool::Polyline, (0., -16.).into()),
+            (Tool::Polygon, (0., -16.).into()),
+            (Tool::Bezier, (0., -16.).into()),
         ];
 
         let mut hm: HashMap<_, _> = tools
@@ -20,9 +20,16 @@ impl Resources {
             Tool::Eyedropper => include_bytes!("../res/icon/eyedropper.png"),
             Tool::Line => include_bytes!("../res/icon/line.png"),
             Tool::Selection => include_bytes!("../res/icon/selection.png"),
+            Tool::EllipseSelection => include_bytes!("../res/icon/ellipse_selection.png"),
+            Tool::Lasso => include_bytes!("../res/icon/lasso.png"),
+            Tool::PolygonLasso => include_bytes!("../res/icon/polygon_lasso.png"),
+            Tool::MagicWand => include_bytes!("../res/icon/magic_wand.png"),
             Tool::Move => include_bytes!("../res/icon/move.png"),
             Tool::Rectangle => include_bytes!("../res/icon/rectangle.png"),
             Tool::Ellipse => include_bytes!("../res/icon/ellipse.png"),
+            Tool::Polyline => include_bytes!("../res/icon/polyline.png"),
+            Tool::Polygon => include_bytes!("../res/icon/polygon.png"),
+            Tool::Bezier => include_bytes!("../res/icon/bezier.png"),
         }
     }
 }
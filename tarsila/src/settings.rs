@@ -0,0 +1,123 @@
+use crate::autosave::AutosaveConfig;
+use crate::{Error, Result};
+use directories::ProjectDirs;
+use lapix::Size;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const SETTINGS_FILE: &str = "settings.toml";
+const MAX_RECENT_PROJECTS: usize = 10;
+
+/// Which of the GUI panels are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PanelVisibility {
+    pub toolbox: bool,
+    pub palette: bool,
+    pub layers: bool,
+    pub preview: bool,
+    pub history: bool,
+    pub status_bar: bool,
+}
+
+impl Default for PanelVisibility {
+    fn default() -> Self {
+        Self {
+            toolbox: true,
+            palette: true,
+            layers: true,
+            preview: true,
+            history: true,
+            status_bar: true,
+        }
+    }
+}
+
+/// User settings, stored in the config directory (e.g.
+/// `~/.config/tarsila/settings.toml`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Size of the canvas of new projects
+    pub canvas_size: Size<i32>,
+    /// Zoom level of new projects (and when resetting the zoom)
+    pub zoom: f32,
+    pub camera_speed: f32,
+    /// Directory where the last file was opened or saved
+    pub last_dir: Option<PathBuf>,
+    /// Most recently opened or saved projects, newest first
+    pub recent_projects: Vec<PathBuf>,
+    pub panels: PanelVisibility,
+    pub autosave: AutosaveConfig,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            canvas_size: Size::new(64, 64),
+            zoom: 8.,
+            camera_speed: 12.,
+            last_dir: None,
+            recent_projects: Vec::new(),
+            panels: PanelVisibility::default(),
+            autosave: AutosaveConfig::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Load the settings file. If there is none, the default settings are used.
+    /// If it cannot be read, it is backed up before returning the error, since
+    /// the defaults used instead are saved over it when a setting changes.
+    pub fn load() -> Result<Self> {
+        let Some(path) = config_file(SETTINGS_FILE) else {
+            return Ok(Self::default());
+        };
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let text = std::fs::read_to_string(&path)?;
+
+        toml::from_str(&text).or_else(|e| {
+            let backup = path.with_extension("toml.bak");
+            std::fs::copy(&path, &backup)?;
+            Err(Error::SettingsRead(e, backup))
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = config_file(SETTINGS_FILE) else {
+            return Ok(());
+        };
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, toml::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+
+    /// Record that a file was opened or saved in a directory
+    pub fn used_file(&mut self, path: &Path) {
+        if let Some(dir) = path.parent() {
+            self.last_dir = Some(dir.to_owned());
+        }
+    }
+
+    /// Record that a project was opened or saved, moving it to the top of the
+    /// recent projects
+    pub fn used_project(&mut self, path: &Path) {
+        self.used_file(path);
+        self.recent_projects.retain(|p| p != path);
+        self.recent_projects.insert(0, path.to_owned());
+        self.recent_projects.truncate(MAX_RECENT_PROJECTS);
+    }
+}
+
+/// Path of a file in the config directory
+pub fn config_file(name: &str) -> Option<PathBuf> {
+    ProjectDirs::from("", "", "tarsila").map(|dirs| dirs.config_dir().join(name))
+}
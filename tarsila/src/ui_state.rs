@@ -1,38 +1,42 @@
+use crate::autosave::Autosave;
 use crate::bg::Background;
-use crate::graphics::DrawContext;
+use crate::graphics::{DrawContext, Segment};
 use crate::gui::{Gui, GuiSyncParams};
 use crate::input::bindings::KeyBindings;
 use crate::input::manager::InputManager;
+use crate::macros::{Macro, Macros};
 use crate::mouse::{CursorType, MouseManager};
-use crate::project;
+use crate::settings::Settings;
 use crate::wrapped_image::WrappedImage;
-use crate::{graphics, Result, Timer};
+use crate::{graphics, Error, Result, Timer};
 use egui_macroquad::macroquad;
 use egui_macroquad::macroquad::prelude::Color as MqColor;
 use egui_macroquad::macroquad::prelude::{FilterMode, Texture2D};
 use lapix::primitives::*;
-use lapix::{Canvas, CanvasEffect, Event, Layer, LoadProject, SaveProject, Selection, State, Tool};
-use std::default::Default;
+use lapix::{Brush, BrushShape, Canvas, CanvasEffect, Event, Layer, Mask, Selection, State, Tool};
+use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
 pub const WINDOW_W: i32 = 1000;
 pub const WINDOW_H: i32 = 600;
-pub const CANVAS_W: u16 = 64;
-pub const CANVAS_H: u16 = 64;
 const CANVAS_SCALE: f32 = 8.;
 const LEFT_TOOLBAR_W: u16 = 300;
-const CAMERA_SPEED: f32 = 12.;
 const BG_COLOR: MqColor = MqColor::new(0.5, 0.5, 0.5, 1.);
 const GUI_REST_MS: u64 = 100;
+const DOUBLE_CLICK_MS: u64 = 400;
 const FPS_INTERVAL: usize = 15;
-const DEFAULT_ZOOM_LEVEL: f32 = 8.;
 pub const MIN_ZOOM: f32 = 0.125;
 pub const MAX_ZOOM: f32 = 1024.;
 
-// Center on the space after the toolbar
-const CANVAS_X: f32 = LEFT_TOOLBAR_W as f32 + ((WINDOW_W as u16 - LEFT_TOOLBAR_W) / 2) as f32
-    - (CANVAS_W as f32 * CANVAS_SCALE / 2.);
-const CANVAS_Y: f32 = (WINDOW_H / 2) as f32 - (CANVAS_H as f32 * CANVAS_SCALE / 2.);
+/// Initial position of a canvas of a certain size, centered on the space after
+/// the toolbar
+fn initial_canvas_pos(size: Size<i32>) -> Position<f32> {
+    let x = LEFT_TOOLBAR_W as f32 + ((WINDOW_W as u16 - LEFT_TOOLBAR_W) / 2) as f32
+        - (size.x as f32 * CANVAS_SCALE / 2.);
+    let y = (WINDOW_H / 2) as f32 - (size.y as f32 * CANVAS_SCALE / 2.);
+
+    (x, y).into()
+}
 
 #[derive(Debug, Clone)]
 pub enum Effect {
@@ -78,6 +82,32 @@ pub enum UiEvent {
     ToolEnd,
     BlockCanvas,
     UnblockCanvas,
+    UpdateSettings(Settings),
+    /// Load a project, adding it to the recent projects if it loads
+    LoadProject(PathBuf),
+    /// Delete the autosave offered for recovery, once it was restored or not
+    DiscardRecovery,
+    UpdateKeyBindings(KeyBindings),
+    StartRecording,
+    /// Stop recording and ask for a name for the macro
+    StopRecording,
+    SaveMacro(Macro),
+    DeleteMacro(usize),
+    /// Replay a macro where it was recorded
+    ReplayMacro(usize),
+    /// Replay a macro at the next point clicked on the canvas
+    PlaceMacro(usize),
+    /// Use the pixels of the selection as the shape of the brush
+    StampFromSelection,
+    ToggleTileMode,
+    /// Show or hide the active layer
+    ToggleLayerVisibility,
+    /// Move the active layer above the next one, if there is one
+    MoveLayerUp,
+    /// Move the active layer below the previous one, if there is one
+    MoveLayerDown,
+    ShowResizeWindow,
+    ShowSpritesheetWindow,
 }
 
 impl UiEvent {
@@ -91,8 +121,9 @@ impl<'a> From<&'a UiState> for GuiSyncParams {
         let n_layers = state.inner.layers().count();
         let (x, y) = macroquad::prelude::mouse_position();
         let (x, y) = state.screen_to_canvas(x, y);
-        let p = (x, y).into();
-        let in_canvas = state.canvas().is_in_bounds(p);
+        let in_canvas = state.is_on_canvas((x, y).into());
+        // In tile mode, the pixel under a copy of the canvas
+        let p = state.tile((x, y).into());
         let visible_pixel = if in_canvas {
             Some(state.visible_pixel(p))
         } else {
@@ -101,6 +132,11 @@ impl<'a> From<&'a UiState> for GuiSyncParams {
 
         Self {
             main_color: state.inner.main_color().into(),
+            brush: state.inner.brush().clone(),
+            shape_options: state.inner.shape_options(),
+            bucket_options: state.inner.bucket_options(),
+            symmetry: state.inner.symmetry(),
+            tile_mode: state.inner.tile_mode(),
             num_layers: n_layers,
             active_layer: state.inner.layers().active_index(),
             layers_vis: (0..n_layers)
@@ -110,7 +146,7 @@ impl<'a> From<&'a UiState> for GuiSyncParams {
                 .map(|i| state.inner.layers().get(i).opacity())
                 .collect(),
             palette: state.inner.palette().iter().map(|c| (*c).into()).collect(),
-            mouse_canvas: (x, y).into(),
+            mouse_canvas: p,
             is_on_canvas: in_canvas,
             selected_tool: state.selected_tool(),
             visible_pixel_on_mouse: visible_pixel,
@@ -118,6 +154,17 @@ impl<'a> From<&'a UiState> for GuiSyncParams {
             spritesheet: state.inner.spritesheet(),
             zoom: state.zoom,
             fps: state.fps,
+            history: state
+                .inner
+                .history()
+                .labels()
+                .map(|label| label.to_owned())
+                .collect(),
+            history_position: state.inner.history().position(),
+            settings: state.settings.clone(),
+            key_bindings: state.key_bindings.clone(),
+            macros: state.macros.names(),
+            recording: state.inner.is_recording(),
         }
     }
 }
@@ -129,69 +176,198 @@ pub struct UiState {
     canvas_pos: Position<f32>,
     zoom: f32,
     layer_textures: Vec<Texture2D>,
+    /// What the events of this frame changed in the layers, applied to their
+    /// textures once before drawing
+    canvas_effect: CanvasEffect,
     input: InputManager,
     mouse: MouseManager,
     mouse_over_gui: bool,
     key_bindings: KeyBindings,
+    macros: Macros,
+    /// Macro to be replayed at the next point clicked on the canvas
+    placing_macro: Option<usize>,
+    /// Whether the click that placed a macro is still going on, so that the
+    /// tool must ignore it
+    placed_macro: bool,
+    /// Runs after a click on the canvas with a path tool, so that another
+    /// click on the same point finishes the path
+    double_click: Timer,
+    last_click: Point<i32>,
+    /// Index of the control point of a Bezier curve being dragged
+    dragged_point: Option<usize>,
     gui_interaction_rest: Timer,
     manual_canvas_block: bool,
     free_image_tex: Option<Texture2D>,
+    /// The outline of the selection and the mask it was computed from, so that
+    /// it is only computed again when the selected pixels change
+    selection_outline: Option<(Mask, Vec<Segment>)>,
     must_exit: bool,
     t0: SystemTime,
     fps: f32,
     bg: Background,
     prev_cursor: CursorType,
+    autosave: Autosave,
+    settings: Settings,
 }
 
-impl Default for UiState {
-    fn default() -> Self {
-        let state = State::<WrappedImage>::new(
-            (CANVAS_W as i32, CANVAS_H as i32).into(),
-            Some(LoadProject(project::load)),
-            Some(SaveProject(project::save)),
-        );
+impl UiState {
+    pub fn new(
+        canvas_size: Size<i32>,
+        settings: Settings,
+        key_bindings: KeyBindings,
+        macros: Macros,
+    ) -> Self {
+        let state = State::<WrappedImage>::new(canvas_size);
         let drawing = Texture2D::from_image(&state.canvas().inner().0);
         drawing.set_filter(FilterMode::Nearest);
 
-        let key_bindings = KeyBindings::new();
-
-        // TODO: keys_to_track should be defined by the shortcuts in use
         let input = InputManager::new(key_bindings.used_keys());
 
         Self {
             inner: state,
             gui: Gui::new(),
             camera: Position::ZERO_F32,
-            canvas_pos: (CANVAS_X, CANVAS_Y).into(),
-            zoom: DEFAULT_ZOOM_LEVEL,
+            canvas_pos: initial_canvas_pos(canvas_size),
+            zoom: settings.zoom,
             layer_textures: vec![drawing],
+            canvas_effect: CanvasEffect::None,
             input,
             mouse: MouseManager::new(),
             mouse_over_gui: false,
             key_bindings,
+            macros,
+            placing_macro: None,
+            placed_macro: false,
+            double_click: Timer::new(),
+            last_click: Point::ZERO,
+            dragged_point: None,
             gui_interaction_rest: Timer::new(),
             free_image_tex: None,
+            selection_outline: None,
             must_exit: false,
             t0: SystemTime::now(),
             fps: 60.,
             bg: Background::new(),
             prev_cursor: CursorType::Tool(Tool::Brush),
             manual_canvas_block: false,
+            autosave: Autosave::new(settings.autosave),
+            settings,
         }
     }
-}
 
-impl UiState {
     pub fn must_exit(&self) -> bool {
         self.must_exit
     }
 
-    /*pub fn drawing_mut(&mut self) -> &mut Texture2D {
-        &mut self.layer_textures[self.inner.layers().active_index()]
-    }*/
+    /// Apply new settings, saving them if they changed
+    fn update_settings(&mut self, settings: Settings) -> Result<()> {
+        if settings == self.settings {
+            return Ok(());
+        }
+
+        self.autosave.set_config(settings.autosave);
+        self.settings = settings;
+        self.settings.save()
+    }
+
+    /// Apply new key bindings, saving them if they changed
+    fn update_key_bindings(&mut self, key_bindings: KeyBindings) -> Result<()> {
+        if key_bindings == self.key_bindings {
+            return Ok(());
+        }
+
+        self.input.set_keys_to_track(key_bindings.used_keys());
+        self.key_bindings = key_bindings;
+        self.key_bindings.save()
+    }
 
-    pub fn drawing(&self) -> &Texture2D {
-        &self.layer_textures[self.inner.layers().active_index()]
+    /// Add a macro (or replace the one with the same name) and save the
+    /// macros
+    fn save_macro(&mut self, new: Macro) -> Result<()> {
+        self.macros.add(new);
+        self.macros.save()
+    }
+
+    fn delete_macro(&mut self, index: usize) -> Result<()> {
+        self.macros.remove(index);
+        self.placing_macro = None;
+        self.macros.save()
+    }
+
+    /// Replay a macro moved by an offset, as a single action in the history
+    fn replay_macro(&mut self, index: usize, offset: Point<i32>) -> Result<()> {
+        let Some(m) = self.macros.get(index) else {
+            return Ok(());
+        };
+
+        let label = format!("Macro: {}", m.name);
+        let events = m.events.clone();
+        let effect = self.inner.replay(label, &events, offset);
+        // Update the textures even if an event failed halfway
+        self.sync_layer_textures();
+        effect?;
+
+        Ok(())
+    }
+
+    /// Use the pixels of the selection (or of the free image) as the shape of
+    /// the brush
+    fn stamp_from_selection(&mut self) -> Result<()> {
+        let Some(img) = self.inner.selection_image() else {
+            return Ok(());
+        };
+
+        let brush = Brush {
+            shape: BrushShape::stamp_from_image(&img),
+            ..self.inner.brush().clone()
+        };
+
+        self.execute(Event::SetBrush(brush))
+    }
+
+    /// Start autosaving. If the previous session crashed, offer to restore
+    /// its latest autosave
+    pub fn start_session(&mut self) -> Result<()> {
+        if let Some(path) = self.autosave.start()? {
+            self.gui.offer_recovery(path);
+        }
+
+        Ok(())
+    }
+
+    /// Mark the session as cleanly finished
+    pub fn end_session(&self) -> Result<()> {
+        self.autosave.finish()
+    }
+
+    /// Open a project (`.tarsila`) or an image file as the whole project,
+    /// centering the canvas
+    pub fn open_file(&mut self, path: &Path) -> Result<()> {
+        if path.extension().is_some_and(|ext| ext == "tarsila") {
+            self.load_project(path)?;
+        } else {
+            let bytes = std::fs::read(path)?;
+            self.inner.load_image_bytes(&bytes)?;
+            self.sync_layer_textures();
+        }
+
+        self.canvas_pos = initial_canvas_pos(self.canvas().size());
+
+        Ok(())
+    }
+
+    /// Load a project, recording it as a recent project only once it loads
+    fn load_project(&mut self, path: &Path) -> Result<()> {
+        self.execute(Event::LoadProject(path.to_owned()))?;
+
+        let mut settings = self.settings.clone();
+        settings.used_project(path);
+        self.update_settings(settings)
+    }
+
+    /// Show an error message to the user
+    pub fn show_error(&mut self, error: Error) {
+        self.gui.show_error(error.to_string());
     }
 
     pub fn update(&mut self, frame: usize) -> Result<()> {
@@ -212,10 +388,13 @@ impl UiState {
         let (cx, cy) = self.screen_to_canvas(x, y);
         let cp = (cx, cy).into();
         self.input.sync(sp, cp);
+        self.inner
+            .set_shape_constraints(self.input.shape_constraints());
         let fx = self.input.update(&self.key_bindings);
         self.process_fx(fx)?;
 
         self.sync_mouse();
+        self.autosave.update(&self.inner)?;
 
         Ok(())
     }
@@ -244,18 +423,29 @@ impl UiState {
             canvas_pos: self.canvas_pos(),
             camera: self.camera(),
             canvas_size: (self.canvas().width() as f32, self.canvas().height() as f32).into(),
-            selection: self.inner.selection(),
         }
     }
 
     pub fn draw(&mut self) -> Result<()> {
+        self.sync_canvas_effect();
         macroquad::prelude::clear_background(BG_COLOR);
 
         let ctx = self.draw_ctx();
 
-        self.bg.draw(ctx);
-        graphics::draw_canvas(&*self);
+        // In tile mode, the canvas is repeated around itself
+        let tiles = if self.inner.tile_mode() {
+            -1..=1
+        } else {
+            0..=0
+        };
+        for i in tiles.clone() {
+            for j in tiles.clone() {
+                self.bg.draw(ctx.tile(i, j));
+                graphics::draw_canvas(&*self, ctx.tile(i, j));
+            }
+        }
         graphics::draw_spritesheet_boundaries(ctx);
+        graphics::draw_symmetry_axes(ctx, self.inner.symmetry());
 
         let (x, y) = macroquad::prelude::mouse_position();
         let mouse_canvas = self.screen_to_canvas(x, y).into();
@@ -263,8 +453,8 @@ impl UiState {
         // TODO should be in update method
         self.inner.update_free_image(mouse_canvas)?;
 
-        if self.inner.selection().is_some() {
-            graphics::draw_selection(ctx, self.inner.free_image());
+        if let Some((outline, offset)) = self.selection_outline() {
+            graphics::draw_selection(ctx, outline, offset);
         }
 
         // TODO: most of this logic should be in some update method, not a draw one
@@ -283,6 +473,42 @@ impl UiState {
             self.free_image_tex = None;
         }
 
+        let tool = self.selected_tool();
+        if tool == Tool::Bezier && self.inner.path().len() > 1 {
+            graphics::draw_bezier_handles(ctx, self.inner.path());
+        }
+        match tool {
+            Tool::Lasso => graphics::draw_lasso(ctx, self.inner.path()),
+            Tool::PolygonLasso if !self.inner.path().is_empty() => {
+                let mut path = self.inner.path().to_vec();
+                path.push(mouse_canvas);
+                graphics::draw_lasso(ctx, &path);
+            }
+            _ => (),
+        }
+
+        if tool.uses_brush() && self.is_on_canvas(mouse_canvas) {
+            let color = match tool {
+                Tool::Eraser => MqColor::new(1., 1., 1., 0.5),
+                _ => {
+                    let c = self.inner.main_color();
+                    MqColor::from_rgba(c.r, c.g, c.b, c.a / 2)
+                }
+            };
+            // In tile mode, the pixels are wrapped around the canvas and shown
+            // on each of its copies, like the stroke
+            let footprint = self.inner.brush().footprint();
+            let points: Vec<_> = footprint
+                .iter()
+                .map(|offset| self.tile(mouse_canvas + *offset))
+                .collect();
+            for i in tiles.clone() {
+                for j in tiles.clone() {
+                    graphics::draw_brush_preview(ctx.tile(i, j), &points, color);
+                }
+            }
+        }
+
         self.gui.egui_mq.draw();
         self.gui.draw_preview(self);
         self.mouse.draw();
@@ -290,39 +516,72 @@ impl UiState {
         Ok(())
     }
 
+    /// The outline of the selected pixels (of the canvas, or of the free
+    /// image) and the offset to draw it at
+    fn selection_outline(&mut self) -> Option<(&[Segment], Point<i32>)> {
+        let (mask, offset) = match (self.inner.selection()?, self.inner.free_image()) {
+            (Selection::FreeImage, Some(img)) => (&img.mask, img.rect.pos()),
+            (Selection::Canvas(mask), _) => (mask, Point::ZERO),
+            _ => return None,
+        };
+
+        if !matches!(&self.selection_outline, Some((cached, _)) if cached == mask) {
+            self.selection_outline = Some((mask.clone(), mask.outline()));
+        }
+
+        let (_, outline) = self.selection_outline.as_ref()?;
+
+        Some((outline, offset))
+    }
+
     pub fn sync_mouse(&mut self) {
         let (x, y) = macroquad::prelude::mouse_position();
         let (x, y) = self.screen_to_canvas(x, y);
-        let p = (x, y).into();
-        let in_canvas = self.canvas().is_in_bounds(p);
+        let in_canvas = self.is_on_canvas((x, y).into());
 
         self.mouse.sync(in_canvas, self.selected_tool());
     }
 
     pub fn execute(&mut self, event: Event) -> Result<()> {
         let effect = self.inner.execute(event)?;
-
-        match effect {
-            // TODO: Texture2D is copy, so we don't need `drawing_mut` here, but
-            // it would be better.
-            CanvasEffect::Update => {
-                self.drawing().update(&self.canvas().inner().0);
-            }
-            CanvasEffect::New | CanvasEffect::Layer => {
-                self.sync_layer_textures();
-            }
-            CanvasEffect::None => (),
-        };
+        self.canvas_effect = self.canvas_effect.merge(effect);
 
         Ok(())
     }
 
+    /// Update the layer textures with what changed since the last time, e.g.
+    /// only the area of a layer touched by the brush strokes of this frame
+    fn sync_canvas_effect(&mut self) {
+        match std::mem::take(&mut self.canvas_effect) {
+            CanvasEffect::Update(index, rect) => self.sync_layer_area(index, rect),
+            CanvasEffect::New | CanvasEffect::Layer => self.sync_layer_textures(),
+            CanvasEffect::None => (),
+        }
+    }
+
     pub fn sync_layer_textures(&mut self) {
         for layer in 0..self.inner.layers().count() {
             self.sync_layer_texture(layer);
         }
     }
 
+    /// Upload an area of a layer to its texture, instead of the whole image
+    pub fn sync_layer_area(&mut self, index: usize, rect: Rect<i32>) {
+        if index >= self.layer_textures.len() || index >= self.inner.layers().count() {
+            self.sync_layer_textures();
+            return;
+        }
+
+        let canvas = self.inner.layers().canvas_at(index);
+        let rect = rect.clip_to(canvas.rect());
+        if rect.w <= 0 || rect.h <= 0 {
+            return;
+        }
+
+        let area = canvas.img_from_area(rect);
+        self.layer_textures[index].update_part(&area.0, rect.x, rect.y, rect.w, rect.h);
+    }
+
     pub fn sync_layer_texture(&mut self, index: usize) {
         let layer_img = &self.inner.layers().canvas_at(index).inner().0;
         let texture = Texture2D::from_image(layer_img);
@@ -359,7 +618,53 @@ impl UiState {
                 self.execute(Event::Paste(p))?;
             }
             UiEvent::Exit => self.must_exit = true,
-            UiEvent::NewProject => *self = UiState::default(),
+            UiEvent::NewProject => {
+                let autosave = std::mem::take(&mut self.autosave);
+                let key_bindings = std::mem::take(&mut self.key_bindings);
+                let macros = std::mem::take(&mut self.macros);
+                let settings = self.settings.clone();
+                *self = UiState::new(settings.canvas_size, settings, key_bindings, macros);
+                self.autosave = autosave;
+            }
+            UiEvent::UpdateSettings(settings) => self.update_settings(settings)?,
+            UiEvent::LoadProject(path) => self.load_project(&path)?,
+            UiEvent::DiscardRecovery => self.autosave.discard_recovery()?,
+            UiEvent::UpdateKeyBindings(key_bindings) => self.update_key_bindings(key_bindings)?,
+            UiEvent::StartRecording => self.inner.start_recording(),
+            UiEvent::StopRecording => {
+                let events = self.inner.stop_recording();
+                if !events.is_empty() {
+                    self.gui.name_macro(events);
+                }
+            }
+            UiEvent::SaveMacro(new) => self.save_macro(new)?,
+            UiEvent::DeleteMacro(index) => self.delete_macro(index)?,
+            UiEvent::ReplayMacro(index) => self.replay_macro(index, Point::ZERO)?,
+            UiEvent::PlaceMacro(index) => self.placing_macro = Some(index),
+            UiEvent::StampFromSelection => self.stamp_from_selection()?,
+            UiEvent::ToggleTileMode => {
+                self.execute(Event::SetTileMode(!self.inner.tile_mode()))?;
+            }
+            UiEvent::ToggleLayerVisibility => {
+                let i = self.inner.layers().active_index();
+                let visible = self.inner.layers().active().visible();
+                self.execute(Event::ChangeLayerVisibility(i, !visible))?;
+            }
+            UiEvent::MoveLayerUp => {
+                let layers = self.inner.layers();
+                let i = layers.active_index();
+                if i + 1 < layers.count() {
+                    self.execute(Event::MoveLayerUp(i))?;
+                }
+            }
+            UiEvent::MoveLayerDown => {
+                let i = self.inner.layers().active_index();
+                if i > 0 {
+                    self.execute(Event::MoveLayerDown(i))?;
+                }
+            }
+            UiEvent::ShowResizeWindow => self.gui.show_resize_window(),
+            UiEvent::ShowSpritesheetWindow => self.gui.show_spritesheet_window(),
             UiEvent::SetPreviousCursor => self.mouse.set_cursor(self.prev_cursor),
             UiEvent::SetCursor(c) => {
                 self.prev_cursor = self.mouse.cursor();
@@ -376,27 +681,56 @@ impl UiState {
             }
             // TODO: this used to be in mouse.rs, now it's cluttering this
             // module, we should move it somewhere else
+            UiEvent::ToolStart if self.placing_macro.is_some() && !self.is_canvas_blocked() => {
+                if let Some(index) = self.placing_macro.take() {
+                    let offset = self
+                        .macros
+                        .get(index)
+                        .map_or(Point::ZERO, |m| p - m.origin());
+                    self.placed_macro = true;
+                    self.replay_macro(index, offset)?;
+                }
+            }
             UiEvent::ToolStart => match (self.selected_tool(), self.is_canvas_blocked()) {
                 (Tool::Brush, false) => self.execute(Event::BrushStart)?,
                 (Tool::Eraser, false) => self.execute(Event::EraseStart)?,
                 (Tool::Line, false) => self.execute(Event::LineStart(p))?,
                 (Tool::Rectangle, false) => self.execute(Event::RectStart(p))?,
                 (Tool::Ellipse, false) => self.execute(Event::EllipseStart(p))?,
+                (Tool::Polyline | Tool::Polygon | Tool::Bezier, false) => self.path_click(p)?,
                 (Tool::Bucket, false) => self.execute(Event::Bucket(p))?,
-                (Tool::Selection, false) => self.execute(Event::StartSelection(p))?,
+                (Tool::Selection | Tool::EllipseSelection, false) => {
+                    self.execute(Event::StartSelection(p))?;
+                }
+                (Tool::MagicWand, false) => self.execute(Event::MagicWand(p))?,
+                (Tool::Lasso, false) => self.execute(Event::PathPoint(p))?,
+                (Tool::PolygonLasso, false) => {
+                    self.path_click(p)?;
+                    if self.inner.path().is_empty() {
+                        self.execute(Event::SetTool(Tool::Move))?;
+                    }
+                }
                 (Tool::Move, false) => self.execute(Event::MoveStart(p))?,
                 (Tool::Eyedropper, false) => {
-                    if self.canvas().is_in_bounds(p) {
-                        let color = self.visible_pixel(p);
+                    if self.is_on_canvas(p) {
+                        let color = self.visible_pixel(self.tile(p));
                         self.execute(Event::SetMainColor(color.into()))?;
                         self.execute(Event::SetTool(Tool::Brush))?;
                     }
                 }
                 _ => (),
             },
+            UiEvent::ToolStroke if self.placed_macro => (),
+            UiEvent::ToolEnd if self.placed_macro => self.placed_macro = false,
             UiEvent::ToolStroke => match (self.selected_tool(), self.is_canvas_blocked()) {
                 (Tool::Brush, false) => self.execute(Event::BrushStroke(p))?,
                 (Tool::Eraser, false) => self.execute(Event::Erase(p))?,
+                (Tool::Lasso, false) => self.execute(Event::PathPoint(p))?,
+                (Tool::Bezier, false) => {
+                    if let Some(i) = self.dragged_point {
+                        self.execute(Event::MovePathPoint(i, p))?;
+                    }
+                }
                 _ => (),
             },
             UiEvent::ToolEnd => match (self.selected_tool(), self.is_canvas_blocked()) {
@@ -405,10 +739,15 @@ impl UiState {
                 (Tool::Line, false) => self.execute(Event::LineEnd(p))?,
                 (Tool::Rectangle, false) => self.execute(Event::RectEnd(p))?,
                 (Tool::Ellipse, false) => self.execute(Event::EllipseEnd(p))?,
-                (Tool::Selection, false) => {
+                (Tool::Selection | Tool::EllipseSelection, false) => {
                     self.execute(Event::EndSelection(p))?;
                     self.execute(Event::SetTool(Tool::Move))?;
                 }
+                (Tool::Lasso, false) => {
+                    self.execute(Event::PathEnd)?;
+                    self.execute(Event::SetTool(Tool::Move))?;
+                }
+                (Tool::MagicWand, false) => self.execute(Event::SetTool(Tool::Move))?,
                 (Tool::Move, false) => {
                     if self.is_mouse_on_selection() {
                         self.execute(Event::MoveEnd(p))?;
@@ -416,6 +755,7 @@ impl UiState {
                         self.execute(Event::ClearSelection)?;
                     }
                 }
+                (Tool::Bezier, _) => self.dragged_point = None,
                 _ => (),
             },
         };
@@ -423,6 +763,54 @@ impl UiState {
         Ok(())
     }
 
+    /// Add a point to the path of the polyline, polygon, Bezier or polygon
+    /// lasso tool, or finish it on a double click. Once the ends of a Bezier
+    /// curve are set, a click on one of its control points starts dragging it
+    /// instead.
+    fn path_click(&mut self, p: Point<i32>) -> Result<()> {
+        let double_click = !self.double_click.expired() && self.last_click == p;
+        self.last_click = p;
+        self.double_click.start(DOUBLE_CLICK_MS);
+
+        if double_click {
+            self.double_click = Timer::new();
+            return self.execute(Event::PathEnd);
+        }
+
+        let path = self.inner.path();
+        if self.selected_tool() == Tool::Bezier && path.len() > 1 {
+            let reach = (graphics::BEZIER_HANDLE_SIZE / self.zoom).max(1.);
+            self.dragged_point = path
+                .iter()
+                .position(|q| lapix::graphics::distance(p, *q) <= reach);
+            return Ok(());
+        }
+
+        self.execute(Event::PathPoint(p))
+    }
+
+    /// Whether a point is on the canvas, or in tile mode, on one of the
+    /// copies of the canvas shown around it
+    fn is_on_canvas(&self, p: Point<i32>) -> bool {
+        let size = self.canvas().size();
+
+        if self.inner.tile_mode() {
+            Rect::new(-size.x, -size.y, 3 * size.x, 3 * size.y).contains(p.x, p.y)
+        } else {
+            self.canvas().is_in_bounds(p)
+        }
+    }
+
+    /// The pixel of the canvas a point stands for, which in tile mode may be
+    /// on a copy of the canvas
+    fn tile(&self, p: Point<i32>) -> Point<i32> {
+        if self.inner.tile_mode() {
+            self.canvas().wrap(p)
+        } else {
+            p
+        }
+    }
+
     pub fn visible_pixel(&self, p: Point<i32>) -> [u8; 4] {
         self.inner.layers().visible_pixel(p).into()
     }
@@ -492,11 +880,11 @@ impl UiState {
     }
 
     pub fn reset_zoom(&mut self) {
-        self.zoom = DEFAULT_ZOOM_LEVEL;
+        self.zoom = self.settings.zoom;
     }
 
     pub fn move_camera(&mut self, direction: Direction) {
-        let speed = CAMERA_SPEED;
+        let speed = self.settings.camera_speed;
 
         if !self.is_camera_off(direction) {
             match direction {
@@ -561,12 +949,15 @@ impl UiState {
         let (x, y) = macroquad::prelude::mouse_position();
         let (x, y) = self.screen_to_canvas(x, y);
 
-        let rect = match self.inner.selection() {
-            Some(Selection::FreeImage) => self.inner.free_image().unwrap().rect,
-            Some(Selection::Canvas(rect)) => rect,
-            _ => return false,
-        };
+        let p = Point::new(x, y);
 
-        rect.contains(x, y)
+        match self.inner.selection() {
+            Some(Selection::FreeImage) => {
+                let img = self.inner.free_image().unwrap();
+                img.mask.contains(p - img.rect.pos())
+            }
+            Some(Selection::Canvas(mask)) => mask.contains(p),
+            _ => false,
+        }
     }
 }
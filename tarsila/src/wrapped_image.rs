@@ -33,20 +33,13 @@ impl<'a> Deserialize<'a> for WrappedImage {
 
 impl Bitmap for WrappedImage {
     fn new(size: Size<i32>, color: Color) -> Self {
-        let bytes = vec![0; size.x as usize * size.y as usize * 4];
-        let mut img = Self(Image {
+        let bytes = [color.r, color.g, color.b, color.a].repeat(size.x as usize * size.y as usize);
+
+        Self(Image {
             bytes,
             width: size.x as u16,
             height: size.y as u16,
-        });
-
-        for i in 0..size.x {
-            for j in 0..size.y {
-                img.set_pixel((i, j).into(), color);
-            }
-        }
-
-        img
+        })
     }
 
     fn size(&self) -> Size<i32> {
@@ -88,6 +81,12 @@ impl Bitmap for WrappedImage {
         &self.0.bytes
     }
 
+    fn row_mut(&mut self, y: i32) -> Option<&mut [u8]> {
+        let len = self.0.width as usize * 4;
+
+        Some(&mut self.0.bytes[y as usize * len..][..len])
+    }
+
     fn from_parts(size: Size<i32>, bytes: &[u8]) -> Self {
         Self(Image {
             bytes: bytes.to_owned(),